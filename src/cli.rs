@@ -1,5 +1,12 @@
-use clap::{Parser, ValueEnum};
+use crate::region_loader::chunk_loader::chunk_status::ChunkStatus;
+use crate::region_loader::chunk_loader::compression_scheme::CompressionScheme;
+use crate::region_loader::compression_level::CompressionLevel;
+use crate::world::dimension::Dimension;
+use crate::world::modified_since::parse_cutoff;
+use clap::{ArgAction, Parser, ValueEnum};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::time::SystemTime;
 
 #[derive(Parser)]
 #[command(
@@ -13,29 +20,394 @@ pub struct Cli {
     #[arg(value_enum, required = true)]
     pub mode: Mode,
 
-    /// Path to your Minecraft Worlds containing `level.dat` file
+    /// Path to your Minecraft Worlds containing `level.dat` file, or to single `.mca` region files
     #[arg(required = true)]
     pub world_paths: Vec<PathBuf>,
 
+    /// Read option defaults from this config file of `option = value` lines, such as `compression-level = 9`.
+    /// `MINECRAFT_WORLD_TRIMMER_<OPTION>` environment variables override it, and the command line overrides both
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
     /// Compression level when writing region files
-    #[arg(short, long, default_value = "6", value_parser = validate_compression_level)]
-    pub compression_level: u32,
+    #[arg(short, long, default_value = "6")]
+    pub compression_level: CompressionLevel,
+
+    /// Increase logging verbosity (`-v` for info, `-vv` for per-chunk debug decisions)
+    #[arg(short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Keep region files left without chunks as valid empty (header-only) regions instead of deleting them
+    #[arg(long)]
+    pub keep_empty_regions: bool,
+
+    /// Also delete never inhabited nether chunks containing a nether portal, even when fully generated.
+    /// Portal travel keeps generating such chunks around the exit portals
+    #[arg(long)]
+    pub aggressive_nether: bool,
+
+    /// Output format of check and write. `jsonl` prints one JSON object per processed chunk on stdout
+    /// (region, coordinates, keep or delete and why) and moves the summary to stderr
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+
+    /// In check mode, print the decision taken for every chunk with the rule that took it (fully generated,
+    /// inhabited, --biome, --keep-structures...) and its status and inhabited time, to tell which option saves or
+    /// deletes a chunk
+    #[arg(long, conflicts_with = "format")]
+    pub explain: bool,
+
+    /// Format of the summary printed after check and write. `csv` prints a header and a single row (timestamp,
+    /// worlds, total_chunks, deleted_chunks, deleted_regions, bytes_saved) for a spreadsheet of runs
+    #[arg(long, value_enum, default_value = "text")]
+    pub report_format: ReportFormat,
+
+    /// In write mode, only rewrite a modified region if it shrinks by at least this percentage.
+    /// Check mode does not compress chunks, so it ignores this setting
+    #[arg(long, value_name = "PERCENT", default_value = "0", value_parser = validate_percentage)]
+    pub min_savings: f64,
+
+    /// In check and write modes, limit how many regions are processed at once so that their estimated memory use
+    /// stays under this many MiB. Slower, but avoids running out of memory on worlds with big regions
+    #[arg(long, value_name = "MIB", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_memory: Option<u64>,
+
+    /// In check mode, also print every chunk that would be deleted as `region,x,z` CSV lines on stdout, which
+    /// locates the chunks matched by `--biome`, `--min-sections` or `--max-sections` before trimming them.
+    /// In snapshot mode, print every chunk that appeared or disappeared as `change,region,x,z` lines,
+    /// and in diff mode every chunk that changed as well
+    #[arg(long)]
+    pub list: bool,
+
+    /// In check mode, also draw every chunk as a pixel of a PNG map, green if kept and red if it would be deleted.
+    /// The nether and the end get their own map next to it, suffixed with `-nether` and `-end`
+    #[arg(long, value_name = "PATH")]
+    pub map: Option<PathBuf>,
+
+    /// In check mode, save the chunks write would delete to this file as a deletion plan (JSON Lines) to review.
+    /// In write mode, delete exactly the chunks of this plan instead of deciding again
+    #[arg(long, value_name = "PATH")]
+    pub plan: Option<PathBuf>,
+
+    /// In snapshot mode, save the coordinates of every chunk of the worlds to this file, as `region,x,z` CSV lines
+    #[arg(long, value_name = "PATH")]
+    pub save_snapshot: Option<PathBuf>,
+
+    /// In snapshot mode, report the chunks that appeared or disappeared since this earlier snapshot. It is read
+    /// before `--save-snapshot` writes, so both can name the same file
+    #[arg(long, value_name = "PATH")]
+    pub compare_snapshot: Option<PathBuf>,
+
+    /// In snapshot mode, fail unless the chunks that disappeared since `--compare-snapshot` are exactly the chunks of
+    /// this file, such as the `--list` output of the check run before trimming
+    #[arg(long, value_name = "PATH", requires = "compare_snapshot")]
+    pub expect_removed: Option<PathBuf>,
+
+    /// Field delimiter of the `--list` CSV lines and snapshots, e.g. `;` for spreadsheets of locales using decimal commas.
+    /// Region paths holding the delimiter or quotes are quoted
+    #[arg(long, value_name = "CHAR", default_value = ",", value_parser = validate_csv_delimiter)]
+    pub csv_delimiter: char,
+
+    /// In check mode, also run write on every region file without modifying anything, and fail if it would not
+    /// delete what check predicts
+    #[arg(long)]
+    pub self_check: bool,
+
+    /// In check or write mode, exit with code 3 when chunks or regions would be or were deleted (or sections,
+    /// entities and POI removed), to detect drift in CI. Errors still exit with code 1
+    #[arg(long)]
+    pub fail_on_change: bool,
+
+    /// After check, write or stats, also list the N region files still taking the most disk space, with their chunk
+    /// counts, to find where a world is bloated
+    #[arg(long, value_name = "N")]
+    pub report_top_regions: Option<usize>,
+
+    /// After check or write, append a timestamped summary line (chunk and region counts, bytes saved) to this file.
+    /// In check mode the saved bytes are a prediction
+    #[arg(long, value_name = "PATH")]
+    pub summary_file: Option<PathBuf>,
+
+    /// In write mode, remember in this directory the regions in which nothing had to change, and skip them in the
+    /// next runs with the same options as long as their size and modification time stay the same
+    #[arg(long, value_name = "DIR")]
+    pub cache: Option<PathBuf>,
+
+    /// In write and browse modes, copy the worlds into this directory first and trim the copies, leaving the
+    /// originals untouched. Each world is copied under its folder name, which must not exist there yet
+    #[arg(long, value_name = "DIR", conflicts_with = "plan")]
+    pub output_dir: Option<PathBuf>,
+
+    /// In palette mode, only report the blocks of the ids listed in this file, one per line (`#` starts a comment).
+    /// Ids without a namespace are taken from `minecraft:`
+    #[arg(long, value_name = "PATH")]
+    pub ids_from: Option<PathBuf>,
+
+    /// Delete chunks containing this biome (e.g. `custom:removed_biome` of a removed datapack), even when fully
+    /// generated or inhabited. Can be repeated
+    #[arg(long = "biome", value_name = "ID")]
+    pub delete_biomes: Vec<String>,
+
+    /// Delete chunks not saved for more than this many game ticks, compared with the current `Time` of the
+    /// world's `level.dat` (72000 ticks is one hour of play), even when fully generated or inhabited
+    #[arg(long, value_name = "TICKS", value_parser = clap::value_parser!(i64).range(0..))]
+    pub stale_update: Option<i64>,
+
+    /// Delete chunks with fewer sections (16 blocks high slices) holding anything but air than this, such as
+    /// nearly empty chunks, even when fully generated or inhabited
+    #[arg(long, value_name = "N")]
+    pub min_sections: Option<usize>,
+
+    /// Delete chunks with more sections holding anything but air than this, such as megastructures spanning the
+    /// build height, even when fully generated or inhabited
+    #[arg(long, value_name = "N")]
+    pub max_sections: Option<usize>,
+
+    /// Delete chunks lying entirely outside of the world border stored in the world's `level.dat`, even when fully
+    /// generated or inhabited. The nether border is the overworld one divided by 8
+    #[arg(long)]
+    pub respect_border: bool,
+
+    /// Keep chunks that hold a structure start or are referenced by a structure (village, stronghold...), even when
+    /// not fully generated or inhabited, so that no structure gets partly deleted
+    #[arg(long)]
+    pub keep_structures: bool,
+
+    /// Keep chunks holding at least one block entity (chest, furnace, sign...), even when not fully generated or
+    /// inhabited, since a player probably built something there. Dungeon chests and spawners count too
+    #[arg(long)]
+    pub keep_block_entities: bool,
+
+    /// Also keep chunks whose generation reached at least this stage (e.g. `features`), for worlds in the middle of
+    /// an upgrade. Stages in order: empty, structure_starts, structure_references, biomes, noise, surface, carvers,
+    /// liquid_carvers, features, initialize_light, light, spawn, heightmaps, full
+    #[arg(long, value_name = "STATUS", value_parser = validate_min_status)]
+    pub min_status: Option<ChunkStatus>,
+
+    /// Ignore `InhabitedTime` and only keep fully generated chunks, to clean up a freshly explored world where
+    /// players passed through many chunks still being generated
+    #[arg(long)]
+    pub ignore_inhabited: bool,
+
+    /// Remove the sections (16 blocks high slices) lying entirely below this Y from the kept 1.18+ chunks, along
+    /// with their block entities, and rebuild the heightmaps. The chunks stay, the game loads the removed sections
+    /// as air. Use check mode first to count the sections that would go
+    #[arg(long, value_name = "Y", allow_negative_numbers = true)]
+    pub delete_below_y: Option<i32>,
+
+    /// Delete chunks that parse but have no position (`xPos`/`zPos`). They are always reported, and otherwise keep
+    /// their region from being rewritten since the rewritten file would have no place for them
+    #[arg(long)]
+    pub delete_malformed: bool,
+
+    /// In write mode, write the chunks of rewritten regions to disk as they are compressed instead of once the whole
+    /// region is compressed, so that compression and disk writes overlap and the compressed region is never held in
+    /// memory whole. Helps on slow storage such as network or spinning disks. Gzipped regions are still compressed
+    /// whole
+    #[arg(long)]
+    pub stream_writes: bool,
+
+    /// In write mode, parse every rewritten region file again before it replaces the original, and leave the
+    /// original untouched if it does not parse or misses chunks
+    #[arg(long)]
+    pub verify_after_write: bool,
+
+    /// In write mode, give rewritten region files back their previous modification time, so that backup tools
+    /// comparing modification times do not upload them again
+    #[arg(long)]
+    pub preserve_mtime: bool,
+
+    /// In write mode, keep the points of interest of `poi/` (beds, workstations, nether portals...) consistent with the
+    /// trimmed regions: delete the POI data of deleted chunks and the records pointing outside of their chunk
+    #[arg(long)]
+    pub trim_poi: bool,
+
+    /// In write mode, remove the entities of these types (e.g. `item,item_frame,armor_stand`) from the `entities/`
+    /// region files of the trimmed regions, keeping their chunks. Ids without a namespace are taken from `minecraft:`
+    #[arg(long, value_name = "IDS", value_delimiter = ',', value_parser = validate_entity_type)]
+    pub trim_entity_types: Vec<String>,
+
+    /// Decode chunks whose compression scheme byte is not a standard one as one of the known schemes, e.g. `zlib:5`
+    /// for a server fork saving zlib chunks with scheme byte 5. Can be repeated. Chunks are rewritten with the
+    /// standard byte of their scheme
+    #[arg(long, value_name = "SCHEME:BYTE", value_parser = validate_assumed_scheme)]
+    pub assume_scheme: Vec<(CompressionScheme, u8)>,
+
+    /// Process everything sequentially on the main thread, printing each region file before processing it.
+    /// Much slower, meant to find which region file makes the program crash
+    #[arg(long)]
+    pub single_thread: bool,
+
+    /// Skip the confirmation prompt of the modes modifying the worlds (write, salvage, fix-heightmaps), for scripts
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Also look for worlds (folders with a `level.dat`) up to this many directory levels below the given paths,
+    /// e.g. 1 to pass a server root holding `world`, `world_nether` and `world_the_end`
+    #[arg(long, value_name = "N", default_value = "0")]
+    pub max_depth: usize,
+
+    /// Find the worlds below the given paths by their `level.dat`, however deep they are, and also process the
+    /// regions of their datapack dimensions (`dimensions/<namespace>/<name>/region`). Point it at a server root and
+    /// run with `-v` to list the worlds found
+    #[arg(long, conflicts_with = "max_depth")]
+    pub paths_from_level_dat: bool,
+
+    /// Only process region files whose name matches one of these glob patterns (e.g. `r.0.*.mca`)
+    #[arg(long = "include-glob", value_name = "PATTERN")]
+    pub include_globs: Vec<String>,
+
+    /// Only process region files modified after this time, for incremental maintenance: a duration ago (`90s`, `30m`,
+    /// `12h`, `2d`, `1w`), a UTC date (`2024-05-01`, `2024-05-01T03:00:00Z`) or a Unix timestamp
+    #[arg(long, value_name = "CUTOFF", value_parser = validate_modified_since)]
+    pub modified_since: Option<SystemTime>,
+
+    /// Leave the region files of these dimensions untouched: `overworld`, `nether` or `end`. Can be repeated or
+    /// comma separated, e.g. to trim the nether on a different schedule than the overworld
+    #[arg(long, value_name = "DIMENSION", value_delimiter = ',', value_parser = validate_dimension)]
+    pub skip_dimensions: Vec<Dimension>,
+
+    /// Skip region files whose name matches one of these glob patterns (e.g. `*.bak`)
+    #[arg(long = "exclude-glob", value_name = "PATTERN")]
+    pub exclude_globs: Vec<String>,
+
+    /// Only process the first N region files, in path order, to try settings on part of a huge world before
+    /// running on all of it
+    #[arg(long, value_name = "N")]
+    pub trial_regions: Option<NonZeroUsize>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum Mode {
+    /// Opens a map of the chunks of each region file in the terminal, to mark chunks or whole regions and delete
+    /// them like write does. Needs a build with the `tui` feature, make a backup of your worlds before running.
+    Browse,
+
     /// Only counts of region files and chunks that can be deleted without making any change to the world
     Check,
 
+    /// Compares the chunks of two worlds, such as a backup and the live world, and reports which chunks changed,
+    /// appeared or disappeared. Makes no change to the worlds
+    Diff,
+
+    /// Rebuilds the `WORLD_SURFACE` and `MOTION_BLOCKING` heightmaps of fully generated chunks from their blocks,
+    /// for worlds whose blocks were edited by other tools. Make a backup of your worlds before running.
+    FixHeightmaps,
+
+    /// Prints how many blocks of each id the given region files (`.mca`) hold, most common first, to find what
+    /// makes a region so large. Makes no change to the files
+    Palette,
+
+    /// Rebuilds region files from every chunk that can still be decoded in them, ignoring their location table.
+    /// Last resort for regions whose header got destroyed, make a backup of your worlds before running.
+    Salvage,
+
+    /// Records which chunks the worlds hold, from the region headers only, and compares them with an earlier
+    /// snapshot to tell which chunks appeared or disappeared. Makes no change to the world
+    Snapshot,
+
+    /// Only reads the region headers to count chunks and used sectors, without decompressing any chunk.
+    /// Much faster than check, but cannot tell which chunks could be deleted.
+    Stats,
+
+    /// Runs integrity checks without making any change to the world,
+    /// such as chunks present in `region/` but not in `entities/` (or vice versa)
+    Verify,
+
+    /// Reports which Minecraft versions saved the chunks of each world (`DataVersion`), from a sample of chunks
+    /// of every region, without making any change. Shows if a world was only partly upgraded
+    Versions,
+
     /// Optimizes the world by deleting unused region files and chunks.
     /// This is a destructive process, make sure to make a backup of your worlds before running.
     /// Also make sure the world is not loaded by the game as this will corrupt the world.
     Write,
 }
 
-fn validate_compression_level(s: &str) -> Result<u32, String> {
-    match s.parse::<u32>() {
-        Ok(level) if level <= 9 => Ok(level),
-        _ => Err("Compression level must be an integer between 0 and 9".to_string()),
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable summary
+    Text,
+
+    /// JSON Lines describing every chunk decision
+    Jsonl,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Human readable summary
+    Text,
+
+    /// CSV header and row, using `--csv-delimiter`
+    Csv,
+}
+
+fn validate_percentage(s: &str) -> Result<f64, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|percentage| (0.0..=100.0).contains(percentage))
+        .ok_or_else(|| "Percentage must be a number between 0 and 100".to_string())
+}
+
+fn validate_min_status(s: &str) -> Result<ChunkStatus, String> {
+    ChunkStatus::from_name(s).ok_or_else(|| {
+        let names: Vec<&str> = ChunkStatus::LADDER
+            .iter()
+            .map(|status| status.name())
+            .collect();
+        format!(
+            "Unknown chunk status, expected one of: {}",
+            names.join(", ")
+        )
+    })
+}
+
+fn validate_dimension(s: &str) -> Result<Dimension, String> {
+    Dimension::from_name(s)
+        .ok_or_else(|| "Unknown dimension, expected overworld, nether or end".to_string())
+}
+
+fn validate_assumed_scheme(s: &str) -> Result<(CompressionScheme, u8), String> {
+    let (name, byte) = s
+        .split_once(':')
+        .ok_or_else(|| format!("Expected <SCHEME>:<BYTE>, e.g. `zlib:5`, got {s:?}"))?;
+    let scheme = CompressionScheme::from_name(name).ok_or_else(|| {
+        format!("Unknown scheme {name:?}, expected zlib, gzip, lz4 or uncompressed")
+    })?;
+    match byte.parse::<u8>() {
+        // 1 to 4 are the standard schemes, and the highest bit flags chunks stored in `.mcc` files
+        Ok(byte @ 5..=127) => Ok((scheme, byte)),
+        _ => Err(format!(
+            "Invalid scheme byte {byte:?}, expected a number from 5 to 127"
+        )),
     }
 }
+
+fn validate_entity_type(s: &str) -> Result<String, String> {
+    let id = s.trim();
+    if id.is_empty() || id.contains(char::is_whitespace) {
+        return Err(format!(
+            "Invalid entity id {s:?}, expected e.g. `item` or `minecraft:item_frame`"
+        ));
+    }
+    Ok(if id.contains(':') {
+        id.to_string()
+    } else {
+        format!("minecraft:{id}")
+    })
+}
+
+fn validate_csv_delimiter(s: &str) -> Result<char, String> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(delimiter), None) if !matches!(delimiter, '"' | '\n' | '\r') => Ok(delimiter),
+        _ => Err(
+            "CSV delimiter must be a single character other than a quote or a line break"
+                .to_string(),
+        ),
+    }
+}
+
+fn validate_modified_since(s: &str) -> Result<SystemTime, String> {
+    parse_cutoff(s, SystemTime::now())
+}
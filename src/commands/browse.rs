@@ -0,0 +1,563 @@
+use crate::commands::deletion_plan::DeletionPlan;
+use crate::commands::optimize_result::OptimizeResult;
+use crate::commands::progress::new_spinner;
+use crate::commands::trim_options::{RegionContext, TrimOptions};
+use crate::commands::write::write_worlds;
+use crate::region_loader::region::Region;
+use crate::world::get_region_files::{get_region_files, warn_if_no_region_files};
+use crate::world::scan_options::ScanOptions;
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+/// Chunks per side of a region
+const REGION_SIZE: usize = 32;
+
+/// What the map shows of a chunk
+struct ChunkInfo {
+    position: (i32, i32),
+    status: Option<&'static str>,
+    /// Game ticks players spent near the chunk
+    inhabited_time: i64,
+    data_version: Option<i32>,
+    /// Why trim keeps the chunk with the given options, `None` when it would delete it
+    keep_reason: Option<&'static str>,
+}
+
+/// Chunks of a region file by their position inside the region, `(0, 0)` being the north-west corner
+struct RegionChunks {
+    chunks: BTreeMap<(usize, usize), ChunkInfo>,
+    /// Chunks that cannot be decoded or have no position, they are not on the map and cannot be marked
+    unmapped: usize,
+}
+
+impl RegionChunks {
+    fn read(
+        region_file_path: &Path,
+        options: &TrimOptions,
+        context: &RegionContext,
+    ) -> Result<Self, String> {
+        let region = Region::from_file_name(region_file_path, &options.schemes)
+            .map_err(|err| format!("Cannot read the region: {err}"))?;
+        let mut chunks = BTreeMap::new();
+        let mut unmapped = region.get_parse_failures().len();
+        for chunk in region.get_chunks() {
+            let Ok((x, z)) = chunk.get_position() else {
+                unmapped += 1;
+                continue;
+            };
+            let cell = (
+                x.rem_euclid(REGION_SIZE as i32) as usize,
+                z.rem_euclid(REGION_SIZE as i32) as usize,
+            );
+            let info = ChunkInfo {
+                position: (x, z),
+                status: chunk.status().map(|status| status.name()),
+                inhabited_time: chunk.inhabited_time(),
+                data_version: chunk.data_version(),
+                keep_reason: options.keep_reason(chunk, context),
+            };
+            chunks.insert(cell, info);
+        }
+        Ok(Self { chunks, unmapped })
+    }
+}
+
+struct RegionEntry {
+    path: PathBuf,
+    /// Path relative to its world, shown in the list
+    label: String,
+    /// Read when the region is first shown
+    chunks: Option<Result<RegionChunks, String>>,
+}
+
+/// What a key asks the browser to do next
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Continue,
+    Quit,
+    /// Leave the browser and delete the marked chunks
+    Commit,
+}
+
+/// State of the browse mode: the region shown, the chunk under the cursor and the chunks marked for deletion
+struct Browser<'a> {
+    options: &'a TrimOptions,
+    /// By the directory of the region files, every region of a directory shares the same world and dimension
+    contexts: HashMap<PathBuf, RegionContext>,
+    regions: Vec<RegionEntry>,
+    list_state: ListState,
+    /// Cell of the chunk under the cursor
+    cursor: (usize, usize),
+    /// Positions of the marked chunks, by index of their region
+    marked: BTreeMap<usize, BTreeSet<(i32, i32)>>,
+    message: String,
+}
+
+impl<'a> Browser<'a> {
+    fn new(regions: Vec<RegionEntry>, options: &'a TrimOptions) -> Self {
+        // Read before the terminal is taken over, the warnings of the options would garble the screen after
+        let mut contexts = HashMap::new();
+        for region in &regions {
+            contexts
+                .entry(directory_of(&region.path))
+                .or_insert_with(|| options.region_context(&region.path));
+        }
+        let mut browser = Self {
+            options,
+            contexts,
+            regions,
+            list_state: ListState::default(),
+            cursor: (0, 0),
+            marked: BTreeMap::new(),
+            message: String::new(),
+        };
+        browser.select_region(0);
+        browser
+    }
+
+    fn selected(&self) -> usize {
+        self.list_state.selected().unwrap_or(0)
+    }
+
+    fn select_region(&mut self, index: usize) {
+        let Some(region) = self.regions.get_mut(index) else {
+            return;
+        };
+        self.list_state.select(Some(index));
+        if region.chunks.is_none() {
+            let context = &self.contexts[&directory_of(&region.path)];
+            region.chunks = Some(RegionChunks::read(&region.path, self.options, context));
+        }
+    }
+
+    fn region_chunks(&self) -> Option<&RegionChunks> {
+        self.regions
+            .get(self.selected())?
+            .chunks
+            .as_ref()?
+            .as_ref()
+            .ok()
+    }
+
+    fn chunk_under_cursor(&self) -> Option<&ChunkInfo> {
+        self.region_chunks()?.chunks.get(&self.cursor)
+    }
+
+    fn handle_key(&mut self, key: KeyCode) -> Action {
+        self.message.clear();
+        let last = REGION_SIZE - 1;
+        let (x, z) = self.cursor;
+        match key {
+            KeyCode::Left | KeyCode::Char('h') => self.cursor = (x.saturating_sub(1), z),
+            KeyCode::Right | KeyCode::Char('l') => self.cursor = ((x + 1).min(last), z),
+            KeyCode::Up | KeyCode::Char('k') => self.cursor = (x, z.saturating_sub(1)),
+            KeyCode::Down | KeyCode::Char('j') => self.cursor = (x, (z + 1).min(last)),
+            KeyCode::PageUp | KeyCode::Char('[') => {
+                self.select_region(self.selected().saturating_sub(1))
+            }
+            KeyCode::PageDown | KeyCode::Char(']') => self.select_region(self.selected() + 1),
+            KeyCode::Char(' ') => self.toggle_chunk(),
+            KeyCode::Char('a') => self.toggle_region(),
+            KeyCode::Char('t') => self.mark_trimmed(),
+            KeyCode::Char('w') if self.marked_count() == 0 => {
+                self.message = "No chunk is marked".to_string()
+            }
+            KeyCode::Char('w') => return Action::Commit,
+            KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    fn toggle_chunk(&mut self) {
+        let Some(position) = self.chunk_under_cursor().map(|chunk| chunk.position) else {
+            self.message = "There is no chunk here".to_string();
+            return;
+        };
+        let marked = self.marked.entry(self.selected()).or_default();
+        if !marked.remove(&position) {
+            marked.insert(position);
+        }
+    }
+
+    /// Marks every chunk of the region, or unmarks them all when they already are
+    fn toggle_region(&mut self) {
+        let Some(chunks) = self.region_chunks() else {
+            return;
+        };
+        let positions: BTreeSet<(i32, i32)> =
+            chunks.chunks.values().map(|chunk| chunk.position).collect();
+        let marked = self.marked.entry(self.selected()).or_default();
+        if *marked == positions {
+            marked.clear();
+        } else {
+            *marked = positions;
+        }
+    }
+
+    /// Marks the chunks of the region that trim would delete with the given options
+    fn mark_trimmed(&mut self) {
+        let Some(chunks) = self.region_chunks() else {
+            return;
+        };
+        let trimmed: Vec<(i32, i32)> = chunks
+            .chunks
+            .values()
+            .filter(|chunk| chunk.keep_reason.is_none())
+            .map(|chunk| chunk.position)
+            .collect();
+        self.message = format!("Marked the {} chunk(s) trim would delete", trimmed.len());
+        self.marked
+            .entry(self.selected())
+            .or_default()
+            .extend(trimmed);
+    }
+
+    fn marked_count(&self) -> usize {
+        self.marked.values().map(BTreeSet::len).sum()
+    }
+
+    /// The marked chunks as a plan for write, by canonical region path like the plans saved by check
+    fn deletion_plan(&self) -> DeletionPlan {
+        let mut plan = DeletionPlan::new();
+        for (&index, positions) in self
+            .marked
+            .iter()
+            .filter(|(_, positions)| !positions.is_empty())
+        {
+            let path = &self.regions[index].path;
+            let region = std::fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            plan.entry(region).or_default().extend(positions);
+        }
+        plan
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, help] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [list, right] =
+            Layout::horizontal([Constraint::Length(36), Constraint::Min(0)]).areas(main);
+        let map_size = REGION_SIZE as u16 + 2;
+        let [map, details] =
+            Layout::vertical([Constraint::Length(map_size), Constraint::Min(0)]).areas(right);
+        let map_width = 2 * REGION_SIZE as u16 + 2;
+        let [map, _] =
+            Layout::horizontal([Constraint::Length(map_width), Constraint::Min(0)]).areas(map);
+
+        let items: Vec<ListItem> = self
+            .regions
+            .iter()
+            .enumerate()
+            .map(
+                |(index, region)| match self.marked.get(&index).map(BTreeSet::len).unwrap_or(0) {
+                    0 => ListItem::new(region.label.as_str()),
+                    marked => ListItem::new(format!("{} ({marked} marked)", region.label)).red(),
+                },
+            )
+            .collect();
+        let regions = List::new(items)
+            .block(Block::bordered().title(format!(" Regions ({}) ", self.regions.len())))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(regions, list, &mut self.list_state);
+
+        frame.render_widget(
+            Paragraph::new(self.map_lines()).block(Block::bordered().title(" Chunks ")),
+            map,
+        );
+        frame.render_widget(
+            Paragraph::new(self.detail_lines()).block(Block::bordered().title(" Details ")),
+            details,
+        );
+        let help_line = if self.message.is_empty() {
+            "arrows move  [ ] region  space mark  a whole region  t trim rules  w delete marked  q quit"
+        } else {
+            self.message.as_str()
+        };
+        frame.render_widget(Paragraph::new(help_line), help);
+    }
+
+    /// Two characters per chunk, north up: green kept by trim, yellow deleted by trim, red marked.
+    /// Cells without a chunk are dots
+    fn map_lines(&self) -> Vec<Line<'static>> {
+        let Some(chunks) = self.region_chunks() else {
+            return Vec::new();
+        };
+        let marked = self.marked.get(&self.selected());
+        (0..REGION_SIZE)
+            .map(|z| {
+                let cells = (0..REGION_SIZE).map(|x| {
+                    let (text, color) = match chunks.chunks.get(&(x, z)) {
+                        None => ("· ", Color::DarkGray),
+                        Some(chunk)
+                            if marked.is_some_and(|marked| marked.contains(&chunk.position)) =>
+                        {
+                            ("██", Color::Red)
+                        }
+                        Some(chunk) if chunk.keep_reason.is_none() => ("██", Color::Yellow),
+                        Some(_) => ("██", Color::Green),
+                    };
+                    let style = Style::new().fg(color);
+                    if (x, z) == self.cursor {
+                        Span::styled("[]", style.add_modifier(Modifier::REVERSED))
+                    } else {
+                        Span::styled(text, style)
+                    }
+                });
+                Line::from(cells.collect::<Vec<_>>())
+            })
+            .collect()
+    }
+
+    fn detail_lines(&self) -> Vec<Line<'static>> {
+        let Some(region) = self.regions.get(self.selected()) else {
+            return vec![Line::from("No region file")];
+        };
+        let mut lines = vec![Line::from(region.path.display().to_string())];
+        let chunks = match &region.chunks {
+            Some(Ok(chunks)) => chunks,
+            Some(Err(err)) => {
+                lines.push(Line::from(err.clone()).red());
+                return lines;
+            }
+            None => return lines,
+        };
+        let marked = self
+            .marked
+            .get(&self.selected())
+            .map(BTreeSet::len)
+            .unwrap_or(0);
+        lines.push(Line::from(format!(
+            "{} chunk(s), {marked} marked here, {} marked in every region",
+            chunks.chunks.len(),
+            self.marked_count()
+        )));
+        if chunks.unmapped > 0 {
+            lines.push(
+                Line::from(format!(
+                    "{} chunk(s) cannot be placed on the map",
+                    chunks.unmapped
+                ))
+                .yellow(),
+            );
+        }
+
+        let (x, z) = self.cursor;
+        let Some(chunk) = chunks.chunks.get(&self.cursor) else {
+            lines.push(Line::from(format!("No chunk at {x}, {z} in the region")));
+            return lines;
+        };
+        let (chunk_x, chunk_z) = chunk.position;
+        lines.push(Line::from(format!(
+            "Chunk {chunk_x}, {chunk_z} (blocks {}, {})",
+            chunk_x * 16,
+            chunk_z * 16
+        )));
+        lines.push(Line::from(format!(
+            "Status: {}",
+            chunk.status.unwrap_or("unknown")
+        )));
+        lines.push(Line::from(format!(
+            "Inhabited time: {} tick(s) ({:.1} s)",
+            chunk.inhabited_time,
+            chunk.inhabited_time as f64 / 20.0
+        )));
+        let data_version = chunk
+            .data_version
+            .map_or("none".to_string(), |version| version.to_string());
+        lines.push(Line::from(format!("Data version: {data_version}")));
+        lines.push(Line::from(match chunk.keep_reason {
+            Some(reason) => format!("Trim keeps it: {reason}"),
+            None => "Trim deletes it".to_string(),
+        }));
+        lines
+    }
+}
+
+/// Browses the region files of the worlds in the terminal: a map of the chunks of each region where chunks, or
+/// whole regions, are marked for deletion. The marked chunks are then deleted like a deletion plan of check, see
+/// [`crate::commands::write::execute_write`]. Returns the summary of the write, `None` when nothing was deleted
+pub fn execute_browse(
+    world_paths: &[PathBuf],
+    scan_options: &ScanOptions,
+    options: &TrimOptions,
+    assume_yes: bool,
+) -> Result<Option<OptimizeResult>, Box<dyn Error>> {
+    if !std::io::stdout().is_terminal() {
+        return Err("browse needs an interactive terminal".into());
+    }
+    let spinner = new_spinner("Scanning worlds for region files...");
+    let regions = browsed_regions(world_paths, scan_options);
+    spinner.finish_and_clear();
+    let regions = regions?;
+    warn_if_no_region_files(world_paths, regions.len());
+    if regions.is_empty() {
+        return Ok(None);
+    }
+
+    let mut browser = Browser::new(regions, options);
+    let mut terminal = ratatui::init();
+    let action = loop {
+        if let Err(err) = terminal.draw(|frame| browser.draw(frame)) {
+            break Err(err);
+        }
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                match browser.handle_key(key.code) {
+                    Action::Continue => {}
+                    action => break Ok(action),
+                }
+            }
+            Ok(_) => {}
+            Err(err) => break Err(err),
+        }
+    };
+    ratatui::restore();
+
+    if action? == Action::Quit {
+        println!("Nothing was deleted");
+        return Ok(None);
+    }
+    write_worlds(
+        world_paths,
+        scan_options,
+        options,
+        assume_yes,
+        Some(browser.deletion_plan()),
+    )
+    .map(Some)
+}
+
+/// Region files of the worlds in path order, labeled by their path relative to their world (or by their file name
+/// when given as region files) and prefixed by the name of the world when browsing several
+fn browsed_regions(
+    world_paths: &[PathBuf],
+    scan_options: &ScanOptions,
+) -> Result<Vec<RegionEntry>, Box<dyn Error>> {
+    let mut regions = Vec::new();
+    for world in world_paths {
+        let mut paths = get_region_files(std::slice::from_ref(world), scan_options)?;
+        paths.sort();
+        for path in paths {
+            let relative = path
+                .strip_prefix(world)
+                .ok()
+                .filter(|relative| !relative.as_os_str().is_empty())
+                .or_else(|| path.file_name().map(Path::new))
+                .unwrap_or(&path);
+            let label = match world.file_name() {
+                Some(world_name) if world_paths.len() > 1 && world.is_dir() => {
+                    Path::new(world_name).join(relative).display().to_string()
+                }
+                _ => relative.display().to_string(),
+            };
+            regions.push(RegionEntry {
+                path,
+                label,
+                chunks: None,
+            });
+        }
+    }
+    Ok(regions)
+}
+
+fn directory_of(region_file_path: &Path) -> PathBuf {
+    region_file_path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_loader::test_region::{TestChunk, test_region_bytes};
+
+    #[test]
+    fn test_browser_marks() {
+        let tmp_dir = std::env::temp_dir().join(format!("mwt_browse_{}", std::process::id()));
+        std::fs::create_dir_all(tmp_dir.join("region")).unwrap();
+        std::fs::write(tmp_dir.join("level.dat"), b"").unwrap();
+        let chunks = [
+            TestChunk::full(32, 0),
+            TestChunk::full(33, 0).status("minecraft:features"),
+            TestChunk::full(32, 1).inhabited_time(600),
+        ];
+        std::fs::write(tmp_dir.join("region/r.1.0.mca"), test_region_bytes(&chunks)).unwrap();
+        std::fs::write(
+            tmp_dir.join("region/r.2.0.mca"),
+            test_region_bytes(&[TestChunk::full(64, 0)]),
+        )
+        .unwrap();
+
+        let options = TrimOptions::default();
+        let regions =
+            browsed_regions(std::slice::from_ref(&tmp_dir), &ScanOptions::default()).unwrap();
+        let labels: Vec<&str> = regions.iter().map(|region| region.label.as_str()).collect();
+        assert_eq!(labels, ["region/r.1.0.mca", "region/r.2.0.mca"]);
+        let mut browser = Browser::new(regions, &options);
+
+        // Chunks are placed by their position inside the region, x to the east and z to the south
+        assert_eq!(
+            browser.chunk_under_cursor().map(|chunk| chunk.position),
+            Some((32, 0))
+        );
+        assert_eq!(browser.handle_key(KeyCode::Char('w')), Action::Continue);
+        assert_eq!(browser.handle_key(KeyCode::Down), Action::Continue);
+        browser.handle_key(KeyCode::Char(' '));
+        assert_eq!(
+            browser
+                .chunk_under_cursor()
+                .map(|chunk| chunk.inhabited_time),
+            Some(600)
+        );
+        browser.handle_key(KeyCode::Char('t'));
+        assert_eq!(browser.marked[&0], BTreeSet::from([(32, 1), (33, 0)]));
+        browser.handle_key(KeyCode::Char(' '));
+        assert_eq!(browser.marked_count(), 1);
+
+        // Nothing to mark where there is no chunk
+        browser.handle_key(KeyCode::Right);
+        browser.handle_key(KeyCode::Char(' '));
+        assert_eq!(browser.marked_count(), 1);
+
+        browser.handle_key(KeyCode::Char(']'));
+        browser.handle_key(KeyCode::Char('a'));
+        assert_eq!(browser.marked_count(), 2);
+        assert!(browser.map_lines()[0].spans[0].content.contains('█'));
+
+        let plan = browser.deletion_plan();
+        let region = |name: &str| std::fs::canonicalize(tmp_dir.join("region").join(name)).unwrap();
+        assert_eq!(plan[&region("r.1.0.mca")], BTreeSet::from([(33, 0)]));
+        assert_eq!(plan[&region("r.2.0.mca")], BTreeSet::from([(64, 0)]));
+
+        let result = write_worlds(
+            std::slice::from_ref(&tmp_dir),
+            &ScanOptions::default(),
+            &options,
+            true,
+            Some(plan),
+        );
+        let result = result.unwrap();
+        assert_eq!(
+            (
+                result.deleted_chunks,
+                result.deleted_regions,
+                result.rewritten_regions
+            ),
+            (2, 1, 1)
+        );
+        assert!(!tmp_dir.join("region/r.2.0.mca").exists());
+        assert_eq!(browser.handle_key(KeyCode::Char('w')), Action::Commit);
+        assert_eq!(browser.handle_key(KeyCode::Char('q')), Action::Quit);
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+}
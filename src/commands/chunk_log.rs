@@ -0,0 +1,176 @@
+use crate::commands::trim_options::Verdict;
+use crate::logger::{Level, enabled};
+use crate::region_loader::chunk_loader::chunk::Chunk;
+use crate::region_loader::chunk_loader::chunk_status::ChunkStatus;
+use crate::region_loader::region::{ChunkParseFailure, Region};
+use crate::{debug, info, warn};
+use std::path::Path;
+
+/// Chunks that failed to parse warned about per region, the others are only logged at info level
+const WARNED_PARSE_FAILURES: usize = 5;
+
+/// Warns about the chunks of a region that could not be parsed and would be lost by a rewrite, with their
+/// coordinates and why they failed. Past the first few of a region, they are only listed with `-v`
+pub fn warn_parse_failures(region_file_path: &Path, failures: &[ChunkParseFailure]) {
+    for (index, failure) in failures.iter().enumerate() {
+        if index < WARNED_PARSE_FAILURES {
+            warn!(
+                "{region_file_path:?}: {failure} failed to parse: {}",
+                failure.reason
+            );
+        } else {
+            info!(
+                "{region_file_path:?}: {failure} failed to parse: {}",
+                failure.reason
+            );
+        }
+    }
+    if failures.len() > WARNED_PARSE_FAILURES {
+        warn!(
+            "{region_file_path:?}: {} more chunk(s) failed to parse, run with -v to list them",
+            failures.len() - WARNED_PARSE_FAILURES
+        );
+    }
+}
+
+/// Tells if `region` must be left untouched because some of its chunks cannot be decoded, rewriting it would lose
+/// them. They are then warned about and added to `unparsable_chunks`
+pub fn skip_if_unparsable(
+    region_file_path: &Path,
+    region: &Region,
+    unparsable_chunks: &mut usize,
+) -> bool {
+    let failures = region.get_parse_failures();
+    if failures.is_empty() {
+        return false;
+    }
+    warn_parse_failures(region_file_path, failures);
+    warn!(
+        "Leaving {:?} untouched, rewriting it would lose the chunks that cannot be decoded",
+        region_file_path
+    );
+    *unparsable_chunks += failures.len();
+    true
+}
+
+/// Logs at debug level whether a chunk is kept or deleted, and why
+pub fn log_chunk_decision(region_file_path: &Path, chunk: &Chunk, keep_reason: Option<&str>) {
+    // Skip formatting the message when it would be discarded anyway
+    if !enabled(Level::Debug) {
+        return;
+    }
+
+    let position = position_text(chunk);
+    match keep_reason {
+        Some(reason) => debug!("{region_file_path:?}: keeping chunk {position}, {reason}"),
+        None => debug!("{region_file_path:?}: deleting chunk {position}, nothing to keep it for"),
+    }
+}
+
+/// Prints the decision taken for a chunk with the rule that took it and the fields the default rule looks at,
+/// to tell which option keeps or deletes it (`--explain`)
+pub fn print_chunk_explanation(region_file_path: &Path, chunk: &Chunk, verdict: Verdict) {
+    println!("{}", chunk_explanation(region_file_path, chunk, verdict));
+}
+
+fn chunk_explanation(region_file_path: &Path, chunk: &Chunk, verdict: Verdict) -> String {
+    let (action, reason) = match verdict {
+        Verdict::Keep(reason) => ("keep", reason),
+        Verdict::Delete(reason) => ("delete", reason),
+    };
+    format!(
+        "{region_file_path:?}: chunk {}: {action}, {reason} [status={}, inhabited={} ticks]",
+        position_text(chunk),
+        chunk.status().map_or("unknown", ChunkStatus::name),
+        chunk.inhabited_time()
+    )
+}
+
+fn position_text(chunk: &Chunk) -> String {
+    match chunk.get_position() {
+        Ok((x, z)) => format!("({x}, {z})"),
+        Err(_) => "(unknown position)".to_string(),
+    }
+}
+
+/// Prints the decision taken for a chunk as a single JSON line on stdout, for log ingestion tools
+pub fn print_chunk_decision_jsonl(
+    region_file_path: &Path,
+    chunk: &Chunk,
+    keep_reason: Option<&str>,
+) {
+    // A single println! call holds the stdout lock, so lines of parallel regions never interleave
+    println!(
+        "{}",
+        chunk_decision_json(region_file_path, chunk.get_position().ok(), keep_reason)
+    );
+}
+
+fn chunk_decision_json(
+    region_file_path: &Path,
+    position: Option<(i32, i32)>,
+    keep_reason: Option<&str>,
+) -> String {
+    let (x, z) = match position {
+        Some((x, z)) => (x.to_string(), z.to_string()),
+        None => ("null".to_string(), "null".to_string()),
+    };
+    let (action, reason) = match keep_reason {
+        Some(reason) => ("keep", json_string(reason)),
+        None => ("delete", "null".to_string()),
+    };
+    format!(
+        r#"{{"region":{},"x":{x},"z":{z},"action":"{action}","reason":{reason}}}"#,
+        json_string(&region_file_path.to_string_lossy())
+    )
+}
+
+pub(crate) fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_loader::test_region::TestChunk;
+
+    #[test]
+    fn test_chunk_decision_json() {
+        assert_eq!(
+            chunk_decision_json(
+                Path::new("world/region/r.0.0.mca"),
+                Some((3, -4)),
+                Some("inhabited")
+            ),
+            r#"{"region":"world/region/r.0.0.mca","x":3,"z":-4,"action":"keep","reason":"inhabited"}"#
+        );
+        assert_eq!(
+            chunk_decision_json(Path::new("C:\\saves\\\"w\"\t.mca"), None, None),
+            r#"{"region":"C:\\saves\\\"w\"\u0009.mca","x":null,"z":null,"action":"delete","reason":null}"#
+        );
+    }
+
+    #[test]
+    fn test_chunk_explanation() {
+        let chunk = TestChunk::full(3, -4)
+            .status("minecraft:features")
+            .inhabited_time(340)
+            .to_chunk();
+        assert_eq!(
+            chunk_explanation(Path::new("r.0.-1.mca"), &chunk, Verdict::Keep("inhabited")),
+            r#""r.0.-1.mca": chunk (3, -4): keep, inhabited [status=features, inhabited=340 ticks]"#
+        );
+    }
+}
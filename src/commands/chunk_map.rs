@@ -0,0 +1,163 @@
+use crate::warn;
+use crate::world::dimension::Dimension;
+use flate2::read::ZlibEncoder;
+use flate2::{Compression, Crc};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Larger maps are skipped, a few far away chunks would otherwise make gigabyte images
+const MAX_MAP_SIDE: u32 = 16_384;
+
+/// Position of a chunk and whether it would be deleted
+pub type ChunkDecision = ((i32, i32), bool);
+
+const KEPT_COLOR: [u8; 3] = [76, 175, 80];
+const DELETED_COLOR: [u8; 3] = [229, 57, 53];
+const MISSING_COLOR: [u8; 3] = [32, 32, 32];
+
+/// Writes one PNG per dimension where every chunk is a pixel, green if kept and red if deleted.
+/// The overworld goes to `map_path`, the nether and the end next to it with a `-nether` or `-end` suffix
+pub fn write_chunk_maps<'a>(
+    map_path: &Path,
+    chunks: impl IntoIterator<Item = (&'a Path, (i32, i32), bool)>,
+) {
+    let mut dimensions: HashMap<Dimension, Vec<ChunkDecision>> = HashMap::new();
+    for (region_file_path, position, deleted) in chunks {
+        dimensions
+            .entry(Dimension::from_region_path(region_file_path))
+            .or_default()
+            .push((position, deleted));
+    }
+
+    for (dimension, chunks) in dimensions {
+        let path = dimension_map_path(map_path, dimension);
+        let Some(png) = render_map(&chunks) else {
+            warn!(
+                "The chunks of {:?} span more than {MAX_MAP_SIDE} pixels, not writing the map",
+                path
+            );
+            continue;
+        };
+        if let Err(err) = std::fs::write(&path, png) {
+            warn!("Cannot write the map {:?}: {err}", path);
+        }
+    }
+}
+
+fn dimension_map_path(map_path: &Path, dimension: Dimension) -> PathBuf {
+    let suffix = match dimension {
+        Dimension::Overworld => return map_path.to_path_buf(),
+        Dimension::Nether => "nether",
+        Dimension::End => "end",
+    };
+    let stem = map_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let file_name = match map_path.extension() {
+        Some(extension) => format!("{stem}-{suffix}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{suffix}"),
+    };
+    map_path.with_file_name(file_name)
+}
+
+/// Renders the chunks as a PNG, north (negative z) up. `None` if the image would be too large
+fn render_map(chunks: &[ChunkDecision]) -> Option<Vec<u8>> {
+    let min_x = chunks.iter().map(|((x, _), _)| *x).min()?;
+    let max_x = chunks.iter().map(|((x, _), _)| *x).max()?;
+    let min_z = chunks.iter().map(|((_, z), _)| *z).min()?;
+    let max_z = chunks.iter().map(|((_, z), _)| *z).max()?;
+    let width = u32::try_from(i64::from(max_x) - i64::from(min_x) + 1).ok()?;
+    let height = u32::try_from(i64::from(max_z) - i64::from(min_z) + 1).ok()?;
+    if width > MAX_MAP_SIDE || height > MAX_MAP_SIDE {
+        return None;
+    }
+
+    let mut pixels = MISSING_COLOR.repeat(width as usize * height as usize);
+    for &((x, z), deleted) in chunks {
+        let index = ((z - min_z) as usize * width as usize + (x - min_x) as usize) * 3;
+        let color = if deleted { DELETED_COLOR } else { KEPT_COLOR };
+        pixels[index..index + 3].copy_from_slice(&color);
+    }
+    Some(encode_png(width, height, &pixels))
+}
+
+/// Encodes 8-bit RGB pixels as a PNG, without filtering the lines
+fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    let mut header = Vec::from(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // 8 bits per sample, true color, deflate, adaptive filtering, no interlace
+    header.extend([8, 2, 0, 0, 0]);
+
+    // Each line starts with its filter type, 0 for none
+    let lines: Vec<u8> = pixels
+        .chunks(width as usize * 3)
+        .flat_map(|line| std::iter::once(0).chain(line.iter().copied()))
+        .collect();
+    let mut data = Vec::new();
+    ZlibEncoder::new(&lines[..], Compression::default())
+        .read_to_end(&mut data)
+        .expect("Compressing to memory cannot fail");
+
+    let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    for (kind, content) in [(b"IHDR", header), (b"IDAT", data), (b"IEND", Vec::new())] {
+        png.extend((content.len() as u32).to_be_bytes());
+        let mut crc = Crc::new();
+        crc.update(kind);
+        crc.update(&content);
+        png.extend(kind);
+        png.extend(content);
+        png.extend(crc.sum().to_be_bytes());
+    }
+    png
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::ZlibDecoder;
+
+    #[test]
+    fn test_render_map() {
+        let png = render_map(&[((-1, 5), true), ((1, 6), false)]).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // IHDR: 3x2 pixels
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 3, 0, 0, 0, 2]);
+        // CRC of the IHDR chunk
+        let mut crc = Crc::new();
+        crc.update(&png[12..29]);
+        assert_eq!(&png[29..33], &crc.sum().to_be_bytes());
+
+        let idat_len = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let mut lines = Vec::new();
+        ZlibDecoder::new(&png[41..41 + idat_len])
+            .read_to_end(&mut lines)
+            .unwrap();
+        let mut expected = vec![0];
+        expected.extend([DELETED_COLOR, MISSING_COLOR, MISSING_COLOR].concat());
+        expected.push(0);
+        expected.extend([MISSING_COLOR, MISSING_COLOR, KEPT_COLOR].concat());
+        assert_eq!(lines, expected);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+
+        assert!(render_map(&[((0, 0), true), ((MAX_MAP_SIDE as i32, 0), true)]).is_none());
+        assert!(render_map(&[]).is_none());
+    }
+
+    #[test]
+    fn test_dimension_map_path() {
+        let path = Path::new("maps/world.png");
+        assert_eq!(dimension_map_path(path, Dimension::Overworld), path);
+        assert_eq!(
+            dimension_map_path(path, Dimension::Nether),
+            Path::new("maps/world-nether.png")
+        );
+        assert_eq!(
+            dimension_map_path(Path::new("map"), Dimension::End),
+            Path::new("map-end")
+        );
+    }
+}
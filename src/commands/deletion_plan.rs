@@ -0,0 +1,226 @@
+use crate::commands::chunk_log::json_string;
+use crate::commands::chunk_map::ChunkDecision;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// First line of a plan, naming the format and its version
+const PLAN_HEADER: &str = r#"{"deletion_plan":1}"#;
+
+/// Chunks to delete, by region file
+pub type DeletionPlan = BTreeMap<PathBuf, BTreeSet<(i32, i32)>>;
+
+/// Saves the deleted chunks among the decisions of check as a plan write can replay.
+///
+/// A plan is a JSON Lines file: the `{"deletion_plan":1}` header, then one `{"region":...,"x":...,"z":...}` line
+/// per chunk, sorted by region and position so that plans of the same world diff cleanly.
+/// Regions are stored as absolute paths, so that replaying does not depend on the current directory
+pub fn write_plan(
+    plan_path: &Path,
+    chunk_decisions: &[(PathBuf, Vec<ChunkDecision>)],
+) -> std::io::Result<()> {
+    let mut plan = DeletionPlan::new();
+    for (region, decisions) in chunk_decisions {
+        let deleted: BTreeSet<(i32, i32)> = decisions
+            .iter()
+            .filter(|(_, deleted)| *deleted)
+            .map(|&(position, _)| position)
+            .collect();
+        if !deleted.is_empty() {
+            let region = std::fs::canonicalize(region).unwrap_or_else(|_| region.clone());
+            plan.entry(region).or_default().extend(deleted);
+        }
+    }
+
+    let mut writer = BufWriter::new(std::fs::File::create(plan_path)?);
+    writeln!(writer, "{PLAN_HEADER}")?;
+    for (region, positions) in &plan {
+        let region = json_string(&region.to_string_lossy());
+        for (x, z) in positions {
+            writeln!(writer, r#"{{"region":{region},"x":{x},"z":{z}}}"#)?;
+        }
+    }
+    writer.flush()
+}
+
+/// Reads a plan saved by [`write_plan`]
+pub fn read_plan(plan_path: &Path) -> Result<DeletionPlan, Box<dyn Error>> {
+    let content = std::fs::read_to_string(plan_path)
+        .map_err(|err| format!("Cannot read the deletion plan {:?}: {err}", plan_path))?;
+    parse_plan(&content)
+        .map_err(|err| format!("Invalid deletion plan {:?}: {err}", plan_path).into())
+}
+
+fn parse_plan(content: &str) -> Result<DeletionPlan, String> {
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    match lines.next() {
+        Some((_, header)) if header.trim() == PLAN_HEADER => {}
+        _ => return Err(format!("it does not start with {PLAN_HEADER}")),
+    }
+
+    let mut plan = DeletionPlan::new();
+    for (index, line) in lines {
+        let (region, position) =
+            parse_plan_line(line).ok_or_else(|| format!("line {} is not a chunk", index + 1))?;
+        plan.entry(PathBuf::from(region))
+            .or_default()
+            .insert(position);
+    }
+    Ok(plan)
+}
+
+/// Reads a `{"region":"...","x":0,"z":0}` line, keys in any order
+fn parse_plan_line(line: &str) -> Option<(String, (i32, i32))> {
+    let mut parser = JsonParser { rest: line.trim() };
+    let (mut region, mut x, mut z) = (None, None, None);
+    parser.expect('{')?;
+    loop {
+        let key = parser.string()?;
+        parser.expect(':')?;
+        match key.as_str() {
+            "region" => region = Some(parser.string()?),
+            "x" => x = Some(parser.integer()?),
+            "z" => z = Some(parser.integer()?),
+            _ => return None,
+        }
+        if parser.expect(',').is_none() {
+            break;
+        }
+    }
+    parser.expect('}')?;
+    parser.rest.is_empty().then_some(())?;
+    Some((region?, (x?, z?)))
+}
+
+/// Reads the few JSON values plans are made of
+struct JsonParser<'a> {
+    rest: &'a str,
+}
+
+impl JsonParser<'_> {
+    fn expect(&mut self, c: char) -> Option<()> {
+        self.rest = self.rest.trim_start().strip_prefix(c)?;
+        Some(())
+    }
+
+    fn integer(&mut self) -> Option<i32> {
+        self.rest = self.rest.trim_start();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+            .unwrap_or(self.rest.len());
+        let (number, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        number.parse().ok()
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.expect('"')?;
+        let mut value = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[index + 1..];
+                    return Some(value);
+                }
+                '\\' => match chars.next()?.1 {
+                    'u' => {
+                        let hex: String = (0..4)
+                            .filter_map(|_| chars.next().map(|(_, c)| c))
+                            .collect();
+                        value.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                    }
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    escaped @ ('"' | '\\' | '/') => value.push(escaped),
+                    _ => return None,
+                },
+                c => value.push(c),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_round_trip() {
+        let path = std::env::temp_dir().join(format!("mwt_plan_{}.jsonl", std::process::id()));
+        let decisions = vec![
+            (
+                PathBuf::from("world/region/r.0.0.mca"),
+                vec![((3, 4), true), ((1, 1), false), ((0, 2), true)],
+            ),
+            (
+                PathBuf::from("world/region/r.1.0.mca"),
+                vec![((32, 0), false)],
+            ),
+            (
+                PathBuf::from("C:\\saves\\\"w\".mca"),
+                vec![((-1, -1), true)],
+            ),
+        ];
+        write_plan(&path, &decisions).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content.lines().collect::<Vec<_>>(),
+            [
+                PLAN_HEADER,
+                r#"{"region":"C:\\saves\\\"w\".mca","x":-1,"z":-1}"#,
+                r#"{"region":"world/region/r.0.0.mca","x":0,"z":2}"#,
+                r#"{"region":"world/region/r.0.0.mca","x":3,"z":4}"#,
+            ]
+        );
+
+        let plan = read_plan(&path).unwrap();
+        assert_eq!(plan.len(), 2);
+        assert_eq!(
+            plan[Path::new("world/region/r.0.0.mca")],
+            BTreeSet::from([(0, 2), (3, 4)])
+        );
+        assert_eq!(
+            plan[Path::new("C:\\saves\\\"w\".mca")],
+            BTreeSet::from([(-1, -1)])
+        );
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_parse_plan() {
+        let plan = parse_plan(&format!(
+            "{PLAN_HEADER}\n\n{{ \"z\": -7, \"region\": \"r.0.0.mca\", \"x\": 5 }}\n"
+        ));
+        assert_eq!(
+            plan.unwrap()[Path::new("r.0.0.mca")],
+            BTreeSet::from([(5, -7)])
+        );
+        assert_eq!(
+            parse_plan_line(r#"{"region":"a\u00e9\/b","x":0,"z":0}"#),
+            Some(("aé/b".to_string(), (0, 0)))
+        );
+
+        assert!(parse_plan(r#"{"region":"r.0.0.mca","x":0,"z":0}"#).is_err());
+        for invalid in [
+            r#"{"region":"r.0.0.mca","x":0}"#,
+            r#"{"region":"r.0.0.mca","x":0,"z":0,"y":1}"#,
+            r#"{"region":"r.0.0.mca","x":1.5,"z":0}"#,
+            r#"{"region":"r.0.0.mca","x":0,"z":0} trailing"#,
+        ] {
+            assert!(
+                parse_plan(&format!("{PLAN_HEADER}\n{invalid}")).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use crate::commands::chunk_log::warn_parse_failures;
+use crate::commands::progress::{new_progress_bar, new_spinner};
+use crate::commands::read::{csv_field, forward_slashes};
+use crate::logger::trace_region;
+use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+use crate::region_loader::region::Region;
+use crate::world::get_region_files::{get_region_files, warn_if_no_region_files};
+use crate::world::scan_options::ScanOptions;
+use crate::{info, warn};
+use rayon::iter::ParallelIterator;
+use rayon::prelude::IntoParallelRefIterator;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+
+/// How a chunk differs between the two worlds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkChange {
+    /// Only in the second world
+    Added,
+    /// Only in the first world
+    Removed,
+    /// In both worlds with different NBT
+    Changed,
+}
+
+impl ChunkChange {
+    fn name(self) -> &'static str {
+        match self {
+            ChunkChange::Added => "added",
+            ChunkChange::Removed => "removed",
+            ChunkChange::Changed => "changed",
+        }
+    }
+}
+
+/// Hash of the NBT of every chunk of a region, by chunk coordinates
+type ChunkHashes = BTreeMap<(i32, i32), u64>;
+
+/// Differences between the chunks of the same region file in the two worlds
+#[derive(Default)]
+struct RegionDiff {
+    chunks_first: usize,
+    chunks_second: usize,
+    unchanged: usize,
+    /// In chunk coordinate order
+    changes: Vec<(ChunkChange, i32, i32)>,
+    /// Chunks of either file that cannot be decoded, left out of the comparison
+    unparsable_chunks: usize,
+}
+
+/// Differences between the chunks of two worlds
+#[derive(Default)]
+struct WorldDiff {
+    chunks_first: usize,
+    chunks_second: usize,
+    unchanged: usize,
+    /// Region file named relative to its world, chunk coordinates, in region order
+    changes: Vec<(ChunkChange, String, i32, i32)>,
+    unparsable_chunks: usize,
+    /// Regions that cannot be read in either world, left out of the comparison
+    unreadable_regions: usize,
+}
+
+impl WorldDiff {
+    fn count(&self, change: ChunkChange) -> usize {
+        self.changes
+            .iter()
+            .filter(|(kind, ..)| *kind == change)
+            .count()
+    }
+}
+
+impl Display for WorldDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "World Comparison:\n\
+                   Chunks in First World: {}\n\
+                   Chunks in Second World: {}\n\
+                   Unchanged Chunks: {}\n\
+                   Changed Chunks: {}\n\
+                   Appeared Chunks: {}\n\
+                   Disappeared Chunks: {}\n\
+                   Unparsable Chunks: {}\n\
+                   Unreadable Regions: {}",
+            self.chunks_first,
+            self.chunks_second,
+            self.unchanged,
+            self.count(ChunkChange::Changed),
+            self.count(ChunkChange::Added),
+            self.count(ChunkChange::Removed),
+            self.unparsable_chunks,
+            self.unreadable_regions
+        )
+    }
+}
+
+/// Compares the chunks of two worlds, such as a backup and the live world, and tells which chunks changed, appeared
+/// or disappeared. Regions are paired by their path inside each world and chunks by their coordinates, a chunk
+/// changed when its NBT is not byte for byte the same. With `list`, every difference is printed as a
+/// `change,region,x,z` CSV line. Makes no change to the worlds
+pub fn execute_diff(
+    world_paths: &[PathBuf],
+    scan_options: &ScanOptions,
+    schemes: &SchemeMap,
+    list: bool,
+    csv_delimiter: char,
+) -> Result<(), Box<dyn Error>> {
+    let [first, second] = world_paths else {
+        return Err(format!(
+            "diff compares two worlds, got {} path(s)",
+            world_paths.len()
+        )
+        .into());
+    };
+    let diff = diff_worlds(first, second, scan_options, schemes)?;
+
+    if list {
+        let d = csv_delimiter;
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "change{d}region{d}x{d}z")?;
+        for (change, region, x, z) in &diff.changes {
+            writeln!(
+                stdout,
+                "{}{d}{}{d}{x}{d}{z}",
+                change.name(),
+                csv_field(region, d)
+            )?;
+        }
+    }
+    println!("{diff}");
+    Ok(())
+}
+
+fn diff_worlds(
+    first: &Path,
+    second: &Path,
+    scan_options: &ScanOptions,
+    schemes: &SchemeMap,
+) -> Result<WorldDiff, Box<dyn Error>> {
+    let spinner = new_spinner("Scanning worlds for region files...");
+    let regions = world_regions(first, scan_options).and_then(|first_regions| {
+        world_regions(second, scan_options).map(|second_regions| (first_regions, second_regions))
+    });
+    spinner.finish_and_clear();
+    let (first_regions, second_regions) = regions?;
+    let names: Vec<&String> = first_regions
+        .keys()
+        .chain(second_regions.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    info!("Comparing {} region file(s)", names.len());
+    let pb = new_progress_bar(names.len() as u64);
+
+    let region_diffs: Vec<(&String, Option<RegionDiff>)> = names
+        .par_iter()
+        .map(|&name| {
+            let diff = diff_region(first_regions.get(name), second_regions.get(name), schemes);
+            pb.inc(1);
+            (name, diff)
+        })
+        .collect();
+    pb.finish_and_clear();
+
+    let mut diff = WorldDiff::default();
+    for (name, region_diff) in region_diffs {
+        let Some(region_diff) = region_diff else {
+            diff.unreadable_regions += 1;
+            continue;
+        };
+        diff.chunks_first += region_diff.chunks_first;
+        diff.chunks_second += region_diff.chunks_second;
+        diff.unchanged += region_diff.unchanged;
+        diff.unparsable_chunks += region_diff.unparsable_chunks;
+        let changes = region_diff.changes.into_iter();
+        diff.changes
+            .extend(changes.map(|(change, x, z)| (change, name.clone(), x, z)));
+    }
+    Ok(diff)
+}
+
+/// Region files of a world by their path relative to it (`DIM-1/region/r.0.0.mca`), a region file given instead
+/// of a world is named by its file name
+fn world_regions(
+    world: &Path,
+    scan_options: &ScanOptions,
+) -> Result<BTreeMap<String, PathBuf>, Box<dyn Error>> {
+    let region_files = get_region_files(std::slice::from_ref(&world.to_path_buf()), scan_options)?;
+    warn_if_no_region_files(
+        std::slice::from_ref(&world.to_path_buf()),
+        region_files.len(),
+    );
+    Ok(region_files
+        .into_iter()
+        .map(|region_file| {
+            let relative = region_file
+                .strip_prefix(world)
+                .ok()
+                .filter(|relative| !relative.as_os_str().is_empty())
+                .or_else(|| region_file.file_name().map(Path::new))
+                .unwrap_or(&region_file);
+            (
+                forward_slashes(&relative.display().to_string(), MAIN_SEPARATOR),
+                region_file.clone(),
+            )
+        })
+        .collect())
+}
+
+/// Compares the chunks of a region file in both worlds, a missing file has no chunks.
+/// `None` when either file cannot be read
+fn diff_region(
+    first: Option<&PathBuf>,
+    second: Option<&PathBuf>,
+    schemes: &SchemeMap,
+) -> Option<RegionDiff> {
+    let (first, first_unparsable) = chunk_hashes(first, schemes)?;
+    let (second, second_unparsable) = chunk_hashes(second, schemes)?;
+    let mut diff = RegionDiff {
+        chunks_first: first.len(),
+        chunks_second: second.len(),
+        unparsable_chunks: first_unparsable + second_unparsable,
+        ..RegionDiff::default()
+    };
+
+    let coordinates: BTreeSet<&(i32, i32)> = first.keys().chain(second.keys()).collect();
+    for &(x, z) in coordinates {
+        let change = match (first.get(&(x, z)), second.get(&(x, z))) {
+            (Some(before), Some(after)) if before == after => {
+                diff.unchanged += 1;
+                continue;
+            }
+            (Some(_), Some(_)) => ChunkChange::Changed,
+            (None, _) => ChunkChange::Added,
+            (_, None) => ChunkChange::Removed,
+        };
+        diff.changes.push((change, x, z));
+    }
+    Some(diff)
+}
+
+/// Hashes of the chunks of a region file and how many of its chunks cannot be decoded, `None` when the file cannot
+/// be read. Chunks that cannot be decoded or have no position are left out
+fn chunk_hashes(
+    region_file_path: Option<&PathBuf>,
+    schemes: &SchemeMap,
+) -> Option<(ChunkHashes, usize)> {
+    let Some(region_file_path) = region_file_path else {
+        return Some(Default::default());
+    };
+    trace_region(region_file_path);
+    let region = match Region::from_file_name(region_file_path, schemes) {
+        Ok(region) => region,
+        Err(err) => {
+            warn!(
+                "Cannot read {:?}, leaving it out of the comparison: {err}",
+                region_file_path
+            );
+            return None;
+        }
+    };
+    warn_parse_failures(region_file_path, region.get_parse_failures());
+
+    let mut unparsable = region.get_parse_failures().len();
+    let mut hashes = ChunkHashes::new();
+    for chunk in region.get_chunks() {
+        match chunk.get_position() {
+            Ok(position) => {
+                let mut hasher = DefaultHasher::new();
+                chunk.nbt.to_bytes().hash(&mut hasher);
+                hashes.insert(position, hasher.finish());
+            }
+            Err(_) => unparsable += 1,
+        }
+    }
+    Some((hashes, unparsable))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_loader::test_region::{TestChunk, test_region_bytes};
+
+    #[test]
+    fn test_diff_worlds() {
+        let root = std::env::temp_dir().join(format!("mwt_diff_{}", std::process::id()));
+        let (backup, live) = (root.join("backup"), root.join("live"));
+        for world in [&backup, &live] {
+            std::fs::create_dir_all(world.join("region")).unwrap();
+            std::fs::write(world.join("level.dat"), b"").unwrap();
+        }
+        let regions = [
+            (
+                "backup/region/r.0.0.mca",
+                vec![
+                    TestChunk::full(0, 0),
+                    TestChunk::full(1, 0),
+                    TestChunk::full(2, 0),
+                ],
+            ),
+            ("backup/region/r.1.0.mca", vec![TestChunk::full(32, 0)]),
+            (
+                "live/region/r.0.0.mca",
+                vec![
+                    TestChunk::full(0, 0),
+                    TestChunk::full(1, 0).inhabited_time(20),
+                    TestChunk::full(3, 0),
+                ],
+            ),
+        ];
+        for (path, chunks) in regions {
+            std::fs::write(root.join(path), test_region_bytes(&chunks)).unwrap();
+        }
+
+        let diff = diff_worlds(
+            &backup,
+            &live,
+            &ScanOptions::default(),
+            &SchemeMap::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            (diff.chunks_first, diff.chunks_second, diff.unchanged),
+            (4, 3, 1)
+        );
+        assert_eq!((diff.unparsable_chunks, diff.unreadable_regions), (0, 0));
+        let region = |name: &str| name.to_string();
+        assert_eq!(
+            diff.changes,
+            vec![
+                (ChunkChange::Changed, region("region/r.0.0.mca"), 1, 0),
+                (ChunkChange::Removed, region("region/r.0.0.mca"), 2, 0),
+                (ChunkChange::Added, region("region/r.0.0.mca"), 3, 0),
+                (ChunkChange::Removed, region("region/r.1.0.mca"), 32, 0),
+            ]
+        );
+
+        let schemes = SchemeMap::default();
+        let same = diff_worlds(&live, &live, &ScanOptions::default(), &schemes).unwrap();
+        assert_eq!((same.unchanged, same.changes.len()), (3, 0));
+        assert!(
+            execute_diff(
+                std::slice::from_ref(&live),
+                &ScanOptions::default(),
+                &schemes,
+                false,
+                ','
+            )
+            .is_err()
+        );
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,176 @@
+use crate::commands::chunk_log::skip_if_unparsable;
+use crate::commands::optimize_result::OptimizeResult;
+use crate::commands::trim_options::TrimOptions;
+use crate::commands::write::rewrite_companion_region;
+use crate::region_loader::region::{ParseRegionError, Region};
+use crate::{info, warn};
+use std::path::{Path, PathBuf};
+
+/// `entities/` mirrors `region/` file by file, both live in the same dimension directory
+pub(crate) fn entities_file_for(region_file: &Path) -> Option<PathBuf> {
+    let region_dir = region_file.parent()?;
+    if region_dir.file_name()? != "region" {
+        return None;
+    }
+    Some(
+        region_dir
+            .parent()?
+            .join("entities")
+            .join(region_file.file_name()?),
+    )
+}
+
+/// Removes the entities of the types of `--trim-entity-types` from the entity region of a region file.
+/// Entity chunks are kept even when they end up without entities, the game expects them next to their terrain chunk
+pub(crate) fn trim_entities(
+    region_file_path: &Path,
+    options: &TrimOptions,
+    result: &mut OptimizeResult,
+) {
+    let Some(entities_file_path) =
+        entities_file_for(region_file_path).filter(|path| path.is_file())
+    else {
+        return;
+    };
+
+    let mut entities = match Region::from_file_name(&entities_file_path, &options.schemes) {
+        Ok(entities) => entities,
+        Err(ParseRegionError::HeaderError) => return,
+        Err(ParseRegionError::ReadError) => {
+            warn!("Cannot read {:?}", entities_file_path);
+            result.io_errors += 1;
+            return;
+        }
+    };
+    if skip_if_unparsable(
+        &entities_file_path,
+        &entities,
+        &mut result.unparsable_chunks,
+    ) {
+        return;
+    }
+    let removed = entities.remove_entity_types(&options.trim_entity_types);
+    if removed == 0 {
+        return;
+    }
+
+    let original_len = std::fs::metadata(&entities_file_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+    match rewrite_companion_region(&entities_file_path, &entities, options) {
+        Ok(new_len) => {
+            info!(
+                "Rewrote {:?} without {} entities",
+                entities_file_path, removed
+            );
+            result.saved_bytes += original_len.saturating_sub(new_len);
+            result.removed_entities += removed;
+        }
+        Err(err) => {
+            warn!("Cannot write {:?}: {err}", entities_file_path);
+            result.io_errors += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::write::optimize_write;
+    use crate::nbt::tag::Tag;
+    use crate::region_loader::chunk_loader::chunk::Chunk;
+    use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+    use crate::region_loader::test_region::corrupt_chunk;
+
+    fn entity_chunk(position: (i32, i32), ids: &[&str]) -> Chunk {
+        let entities = ids
+            .iter()
+            .map(|id| Tag::Compound {
+                name: None,
+                value: vec![Tag::String {
+                    name: Some("id".to_string()),
+                    value: id.to_string(),
+                }],
+            })
+            .collect();
+        Chunk::from_nbt(Tag::Compound {
+            name: Some(String::new()),
+            value: vec![
+                Tag::IntArray {
+                    name: Some("Position".to_string()),
+                    value: vec![position.0, position.1],
+                },
+                Tag::List {
+                    name: Some("Entities".to_string()),
+                    value: entities,
+                    tag_type: 10,
+                },
+            ],
+        })
+    }
+
+    #[test]
+    fn test_entities_file_for() {
+        assert_eq!(
+            entities_file_for(Path::new("world/DIM-1/region/r.0.-1.mca")),
+            Some(PathBuf::from("world/DIM-1/entities/r.0.-1.mca"))
+        );
+        assert_eq!(entities_file_for(Path::new("loose/r.0.0.mca")), None);
+    }
+
+    #[test]
+    fn test_trim_entities() {
+        let world = std::env::temp_dir().join(format!("mwt_entities_{}", std::process::id()));
+        std::fs::create_dir_all(world.join("region")).unwrap();
+        std::fs::create_dir_all(world.join("entities")).unwrap();
+        let region_file = world.join("region/r.0.0.mca");
+        let entities_file = world.join("entities/r.0.0.mca");
+        let sample = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        std::fs::copy(&sample, &region_file).unwrap();
+        let options = TrimOptions {
+            trim_entity_types: vec![
+                "minecraft:item".to_string(),
+                "minecraft:item_frame".to_string(),
+            ],
+            ..TrimOptions::default()
+        };
+        let entities = Region::from_chunks(vec![
+            entity_chunk(
+                (0, 0),
+                &["minecraft:item", "minecraft:item", "minecraft:villager"],
+            ),
+            entity_chunk((1, 0), &["minecraft:item_frame"]),
+        ]);
+        std::fs::write(&entities_file, entities.to_bytes(options.compression).bytes).unwrap();
+
+        let result = optimize_write(&region_file, &options, false);
+        assert_eq!(result.removed_entities, 3);
+        assert_eq!(result.io_errors, 0);
+        let mut entities = Region::from_file_name(&entities_file, &SchemeMap::default()).unwrap();
+        assert_eq!(entities.get_chunk_count(), 2);
+        assert_eq!(entities.remove_entity_types(&options.trim_entity_types), 0);
+        assert_eq!(
+            entities.remove_entity_types(&["minecraft:villager".to_string()]),
+            1
+        );
+
+        // Nothing left to remove, the file is not rewritten
+        let result = optimize_write(&region_file, &options, false);
+        assert_eq!(result.removed_entities, 0);
+
+        // Nor when it holds chunks that cannot be decoded, they would be lost
+        let entities = Region::from_chunks(vec![
+            entity_chunk((0, 0), &["minecraft:villager"]),
+            entity_chunk((1, 0), &["minecraft:item"]),
+        ]);
+        let mut bytes = entities.to_bytes(options.compression).bytes;
+        corrupt_chunk(&mut bytes, 0);
+        std::fs::write(&entities_file, &bytes).unwrap();
+        std::fs::copy(&sample, &region_file).unwrap();
+        let result = optimize_write(&region_file, &options, false);
+        assert_eq!((result.removed_entities, result.unparsable_chunks), (0, 1));
+        assert_eq!(std::fs::read(&entities_file).unwrap(), bytes);
+
+        std::fs::remove_dir_all(&world).ok();
+    }
+}
@@ -0,0 +1,155 @@
+use crate::commands::chunk_log::skip_if_unparsable;
+use crate::commands::progress::{RegionProgress, new_spinner};
+use crate::commands::write::{RegionPayload, atomic_write_region, confirm_write};
+use crate::logger::trace_region;
+use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+use crate::region_loader::compression_level::CompressionLevel;
+use crate::region_loader::region::{ParseRegionError, Region};
+use crate::world::get_region_files::{get_region_files, warn_if_no_region_files};
+use crate::world::scan_options::ScanOptions;
+use crate::{info, warn};
+use rayon::iter::ParallelIterator;
+use rayon::prelude::IntoParallelRefIterator;
+use std::error::Error;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+#[derive(Default, Clone)]
+struct FixHeightmapsResult {
+    scanned_regions: usize,
+    rewritten_regions: usize,
+    fixed_chunks: usize,
+    /// Fully generated chunks whose heightmaps cannot be computed, such as pre-1.18 chunks
+    unsupported_chunks: usize,
+    /// Chunks that cannot be decoded, their region is left untouched since a rewrite would lose them
+    unparsable_chunks: usize,
+    io_errors: usize,
+}
+
+impl Display for FixHeightmapsResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Heightmap Fix Result:\n\
+                   Scanned Regions: {}\n\
+                   Rewritten Regions: {}\n\
+                   Fixed Chunks: {}\n\
+                   Unsupported Chunks: {}\n\
+                   Unparsable Chunks: {}\n\
+                   I/O Errors: {}",
+            self.scanned_regions,
+            self.rewritten_regions,
+            self.fixed_chunks,
+            self.unsupported_chunks,
+            self.unparsable_chunks,
+            self.io_errors
+        )
+    }
+}
+
+/// Rebuilds stale `WORLD_SURFACE` and `MOTION_BLOCKING` heightmaps from the blocks of the chunks,
+/// and rewrites the regions where any of them changed
+pub fn execute_fix_heightmaps(
+    world_paths: &[PathBuf],
+    scan_options: &ScanOptions,
+    compression: CompressionLevel,
+    schemes: &SchemeMap,
+    assume_yes: bool,
+) -> Result<(), Box<dyn Error>> {
+    let spinner = new_spinner("Scanning worlds for region files...");
+    let entries = get_region_files(world_paths, scan_options);
+    spinner.finish_and_clear();
+    let entries = entries?;
+    info!("Found {} region file(s)", entries.len());
+    warn_if_no_region_files(world_paths, entries.len());
+
+    if !assume_yes
+        && !confirm_write(
+            world_paths,
+            entries.len(),
+            &mut std::io::stdin().lock(),
+            &mut std::io::stderr(),
+        )?
+    {
+        return Err(
+            "Heightmap fix aborted, nothing was modified (use --yes to skip the confirmation)"
+                .into(),
+        );
+    }
+
+    let progress = RegionProgress::new(&entries);
+    let result = entries
+        .par_iter()
+        .map(|entry| {
+            let ticket = progress.start(entry);
+            trace_region(entry);
+            let result = fix_region_heightmaps(entry, compression, schemes);
+            drop(ticket);
+            result
+        })
+        .reduce(FixHeightmapsResult::default, |mut acc, cur| {
+            acc.scanned_regions += cur.scanned_regions;
+            acc.rewritten_regions += cur.rewritten_regions;
+            acc.fixed_chunks += cur.fixed_chunks;
+            acc.unsupported_chunks += cur.unsupported_chunks;
+            acc.unparsable_chunks += cur.unparsable_chunks;
+            acc.io_errors += cur.io_errors;
+            acc
+        });
+    progress.finish_and_clear();
+
+    println!("{result}");
+
+    Ok(())
+}
+
+fn fix_region_heightmaps(
+    region_file_path: &Path,
+    compression: CompressionLevel,
+    schemes: &SchemeMap,
+) -> FixHeightmapsResult {
+    let mut result = FixHeightmapsResult {
+        scanned_regions: 1,
+        ..FixHeightmapsResult::default()
+    };
+
+    let mut region = match Region::from_file_name(region_file_path, schemes) {
+        Ok(region) => region,
+        // Without a header there are no chunks to fix
+        Err(ParseRegionError::HeaderError) => return result,
+        Err(ParseRegionError::ReadError) => {
+            warn!("Cannot read {:?}", region_file_path);
+            result.io_errors += 1;
+            return result;
+        }
+    };
+    // Damaged worlds are what this mode is for, it must not lose what is left of them
+    if skip_if_unparsable(region_file_path, &region, &mut result.unparsable_chunks) {
+        return result;
+    }
+    let (fixed, unsupported) = region.recompute_heightmaps();
+    result.unsupported_chunks += unsupported;
+    if !region.is_modified() {
+        return result;
+    }
+
+    let written = RegionPayload::new(&region, compression).and_then(|payload| {
+        atomic_write_region(region_file_path, |writer| payload.write_to(writer))
+    });
+    match written {
+        Ok(()) => {
+            info!(
+                "Fixed the heightmaps of {} chunk(s) in {:?}",
+                fixed, region_file_path
+            );
+            result.rewritten_regions += 1;
+            result.fixed_chunks += fixed;
+        }
+        Err(err) => {
+            warn!("Cannot write {:?}: {err}", region_file_path);
+            result.io_errors += 1;
+        }
+    }
+
+    result
+}
@@ -0,0 +1,72 @@
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static INSTALL: Once = Once::new();
+
+const INTERRUPT_MESSAGE: &[u8] =
+    b"\nInterrupted, finishing the region files in progress (press Ctrl-C again to abort right away)\n";
+
+/// Catches the first Ctrl-C so that a run can stop between two region files instead of in the middle of one.
+/// A second Ctrl-C is left to the default handler and terminates the process
+pub fn install_interrupt_handler() {
+    INSTALL.call_once(platform::install);
+}
+
+/// Whether Ctrl-C was pressed since the handler was installed, no new region file should be started then
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+#[cfg(unix)]
+mod platform {
+    use super::{INTERRUPT_MESSAGE, INTERRUPTED};
+    use std::sync::atomic::Ordering;
+
+    pub fn install() {
+        let handler: extern "C" fn(libc::c_int) = on_interrupt;
+        // SAFETY: the handler only calls async-signal-safe functions
+        unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+    }
+
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+        // SAFETY: signal and write are async-signal-safe
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::write(
+                libc::STDERR_FILENO,
+                INTERRUPT_MESSAGE.as_ptr().cast(),
+                INTERRUPT_MESSAGE.len(),
+            );
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{INTERRUPT_MESSAGE, INTERRUPTED};
+    use std::io::Write;
+    use std::sync::atomic::Ordering;
+    use windows_sys::Win32::System::Console::{CTRL_C_EVENT, SetConsoleCtrlHandler};
+    use windows_sys::core::BOOL;
+
+    pub fn install() {
+        // SAFETY: the handler is a plain function living as long as the process
+        unsafe { SetConsoleCtrlHandler(Some(on_interrupt), 1) };
+    }
+
+    /// Runs on its own thread. Returning 0 hands the event to the default handler, which terminates the process
+    unsafe extern "system" fn on_interrupt(ctrl_type: u32) -> BOOL {
+        if ctrl_type != CTRL_C_EVENT || INTERRUPTED.swap(true, Ordering::Relaxed) {
+            return 0;
+        }
+        let _ = std::io::stderr().write_all(INTERRUPT_MESSAGE);
+        1
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    pub fn install() {}
+}
@@ -0,0 +1,126 @@
+use crate::commands::progress::file_len;
+use std::cell::Cell;
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+
+/// Rough memory needed per byte of region file: the file itself, its decoded NBT trees and the serialized copy
+const MEMORY_PER_FILE_BYTE: u64 = 16;
+
+thread_local! {
+    /// Permits held by the current thread
+    static HELD_PERMITS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Limits how many regions are processed at once so that their estimated memory stays under a budget.
+///
+/// Rayon threads waiting for a nested parallel job (e.g. chunk compression) can pick up another region,
+/// so a thread already holding a permit is never blocked, otherwise it could wait on itself.
+pub struct MemoryBudget {
+    limit: Option<u64>,
+    in_use: Mutex<u64>,
+    released: Condvar,
+}
+
+pub struct MemoryPermit<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl MemoryBudget {
+    /// `max_memory` is in MiB, `None` does not limit anything
+    pub fn new(max_memory: Option<u64>) -> Self {
+        Self {
+            limit: max_memory.map(|mib| mib * 1_048_576),
+            in_use: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Waits until the region file fits in the budget. A region larger than the whole budget runs alone
+    pub fn acquire(&self, region_file_path: &Path) -> MemoryPermit<'_> {
+        self.acquire_bytes(file_len(region_file_path).saturating_mul(MEMORY_PER_FILE_BYTE))
+    }
+
+    fn acquire_bytes(&self, bytes: u64) -> MemoryPermit<'_> {
+        let Some(limit) = self.limit else {
+            return MemoryPermit {
+                budget: self,
+                bytes: 0,
+            };
+        };
+
+        let bytes = bytes.min(limit);
+        let is_nested = HELD_PERMITS.get() > 0;
+        let mut in_use = self.in_use.lock().unwrap_or_else(|err| err.into_inner());
+        while !is_nested && *in_use > 0 && *in_use + bytes > limit {
+            in_use = self
+                .released
+                .wait(in_use)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+        *in_use += bytes;
+        HELD_PERMITS.set(HELD_PERMITS.get() + 1);
+
+        MemoryPermit {
+            budget: self,
+            bytes,
+        }
+    }
+}
+
+impl Drop for MemoryPermit<'_> {
+    fn drop(&mut self) {
+        if self.budget.limit.is_none() {
+            return;
+        }
+        let mut in_use = self
+            .budget
+            .in_use
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        *in_use -= self.bytes;
+        HELD_PERMITS.set(HELD_PERMITS.get() - 1);
+        self.budget.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_waits_for_memory_to_be_released() {
+        let budget = MemoryBudget::new(Some(1));
+        let acquired = AtomicBool::new(false);
+
+        let permit = budget.acquire_bytes(800_000);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let _permit = budget.acquire_bytes(800_000);
+                acquired.store(true, Ordering::SeqCst);
+            });
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(!acquired.load(Ordering::SeqCst));
+            drop(permit);
+        });
+        assert!(acquired.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_oversized_and_nested_permits_do_not_block() {
+        let budget = MemoryBudget::new(Some(1));
+        let outer = budget.acquire_bytes(u64::MAX);
+        let nested = budget.acquire_bytes(1_000_000);
+        drop(nested);
+        drop(outer);
+        assert_eq!(*budget.in_use.lock().unwrap(), 0);
+
+        let unlimited = MemoryBudget::new(None);
+        let _permits = [
+            unlimited.acquire_bytes(u64::MAX),
+            unlimited.acquire_bytes(u64::MAX),
+        ];
+    }
+}
@@ -1,3 +1,26 @@
+#[cfg(feature = "tui")]
+pub mod browse;
+mod chunk_log;
+mod chunk_map;
+mod deletion_plan;
+pub mod diff;
+mod entities;
+pub mod fix_heightmaps;
+mod interrupt;
+mod memory_budget;
 mod optimize_result;
+pub mod output_dir;
+pub mod palette;
+mod poi;
+mod progress;
 pub mod read;
+mod region_cache;
+pub mod salvage;
+pub mod snapshot;
+pub mod stats;
+mod summary_file;
+pub mod top_regions;
+pub mod trim_options;
+pub mod verify;
+pub mod versions;
 pub mod write;
@@ -1,15 +1,45 @@
+use crate::commands::chunk_log::warn_parse_failures;
+use crate::region_loader::chunk_loader::chunk::Chunk;
+use crate::region_loader::region::ChunkParseFailure;
+use crate::warn;
 use std::fmt::Display;
+use std::path::Path;
 
 #[derive(Default, Clone)]
 pub struct OptimizeResult {
     pub total_chunks: usize,
     pub deleted_chunks: usize,
     pub deleted_regions: usize,
+    pub emptied_regions: usize,
+    /// Regions written again without their deleted chunks or sections, predicted by check mode
+    pub rewritten_regions: usize,
+    /// Regions read and left as they are because nothing in them had to change
+    pub unchanged_regions: usize,
+    /// Modified regions left untouched because rewriting them would not save enough space
+    pub skipped_regions: usize,
+    /// Regions not processed because `--cache` knows nothing changed in them since a run found them clean
+    pub cached_regions: usize,
+    /// Chunks that parse but have no position, they cannot be indexed in a region header
+    pub malformed_chunks: usize,
+    /// Chunks of the location tables that could not be parsed, a rewrite of their region loses them
+    pub unparsable_chunks: usize,
     pub io_errors: usize,
     pub compression_failures: usize,
     pub regions_with_compression_issues: usize,
     pub header_write_failures: usize,
     pub regions_with_header_issues: usize,
+    pub orphaned_sectors: usize,
+    pub regions_with_orphaned_sectors: usize,
+    /// POI chunks deleted with their terrain chunk, only counted with `--trim-poi`
+    pub deleted_poi_chunks: usize,
+    /// POI records removed from surviving POI chunks because their block is in another chunk
+    pub pruned_poi_records: usize,
+    /// Entities removed from the `entities/` regions, only counted with `--trim-entity-types`
+    pub removed_entities: usize,
+    /// Sections removed from the kept chunks, only counted with `--delete-below-y`
+    pub removed_sections: usize,
+    /// Bytes freed on disk. Check mode predicts it from the sectors write would drop
+    pub saved_bytes: u64,
 }
 
 impl Display for OptimizeResult {
@@ -20,28 +50,118 @@ impl Display for OptimizeResult {
                    Total Chunks: {}\n\
                    Deleted Chunks: {}\n\
                    Deleted Regions: {}\n\
+                   Emptied Regions: {}\n\
+                   Rewritten Regions: {}\n\
+                   Unchanged Regions: {}\n\
+                   Skipped Regions (Below Minimum Savings): {}\n\
+                   Cached Regions (Unchanged Since Last Run): {}\n\
+                   Malformed Chunks (No Position): {}\n\
+                   Unparsable Chunks: {}\n\
                    I/O Errors: {}\n\
                    Compression Fallbacks: {}\n\
                    Regions With Compression Issues: {}\n\
                    Header Write Failures: {}\n\
-                   Regions With Header Issues: {}",
+                   Regions With Header Issues: {}\n\
+                   Orphaned Sectors: {} ({:.2} MiB reclaimable)\n\
+                   Regions With Orphaned Sectors: {}\n\
+                   Deleted POI Chunks: {}\n\
+                   Pruned POI Records: {}\n\
+                   Removed Entities: {}\n\
+                   Removed Sections: {}\n\
+                   Saved Space: {:.2} MiB",
             self.total_chunks,
             self.deleted_chunks,
             self.deleted_regions,
+            self.emptied_regions,
+            self.rewritten_regions,
+            self.unchanged_regions,
+            self.skipped_regions,
+            self.cached_regions,
+            self.malformed_chunks,
+            self.unparsable_chunks,
             self.io_errors,
             self.compression_failures,
             self.regions_with_compression_issues,
             self.header_write_failures,
-            self.regions_with_header_issues
+            self.regions_with_header_issues,
+            self.orphaned_sectors,
+            (self.orphaned_sectors * 4096) as f64 / 1_048_576.0,
+            self.regions_with_orphaned_sectors,
+            self.deleted_poi_chunks,
+            self.pruned_poi_records,
+            self.removed_entities,
+            self.removed_sections,
+            self.saved_bytes as f64 / 1_048_576.0
         )
     }
 }
 
+impl OptimizeResult {
+    /// Tells if the run changed the worlds, or would change them in check mode
+    pub fn has_changes(&self) -> bool {
+        self.deleted_chunks > 0
+            || self.deleted_regions > 0
+            || self.emptied_regions > 0
+            || self.deleted_poi_chunks > 0
+            || self.pruned_poi_records > 0
+            || self.removed_entities > 0
+            || self.removed_sections > 0
+    }
+
+    /// Counts a region that is deleted, or emptied when empty regions are kept
+    pub fn add_discarded_region(&mut self, keep_empty_regions: bool) {
+        if keep_empty_regions {
+            self.emptied_regions += 1;
+        } else {
+            self.deleted_regions += 1;
+        }
+    }
+
+    /// Counts the chunks of a region without a position, and warns about them
+    pub fn add_malformed_chunks(&mut self, region_file_path: &Path, chunks: &[Chunk]) {
+        let malformed = chunks
+            .iter()
+            .filter(|chunk| chunk.get_position().is_err())
+            .count();
+        if malformed > 0 {
+            warn!(
+                "{malformed} chunk(s) without a position in {:?}",
+                region_file_path
+            );
+            self.malformed_chunks += malformed;
+        }
+    }
+
+    /// Counts the chunks of a region that could not be parsed, and warns about them with their coordinates
+    pub fn add_unparsable_chunks(
+        &mut self,
+        region_file_path: &Path,
+        failures: &[ChunkParseFailure],
+    ) {
+        warn_parse_failures(region_file_path, failures);
+        self.unparsable_chunks += failures.len();
+    }
+
+    pub fn add_orphaned_sectors(&mut self, orphaned_sectors: usize) {
+        if orphaned_sectors > 0 {
+            self.orphaned_sectors += orphaned_sectors;
+            self.regions_with_orphaned_sectors += 1;
+        }
+    }
+}
+
 pub fn reduce_optimize_results(results: &mut [OptimizeResult]) -> OptimizeResult {
     results
         .iter_mut()
         .reduce(|acc, cur| {
             acc.deleted_regions += cur.deleted_regions;
+            acc.emptied_regions += cur.emptied_regions;
+            acc.rewritten_regions += cur.rewritten_regions;
+            acc.unchanged_regions += cur.unchanged_regions;
+            acc.skipped_regions += cur.skipped_regions;
+            acc.cached_regions += cur.cached_regions;
+            acc.malformed_chunks += cur.malformed_chunks;
+            acc.unparsable_chunks += cur.unparsable_chunks;
             acc.total_chunks += cur.total_chunks;
             acc.deleted_chunks += cur.deleted_chunks;
             acc.io_errors += cur.io_errors;
@@ -49,8 +169,47 @@ pub fn reduce_optimize_results(results: &mut [OptimizeResult]) -> OptimizeResult
             acc.regions_with_compression_issues += cur.regions_with_compression_issues;
             acc.header_write_failures += cur.header_write_failures;
             acc.regions_with_header_issues += cur.regions_with_header_issues;
+            acc.orphaned_sectors += cur.orphaned_sectors;
+            acc.regions_with_orphaned_sectors += cur.regions_with_orphaned_sectors;
+            acc.deleted_poi_chunks += cur.deleted_poi_chunks;
+            acc.pruned_poi_records += cur.pruned_poi_records;
+            acc.removed_entities += cur.removed_entities;
+            acc.removed_sections += cur.removed_sections;
+            acc.saved_bytes += cur.saved_bytes;
             acc
         })
         .cloned()
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_changes() {
+        let unchanged = OptimizeResult {
+            total_chunks: 1024,
+            orphaned_sectors: 3,
+            io_errors: 1,
+            ..OptimizeResult::default()
+        };
+        assert!(!unchanged.has_changes());
+        for changed in [
+            OptimizeResult {
+                deleted_chunks: 1,
+                ..unchanged.clone()
+            },
+            OptimizeResult {
+                emptied_regions: 1,
+                ..unchanged.clone()
+            },
+            OptimizeResult {
+                removed_sections: 4,
+                ..unchanged.clone()
+            },
+        ] {
+            assert!(changed.has_changes());
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::commands::progress::{new_progress_bar, new_spinner};
+use crate::{info, warn};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Copies the given worlds (or region files) into `output_dir`, each under its own folder name, so that write trims
+/// the copies and leaves the originals untouched (`--output-dir`). Returns the paths of the copies, in the order of
+/// `world_paths`. Copies keep the modification times of the originals, which `--modified-since` and `--cache` go by
+pub fn copy_to_output_dir(
+    world_paths: &[PathBuf],
+    output_dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    std::fs::create_dir_all(output_dir)
+        .map_err(|err| format!("Cannot create the output directory {:?}: {err}", output_dir))?;
+    let canonical_output = std::fs::canonicalize(output_dir)?;
+
+    let mut names = HashSet::new();
+    let mut copies = Vec::with_capacity(world_paths.len());
+    for path in world_paths {
+        let source =
+            std::fs::canonicalize(path).map_err(|err| format!("Cannot read {:?}: {err}", path))?;
+        if canonical_output.starts_with(&source) {
+            return Err(format!(
+                "The output directory {:?} cannot be inside {:?}",
+                output_dir, path
+            )
+            .into());
+        }
+        let name = source
+            .file_name()
+            .ok_or_else(|| format!("{:?} has no name to copy it under", path))?;
+        if !names.insert(name.to_os_string()) {
+            return Err(format!("Several of the given paths are named {name:?}, they cannot share the output directory")
+                .into());
+        }
+        let copy = output_dir.join(name);
+        if copy.exists() {
+            return Err(format!(
+                "{:?} already exists, give an output directory without an earlier copy",
+                copy
+            )
+            .into());
+        }
+        copies.push((source, copy));
+    }
+
+    let spinner = new_spinner("Listing the files to copy...");
+    let files: Vec<(PathBuf, PathBuf)> = copies
+        .iter()
+        .flat_map(|(source, copy)| files_to_copy(source, copy))
+        .collect();
+    spinner.finish_and_clear();
+
+    let pb = new_progress_bar(files.len() as u64);
+    let copied: Result<(), Box<dyn Error + Send + Sync>> =
+        files.par_iter().try_for_each(|(source, copy)| {
+            copy_file(source, copy)
+                .map_err(|err| format!("Cannot copy {:?} to {:?}: {err}", source, copy))?;
+            pb.inc(1);
+            Ok(())
+        });
+    pb.finish_and_clear();
+    copied.map_err(|err| err as Box<dyn Error>)?;
+    info!("Copied {} file(s) to {:?}", files.len(), output_dir);
+
+    Ok(copies.into_iter().map(|(_, copy)| copy).collect())
+}
+
+/// Every file below `source` with where its copy goes below `copy`, `source` itself if it is a file
+fn files_to_copy(source: &Path, copy: &Path) -> Vec<(PathBuf, PathBuf)> {
+    if !source.is_dir() {
+        return vec![(source.to_path_buf(), copy.to_path_buf())];
+    }
+    let mut files = Vec::new();
+    let mut directories = vec![(source.to_path_buf(), copy.to_path_buf())];
+    while let Some((directory, copy_directory)) = directories.pop() {
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!(
+                    "Cannot list {:?}, it is left out of the copy: {err}",
+                    directory
+                );
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let (path, copy_path) = (entry.path(), copy_directory.join(entry.file_name()));
+            let is_symlink = entry
+                .file_type()
+                .is_ok_and(|file_type| file_type.is_symlink());
+            if path.is_dir() && is_symlink {
+                // Could loop back to a parent
+                warn!(
+                    "Not following the symbolic link {:?}, it is left out of the copy",
+                    path
+                );
+            } else if path.is_dir() {
+                directories.push((path, copy_path));
+            } else {
+                files.push((path, copy_path));
+            }
+        }
+    }
+    files
+}
+
+fn copy_file(source: &Path, copy: &Path) -> std::io::Result<()> {
+    if let Some(parent) = copy.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(source, copy)?;
+    let modified = std::fs::metadata(source)?.modified()?;
+    File::options()
+        .write(true)
+        .open(copy)?
+        .set_modified(modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_to_output_dir() {
+        let root = std::env::temp_dir().join(format!("mwt_output_dir_{}", std::process::id()));
+        let world = root.join("saves/world");
+        std::fs::create_dir_all(world.join("region")).unwrap();
+        std::fs::write(world.join("level.dat"), b"level").unwrap();
+        std::fs::write(world.join("region/r.0.0.mca"), [1; 8192]).unwrap();
+        let output = root.join("trimmed");
+
+        let copies = copy_to_output_dir(std::slice::from_ref(&world), &output).unwrap();
+        assert_eq!(copies, vec![output.join("world")]);
+        assert_eq!(
+            std::fs::read(output.join("world/region/r.0.0.mca")).unwrap(),
+            [1; 8192]
+        );
+        let modified = |path: &Path| std::fs::metadata(path).unwrap().modified().unwrap();
+        assert_eq!(
+            modified(&output.join("world/level.dat")),
+            modified(&world.join("level.dat"))
+        );
+
+        // Never mixed with an earlier copy, nor copied into itself
+        assert!(copy_to_output_dir(std::slice::from_ref(&world), &output).is_err());
+        assert!(copy_to_output_dir(std::slice::from_ref(&world), &world.join("trimmed")).is_err());
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
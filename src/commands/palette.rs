@@ -0,0 +1,216 @@
+use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+use crate::region_loader::region::Region;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// Blocks of each id in the chunks of a region
+struct RegionPalette {
+    chunk_count: usize,
+    /// Blocks of every id
+    total_blocks: usize,
+    /// Number of blocks per block id (e.g. `minecraft:stone`), summed over the sections of every chunk
+    blocks: BTreeMap<String, usize>,
+    /// Only the blocks of these ids are in `blocks` (`--ids-from`)
+    is_filtered: bool,
+}
+
+impl RegionPalette {
+    fn from_region(region: &Region, block_ids: Option<&BTreeSet<String>>) -> Self {
+        let mut blocks: BTreeMap<String, usize> = BTreeMap::new();
+        let mut total_blocks = 0;
+        for chunk in region.get_chunks() {
+            for (block_id, count) in chunk.blocks() {
+                total_blocks += count;
+                if block_ids.is_some_and(|block_ids| !block_ids.contains(block_id)) {
+                    continue;
+                }
+                match blocks.get_mut(block_id) {
+                    Some(total) => *total += count,
+                    None => {
+                        blocks.insert(block_id.to_string(), count);
+                    }
+                }
+            }
+        }
+        RegionPalette {
+            chunk_count: region.get_chunk_count(),
+            total_blocks,
+            blocks,
+            is_filtered: block_ids.is_some(),
+        }
+    }
+}
+
+impl Display for RegionPalette {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let total = self.total_blocks;
+        write!(f, "  Chunks: {}\n  Blocks: {total}", self.chunk_count)?;
+        if total == 0 {
+            return write!(
+                f,
+                "\n  No 1.18+ sections, blocks of older chunks are not counted"
+            );
+        }
+        if self.is_filtered && self.blocks.is_empty() {
+            return write!(f, "\n  None of the listed block ids");
+        }
+
+        // Most common first, ids in alphabetical order on ties
+        let mut blocks: Vec<(&String, &usize)> = self.blocks.iter().collect();
+        blocks
+            .sort_by(|(id_a, count_a), (id_b, count_b)| count_b.cmp(count_a).then(id_a.cmp(id_b)));
+        for (block_id, count) in blocks {
+            write!(
+                f,
+                "\n  {block_id}: {count} ({:.2}%)",
+                *count as f64 * 100.0 / total as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints how many blocks of each id the given region files hold, from the palettes of their sections weighted by
+/// how often each entry is used, to find what makes one region so large. With `ids_from`, only the block ids listed
+/// in that file are reported, see [`parse_block_ids`]. Makes no change to the files
+pub fn execute_palette(
+    region_paths: &[PathBuf],
+    schemes: &SchemeMap,
+    ids_from: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = region_paths.iter().find(|path| !path.is_file()) {
+        return Err(format!("palette inspects region files, {path:?} is not a `.mca` file").into());
+    }
+    let block_ids = ids_from
+        .map(|path| {
+            let content = std::fs::read_to_string(path)
+                .map_err(|err| format!("Cannot read {path:?}: {err}"))?;
+            parse_block_ids(&content).map_err(|err| format!("{path:?}: {err}"))
+        })
+        .transpose()?;
+
+    println!("Region Palettes:");
+    for path in region_paths {
+        println!(
+            "{}:\n{}",
+            path.display(),
+            region_palette(path, schemes, block_ids.as_ref())?
+        );
+    }
+
+    Ok(())
+}
+
+fn region_palette(
+    region_file_path: &Path,
+    schemes: &SchemeMap,
+    block_ids: Option<&BTreeSet<String>>,
+) -> Result<RegionPalette, Box<dyn Error>> {
+    let region = Region::from_file_name(region_file_path, schemes)
+        .map_err(|err| format!("Cannot read the region file {region_file_path:?}: {err}"))?;
+    Ok(RegionPalette::from_region(&region, block_ids))
+}
+
+/// Reads a list of block ids, one per line. Blank lines and what follows a `#` are skipped, and ids without a
+/// namespace are taken from `minecraft:`
+fn parse_block_ids(content: &str) -> Result<BTreeSet<String>, String> {
+    let mut block_ids = BTreeSet::new();
+    for (index, line) in content.lines().enumerate() {
+        let id = line.split('#').next().unwrap_or_default().trim();
+        if id.is_empty() {
+            continue;
+        }
+        if id.contains(char::is_whitespace) {
+            return Err(format!(
+                "line {}: invalid block id {id:?}, expected e.g. `mod:ore`",
+                index + 1
+            ));
+        }
+        block_ids.insert(if id.contains(':') {
+            id.to_string()
+        } else {
+            format!("minecraft:{id}")
+        });
+    }
+    Ok(block_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_palette() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let region = Region::from_file_name(&path, &SchemeMap::default()).unwrap();
+        let palette = region_palette(&path, &SchemeMap::default(), None).unwrap();
+        assert_eq!(palette.chunk_count, region.get_chunk_count());
+
+        // Every section holds 16x16x16 blocks
+        let total: usize = palette.blocks.values().sum();
+        assert!(total > 0 && total % 4096 == 0, "{total}");
+        assert_eq!(palette.total_blocks, total);
+        let air = region
+            .get_chunks()
+            .iter()
+            .map(|chunk| chunk.count_block("minecraft:air"))
+            .sum();
+        assert_eq!(palette.blocks.get("minecraft:air"), Some(&air));
+
+        let report = palette.to_string();
+        let first_line = report.lines().nth(2).unwrap();
+        let most_common = palette.blocks.values().max().unwrap();
+        assert!(
+            first_line.contains(&format!(": {most_common} (")),
+            "{first_line}"
+        );
+
+        assert!(
+            execute_palette(
+                &[Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files")],
+                &SchemeMap::default(),
+                None
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_palette_of_listed_ids() {
+        let block_ids =
+            parse_block_ids("# Removed mods\nair\n\nminecraft:stone  # kept\noldmod:copper_ore\n")
+                .unwrap();
+        let expected = ["minecraft:air", "minecraft:stone", "oldmod:copper_ore"].map(String::from);
+        assert_eq!(block_ids, BTreeSet::from(expected));
+        assert!(
+            parse_block_ids("mod:a b")
+                .unwrap_err()
+                .starts_with("line 1:")
+        );
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let schemes = SchemeMap::default();
+        let everything = region_palette(&path, &schemes, None).unwrap();
+        let palette = region_palette(&path, &schemes, Some(&block_ids)).unwrap();
+        assert_eq!(palette.total_blocks, everything.total_blocks);
+        let kept: Vec<&String> = palette.blocks.keys().collect();
+        assert!(
+            !kept.is_empty() && kept.iter().all(|id| block_ids.contains(*id)),
+            "{kept:?}"
+        );
+        assert_eq!(
+            palette.blocks.get("minecraft:air"),
+            everything.blocks.get("minecraft:air")
+        );
+
+        let none = region_palette(
+            &path,
+            &schemes,
+            Some(&BTreeSet::from(["oldmod:copper_ore".to_string()])),
+        )
+        .unwrap();
+        assert!(none.to_string().ends_with("None of the listed block ids"));
+    }
+}
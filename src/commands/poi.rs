@@ -0,0 +1,220 @@
+use crate::commands::chunk_log::skip_if_unparsable;
+use crate::commands::optimize_result::OptimizeResult;
+use crate::commands::trim_options::TrimOptions;
+use crate::commands::write::{discard_region, rewrite_companion_region};
+use crate::region_loader::header_stats::occupied_slots;
+use crate::region_loader::region::{ParseRegionError, Region};
+use crate::{info, warn};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Path of the POI region file matching a region file: `<dimension>/region/r.X.Z.mca` pairs with
+/// `<dimension>/poi/r.X.Z.mca`
+fn poi_file_for(region_file: &Path) -> Option<PathBuf> {
+    let region_dir = region_file.parent()?;
+    if region_dir.file_name()? != "region" {
+        return None;
+    }
+    Some(
+        region_dir
+            .parent()?
+            .join("poi")
+            .join(region_file.file_name()?),
+    )
+}
+
+/// Index in the location table of the chunk at these absolute coordinates
+fn table_slot((x, z): (i32, i32)) -> usize {
+    ((x & 31) + (z & 31) * 32) as usize
+}
+
+/// Makes the POI region of a region file that was just written match it: POI chunks whose terrain chunk is gone
+/// are deleted, and records lying outside of their chunk are removed from the others.
+/// An emptied POI region is discarded like an empty region
+pub(crate) fn trim_poi(
+    region_file_path: &Path,
+    options: &TrimOptions,
+    result: &mut OptimizeResult,
+) {
+    let Some(poi_file_path) = poi_file_for(region_file_path).filter(|path| path.is_file()) else {
+        return;
+    };
+
+    // A deleted or invalid region has no chunk left to keep POI data for
+    let terrain_slots = if region_file_path.is_file() {
+        match occupied_slots(region_file_path) {
+            Ok(slots) => slots,
+            Err(ParseRegionError::HeaderError) => HashSet::new(),
+            Err(ParseRegionError::ReadError) => {
+                warn!(
+                    "Cannot read {:?}, leaving its POI data untouched",
+                    region_file_path
+                );
+                result.io_errors += 1;
+                return;
+            }
+        }
+    } else {
+        HashSet::new()
+    };
+
+    let mut poi = match Region::from_poi_file(&poi_file_path, &options.schemes) {
+        Ok(poi) => poi,
+        // Invalid POI files are rebuilt by the game, trimming does not deal with them
+        Err(ParseRegionError::HeaderError) => return,
+        Err(ParseRegionError::ReadError) => {
+            warn!("Cannot read {:?}", poi_file_path);
+            result.io_errors += 1;
+            return;
+        }
+    };
+    if skip_if_unparsable(&poi_file_path, &poi, &mut result.unparsable_chunks) {
+        return;
+    }
+    if poi
+        .get_chunks()
+        .iter()
+        .any(|chunk| chunk.get_position().is_err())
+    {
+        // Rewriting would lose the chunks that could not be located
+        warn!(
+            "Cannot locate the chunks of {:?}, leaving it untouched",
+            poi_file_path
+        );
+        return;
+    }
+
+    let dangling_indices: Vec<usize> = poi
+        .get_chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| {
+            chunk
+                .get_position()
+                .is_ok_and(|position| !terrain_slots.contains(&table_slot(position)))
+        })
+        .map(|(index, _)| index)
+        .collect();
+    for &index in dangling_indices.iter().rev() {
+        poi.remove_chunk_by_index(index);
+    }
+    let pruned_records = poi.prune_poi_records();
+
+    let original_len = std::fs::metadata(&poi_file_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+    if poi.is_empty() {
+        // Only the bytes and errors matter, the POI region is not one of the trimmed regions
+        let mut discarded = OptimizeResult::default();
+        discard_region(&poi_file_path, options, false, &mut discarded);
+        result.saved_bytes += discarded.saved_bytes;
+        if discarded.io_errors > 0 {
+            result.io_errors += discarded.io_errors;
+            return;
+        }
+    } else if poi.is_modified() {
+        match rewrite_companion_region(&poi_file_path, &poi, options) {
+            Ok(new_len) => {
+                info!(
+                    "Rewrote {:?} without {} POI chunk(s) and {} record(s)",
+                    poi_file_path,
+                    dangling_indices.len(),
+                    pruned_records
+                );
+                result.saved_bytes += original_len.saturating_sub(new_len);
+            }
+            Err(err) => {
+                warn!("Cannot write {:?}: {err}", poi_file_path);
+                result.io_errors += 1;
+                return;
+            }
+        }
+    }
+
+    result.deleted_poi_chunks += dangling_indices.len();
+    result.pruned_poi_records += pruned_records;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::write::optimize_write;
+    use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+    use crate::region_loader::compression_level::CompressionLevel;
+    use crate::region_loader::test_region::corrupt_chunk;
+
+    fn world_dir(name: &str) -> PathBuf {
+        let world = std::env::temp_dir().join(format!("mwt_poi_{name}_{}", std::process::id()));
+        std::fs::create_dir_all(world.join("region")).unwrap();
+        std::fs::create_dir_all(world.join("poi")).unwrap();
+        world
+    }
+
+    #[test]
+    fn test_poi_file_for() {
+        assert_eq!(
+            poi_file_for(Path::new("world/DIM-1/region/r.1.-2.mca")),
+            Some(PathBuf::from("world/DIM-1/poi/r.1.-2.mca"))
+        );
+        assert_eq!(poi_file_for(Path::new("backups/r.1.-2.mca")), None);
+    }
+
+    #[test]
+    fn test_poi_of_deleted_chunks_is_trimmed() {
+        let world = world_dir("trimmed");
+        let sample = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let region_file = world.join("region/r.-1.-1.mca");
+        let poi_file = world.join("poi/r.-1.-1.mca");
+
+        // Only the chunk in the first slot is left in the terrain region
+        let mut region = Region::from_file_name(&sample, &SchemeMap::default()).unwrap();
+        while region.get_chunk_count() > 1 {
+            region.remove_chunk_by_index(1);
+        }
+        let kept_position = region.get_chunks()[0].get_position().unwrap();
+        std::fs::write(
+            &region_file,
+            region.to_bytes(CompressionLevel::fast()).bytes,
+        )
+        .unwrap();
+        std::fs::copy(&sample, &poi_file).unwrap();
+
+        let mut result = OptimizeResult::default();
+        trim_poi(&region_file, &TrimOptions::default(), &mut result);
+        assert_eq!(result.deleted_poi_chunks, 1023);
+        assert_eq!(result.io_errors, 0);
+        let poi = Region::from_poi_file(&poi_file, &SchemeMap::default()).unwrap();
+        assert_eq!(poi.get_chunk_count(), 1);
+        assert_eq!(poi.get_chunks()[0].get_position(), Ok(kept_position));
+
+        // Once the terrain region is gone, so is its POI region
+        std::fs::write(&region_file, b"corrupted").unwrap();
+        let options = TrimOptions {
+            trim_poi: true,
+            ..TrimOptions::default()
+        };
+        let result = optimize_write(&region_file, &options, false);
+        assert_eq!(result.deleted_regions, 1);
+        assert_eq!(result.deleted_poi_chunks, 1);
+        assert!(!poi_file.exists());
+
+        // POI regions holding chunks that cannot be decoded are left untouched, they would be lost
+        std::fs::write(
+            &region_file,
+            region.to_bytes(CompressionLevel::fast()).bytes,
+        )
+        .unwrap();
+        let mut poi = std::fs::read(&sample).unwrap();
+        corrupt_chunk(&mut poi, 33);
+        std::fs::write(&poi_file, &poi).unwrap();
+        let mut result = OptimizeResult::default();
+        trim_poi(&region_file, &TrimOptions::default(), &mut result);
+        assert_eq!(
+            (result.deleted_poi_chunks, result.unparsable_chunks),
+            (0, 1)
+        );
+        assert_eq!(std::fs::read(&poi_file).unwrap(), poi);
+
+        std::fs::remove_dir_all(&world).ok();
+    }
+}
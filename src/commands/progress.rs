@@ -0,0 +1,150 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Spinners animate on a thread of their own. Where no more threads can be created, such as containers with a low
+/// thread limit, they only move when the progress changes instead of failing the run
+fn enable_steady_tick(bar: &ProgressBar) {
+    static CAN_SPAWN_THREADS: OnceLock<bool> = OnceLock::new();
+    let can_spawn_threads = *CAN_SPAWN_THREADS.get_or_init(|| {
+        std::thread::Builder::new()
+            .spawn(|| {})
+            .is_ok_and(|thread| thread.join().is_ok())
+    });
+    if can_spawn_threads {
+        bar.enable_steady_tick(Duration::from_millis(100));
+    }
+}
+
+/// Indeterminate spinner shown while the total amount of work is not known yet
+/// (e.g. while walking the world directories for region files).
+pub fn new_spinner(message: &'static str) -> ProgressBar {
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_message(message);
+    enable_steady_tick(&spinner);
+    spinner
+}
+
+pub fn new_progress_bar(len: u64) -> ProgressBar {
+    let pb = ProgressBar::new(len);
+    let style = ProgressStyle::with_template(
+        "{percent}% {bar} {pos}/{len} [{elapsed_precise}>{eta_precise}, {per_sec}]",
+    )
+    .unwrap();
+    pb.set_style(style);
+    pb
+}
+
+/// Progress of region files processed in parallel: a bar weighted by the size of the files, a few huge regions can
+/// take most of the time, and a line with the regions in flight and the throughput in MiB/s, which tells a run bound
+/// by the disk from one making steady progress
+pub struct RegionProgress {
+    // Draws both bars, it has to live as long as them
+    _multi: MultiProgress,
+    bar: ProgressBar,
+    status: ProgressBar,
+    total_regions: usize,
+    in_flight: AtomicUsize,
+    done: AtomicUsize,
+}
+
+/// A region file being processed, it counts as done when dropped
+pub struct RegionTicket<'a> {
+    progress: &'a RegionProgress,
+    len: u64,
+}
+
+impl RegionProgress {
+    pub fn new(region_files: &[PathBuf]) -> Self {
+        let total_bytes = region_files.iter().map(|path| file_len(path)).sum();
+        let multi = MultiProgress::new();
+        let bar = multi.add(ProgressBar::new(total_bytes));
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{percent}% {bar} {bytes}/{total_bytes} [{elapsed_precise}>{eta_precise}, {bytes_per_sec}]",
+            )
+            .unwrap(),
+        );
+        let status = multi.add(ProgressBar::new(total_bytes));
+        status.set_style(
+            ProgressStyle::with_template("{spinner} {msg}, {binary_bytes_per_sec}").unwrap(),
+        );
+        enable_steady_tick(&status);
+
+        let progress = Self {
+            _multi: multi,
+            bar,
+            status,
+            total_regions: region_files.len(),
+            in_flight: AtomicUsize::new(0),
+            done: AtomicUsize::new(0),
+        };
+        progress.update_status();
+        progress
+    }
+
+    /// Starts processing a region file. Its size is measured now, rewrites change it
+    pub fn start(&self, region_file: &Path) -> RegionTicket<'_> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        self.update_status();
+        RegionTicket {
+            progress: self,
+            len: file_len(region_file),
+        }
+    }
+
+    pub fn finish_and_clear(&self) {
+        self.status.finish_and_clear();
+        self.bar.finish_and_clear();
+    }
+
+    fn update_status(&self) {
+        self.status.set_message(format!(
+            "{} region(s) in flight, {}/{} done",
+            self.in_flight.load(Ordering::Relaxed),
+            self.done.load(Ordering::Relaxed),
+            self.total_regions
+        ));
+    }
+}
+
+impl Drop for RegionTicket<'_> {
+    fn drop(&mut self) {
+        let progress = self.progress;
+        progress.in_flight.fetch_sub(1, Ordering::Relaxed);
+        progress.done.fetch_add(1, Ordering::Relaxed);
+        progress.bar.inc(self.len);
+        progress.status.inc(self.len);
+        progress.update_status();
+    }
+}
+
+/// Size of a file on disk, 0 if it cannot be read
+pub fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_progress() {
+        let region_file = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let len = file_len(&region_file);
+        let progress = RegionProgress::new(&[region_file.clone(), region_file.clone()]);
+        assert_eq!(progress.bar.length(), Some(2 * len));
+
+        let first = progress.start(&region_file);
+        let second = progress.start(&region_file);
+        assert_eq!(progress.in_flight.load(Ordering::Relaxed), 2);
+        drop(first);
+        assert_eq!(progress.status.message(), "1 region(s) in flight, 1/2 done");
+        assert_eq!(progress.bar.position(), len);
+        drop(second);
+        assert_eq!(progress.status.position(), 2 * len);
+        progress.finish_and_clear();
+    }
+}
@@ -1,62 +1,579 @@
-use crate::commands::optimize_result::{reduce_optimize_results, OptimizeResult};
-use crate::region_loader::region::{ParseRegionError, Region};
-use crate::world::get_region_files::get_region_files;
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::commands::chunk_log::{
+    log_chunk_decision, print_chunk_decision_jsonl, print_chunk_explanation,
+};
+use crate::commands::chunk_map::{ChunkDecision, write_chunk_maps};
+use crate::commands::deletion_plan::write_plan;
+use crate::commands::memory_budget::MemoryBudget;
+use crate::commands::optimize_result::{OptimizeResult, reduce_optimize_results};
+use crate::commands::progress::{RegionProgress, new_spinner};
+use crate::commands::summary_file::{append_summary, print_summary};
+use crate::commands::trim_options::TrimOptions;
+use crate::commands::write::optimize_write;
+use crate::logger::trace_region;
+use crate::region_loader::region::{HEADER_SIZE, ParseRegionError, Region};
+use crate::world::archive::{is_archive, visit_region_entries};
+use crate::world::get_region_files::{get_region_files, warn_if_no_region_files};
+use crate::world::scan_options::ScanOptions;
+use crate::{info, warn};
 use rayon::iter::ParallelIterator;
 use rayon::prelude::IntoParallelRefIterator;
 use std::error::Error;
-use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-pub fn execute_read(world_paths: &[PathBuf]) -> Result<(), Box<dyn Error>> {
-    let entries = get_region_files(world_paths)?;
-    let pb = ProgressBar::new(entries.len() as u64);
-    let style = ProgressStyle::with_template(
-        "{percent}% {bar} {pos}/{len} [{elapsed_precise}>{eta_precise}, {per_sec}]",
-    )
-    .unwrap();
-    pb.set_style(style);
+/// What check produces besides its summary
+#[derive(Default)]
+pub struct CheckOutputs {
+    /// Print every deletable chunk as a `region,x,z` CSV line, fields separated by `csv_delimiter`
+    pub list: bool,
+    pub csv_delimiter: char,
+    /// Also run write on every region file without modifying it, and fail if it would not do what check predicts
+    pub self_check: bool,
+    /// Draw the kept and deletable chunks as PNG maps, one per dimension
+    pub map: Option<PathBuf>,
+    /// Save the deletable chunks as a deletion plan that write can replay
+    pub plan: Option<PathBuf>,
+}
+
+/// Reports what write would delete with the same options, and returns the predicted summary.
+/// World archives (`.zip`, `.tar.gz`...) among the paths are read in place, without extracting them.
+pub fn execute_read(
+    world_paths: &[PathBuf],
+    scan_options: &ScanOptions,
+    options: &TrimOptions,
+    outputs: &CheckOutputs,
+) -> Result<OptimizeResult, Box<dyn Error>> {
+    let CheckOutputs {
+        list,
+        csv_delimiter,
+        self_check,
+        ..
+    } = *outputs;
+    let collect_positions = list || outputs.map.is_some() || outputs.plan.is_some();
+    let (archives, world_paths): (Vec<PathBuf>, Vec<PathBuf>) = world_paths
+        .iter()
+        .cloned()
+        .partition(|path| path.is_file() && is_archive(path));
 
-    let mut results = entries
+    let spinner = new_spinner("Scanning worlds for region files...");
+    let entries = get_region_files(&world_paths, scan_options);
+    spinner.finish_and_clear();
+    let entries = entries?;
+    info!("Found {} region file(s)", entries.len());
+    let progress = RegionProgress::new(&entries);
+    let budget = MemoryBudget::new(options.max_memory);
+    // The write pass must not print its chunk decisions a second time
+    let dry_run_options = TrimOptions {
+        decisions_as_jsonl: false,
+        ..options.clone()
+    };
+    let disagreements = AtomicUsize::new(0);
+
+    let (mut results, chunk_decisions): (Vec<OptimizeResult>, Vec<Vec<ChunkDecision>>) = entries
         .par_iter()
         .map(|entry| {
-            let result = optimize_read(entry);
-            pb.inc(1);
+            let permit = budget.acquire(entry);
+            let ticket = progress.start(entry);
+            trace_region(entry);
+            let result = optimize_read(entry, options, collect_positions);
+            if self_check {
+                let written = optimize_write(entry, &dry_run_options, true);
+                if !write_matches_check(&result.0, &written) {
+                    warn!(
+                        "Self-check: check predicts {} deleted chunk(s) in {:?} but write would delete {}",
+                        result.0.deleted_chunks, entry, written.deleted_chunks
+                    );
+                    disagreements.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            drop(ticket);
+            drop(permit);
             result
         })
-        .collect::<Vec<OptimizeResult>>();
+        .unzip();
+    progress.finish_and_clear();
+    let mut chunk_decisions: Vec<(PathBuf, Vec<ChunkDecision>)> =
+        entries.into_iter().zip(chunk_decisions).collect();
+    // Write cannot modify archives, so a plan only covers the region files on disk
+    let region_files_on_disk = chunk_decisions.len();
+
+    for archive in &archives {
+        let spinner = new_spinner("Reading world archive...");
+        let archive_results = visit_region_entries(archive, scan_options, |entry, reader| {
+            trace_region(entry);
+            let (result, decisions) = check_region(
+                entry,
+                Region::from_reader(reader, &options.schemes),
+                options,
+                collect_positions,
+            );
+            (entry.to_path_buf(), result, decisions)
+        });
+        spinner.finish_and_clear();
+        match archive_results {
+            Ok(archive_results) => {
+                info!(
+                    "Read {} region file(s) from {:?}",
+                    archive_results.len(),
+                    archive
+                );
+                for (entry, result, decisions) in archive_results {
+                    results.push(result);
+                    chunk_decisions.push((entry, decisions));
+                }
+            }
+            Err(err) => {
+                warn!("Cannot read archive {:?}: {err}", archive);
+                results.push(OptimizeResult {
+                    io_errors: 1,
+                    ..OptimizeResult::default()
+                });
+            }
+        }
+    }
+    let all_paths: Vec<PathBuf> = world_paths.into_iter().chain(archives).collect();
+    warn_if_no_region_files(&all_paths, chunk_decisions.len());
+
+    if list {
+        let mut stdout = std::io::stdout().lock();
+        let d = csv_delimiter;
+        writeln!(stdout, "region{d}x{d}z")?;
+        for (entry, decisions) in &chunk_decisions {
+            let region = csv_field(
+                &forward_slashes(&entry.display().to_string(), MAIN_SEPARATOR),
+                d,
+            );
+            for ((x, z), _) in decisions.iter().filter(|(_, deleted)| *deleted) {
+                writeln!(stdout, "{region}{d}{x}{d}{z}")?;
+            }
+        }
+    }
+    if let Some(plan) = &outputs.plan {
+        write_plan(plan, &chunk_decisions[..region_files_on_disk])
+            .map_err(|err| format!("Cannot write the deletion plan {:?}: {err}", plan))?;
+        info!(
+            "Saved the deletion plan to {:?}, replay it with write --plan",
+            plan
+        );
+    }
+    if let Some(map) = &outputs.map {
+        write_chunk_maps(
+            map,
+            chunk_decisions.iter().flat_map(|(entry, decisions)| {
+                decisions
+                    .iter()
+                    .map(|&(position, deleted)| (entry.as_path(), position, deleted))
+            }),
+        );
+    }
 
     let result = reduce_optimize_results(&mut results);
-    println!("{result}");
+    print_summary(&all_paths, &result, options);
+    if let Some(summary_file) = &options.summary_file {
+        append_summary(summary_file, "check", &result);
+    }
 
-    Ok(())
+    if self_check {
+        match disagreements.into_inner() {
+            0 => info!("Self-check passed, write would do what check predicts"),
+            count => {
+                return Err(format!(
+                    "Self-check failed, check and write disagree on {count} region file(s)"
+                )
+                .into());
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Writes paths with `/` separators, so that a list made on Windows reads the same elsewhere.
+/// Backslashes are only separators where `separator` is one, elsewhere they may be part of file names
+pub(crate) fn forward_slashes(path: &str, separator: char) -> String {
+    if separator == '\\' {
+        path.replace('\\', "/")
+    } else {
+        path.to_string()
+    }
+}
+
+/// Quotes a CSV field when it holds the delimiter, a quote or a line break, doubling its quotes (RFC 4180)
+pub(crate) fn csv_field(value: &str, delimiter: char) -> String {
+    if value.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Compares what check predicts for a region with what write would do
+fn write_matches_check(check: &OptimizeResult, write: &OptimizeResult) -> bool {
+    // Check does not compress chunks, so it cannot predict regions left alone by --min-savings
+    write.skipped_regions > 0
+        || (check.total_chunks == write.total_chunks
+            && check.deleted_chunks == write.deleted_chunks
+            && check.deleted_regions == write.deleted_regions
+            && check.emptied_regions == write.emptied_regions
+            && check.rewritten_regions == write.rewritten_regions
+            && check.unchanged_regions == write.unchanged_regions)
 }
 
-fn optimize_read(region_file_path: &Path) -> OptimizeResult {
+/// Also returns the decisions taken for the chunks with a position when `collect_positions` is set
+fn optimize_read(
+    region_file_path: &Path,
+    options: &TrimOptions,
+    collect_positions: bool,
+) -> (OptimizeResult, Vec<ChunkDecision>) {
+    check_region(
+        region_file_path,
+        Region::from_file_name(region_file_path, &options.schemes),
+        options,
+        collect_positions,
+    )
+}
+
+/// Predicts what write would do with an already read region
+fn check_region(
+    region_file_path: &Path,
+    region: Result<Region, ParseRegionError>,
+    options: &TrimOptions,
+    collect_positions: bool,
+) -> (OptimizeResult, Vec<ChunkDecision>) {
     let mut result = OptimizeResult::default();
+    let mut decisions = Vec::new();
+    let context = options.region_context(region_file_path);
 
-    match Region::from_file_name(region_file_path) {
+    match region {
         Ok(region) => {
+            result.add_orphaned_sectors(region.get_orphaned_sectors());
+            result.add_malformed_chunks(region_file_path, region.get_chunks());
+            result.add_unparsable_chunks(region_file_path, region.get_parse_failures());
             let chunks = region.get_chunks();
             result.total_chunks += chunks.len();
+            let mut deleted_sectors = 0;
+            let mut kept_chunks = Vec::new();
 
             for chunk in chunks {
-                if chunk.should_delete() {
+                let verdict = options.verdict(chunk, &context);
+                if options.explain {
+                    print_chunk_explanation(region_file_path, chunk, verdict);
+                }
+                let keep_reason = verdict.keep_reason();
+                log_chunk_decision(region_file_path, chunk, keep_reason);
+                if options.decisions_as_jsonl {
+                    print_chunk_decision_jsonl(region_file_path, chunk, keep_reason);
+                }
+                if keep_reason.is_none() {
                     result.deleted_chunks += 1;
+                    deleted_sectors += chunk.location.get_sector_count() as usize;
+                } else {
+                    kept_chunks.push(chunk);
+                    if let Some(y) = options.delete_below_y {
+                        result.removed_sections += chunk.sections_below(y);
+                    }
+                }
+                if let (true, Ok(position)) = (collect_positions, chunk.get_position()) {
+                    decisions.push((position, keep_reason.is_none()));
                 }
             }
-            if result.deleted_chunks >= result.total_chunks {
-                result.deleted_regions += 1;
+            // Like write, leave a region alone rather than lose the chunks it cannot place in the rewritten file
+            let is_rewritten = (result.deleted_chunks > 0 || result.removed_sections > 0)
+                && !kept_chunks.is_empty();
+            let unplaceable = if is_rewritten {
+                Region::count_unplaceable(kept_chunks)
+            } else {
+                0
+            };
+            if unplaceable > 0 {
+                warn!(
+                    "Cannot place {unplaceable} chunk(s) in the rewritten {:?}, write will leave it untouched",
+                    region_file_path
+                );
+                result.header_write_failures += unplaceable;
+                result.regions_with_header_issues += 1;
+                result.deleted_chunks = 0;
+                result.removed_sections = 0;
+                deleted_sectors = 0;
             }
+            let usage = region.sector_usage();
+            // A rewrite keeps only the sectors of the remaining chunks, which also drops the orphaned ones
+            let is_minimal_empty_region = options.keep_empty_regions && region.is_header_only();
+            let saved_sectors = if is_minimal_empty_region {
+                // Like write, leave a header-only region alone rather than counting it as emptied
+                result.unchanged_regions += 1;
+                0
+            } else if result.deleted_chunks >= result.total_chunks {
+                result.add_discarded_region(options.keep_empty_regions);
+                let header_sectors = if options.keep_empty_regions {
+                    HEADER_SIZE / 4096
+                } else {
+                    0
+                };
+                usage.total_sectors.saturating_sub(header_sectors)
+            } else if unplaceable > 0 {
+                0
+            } else if is_rewritten {
+                result.rewritten_regions += 1;
+                if result.deleted_chunks > 0 {
+                    usage
+                        .total_sectors
+                        .saturating_sub(usage.used_sectors - deleted_sectors)
+                } else {
+                    0
+                }
+            } else {
+                result.unchanged_regions += 1;
+                0
+            };
+            result.saved_bytes += (saved_sectors * 4096) as u64;
         }
         Err(ParseRegionError::HeaderError) => {
             // Plik za mały / uszkodzony nagłówek — w trybie write zostanie skasowany.
-            result.deleted_regions += 1;
+            result.add_discarded_region(options.keep_empty_regions);
         }
         Err(ParseRegionError::ReadError) => {
             // Błąd I/O (np. brak uprawnień, zerwane łącze sieciowe) — nie do skasowania.
+            warn!("Cannot read {:?}", region_file_path);
             result.io_errors += 1;
         }
     }
 
-    result
+    (result, decisions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::Tag;
+    use crate::region_loader::compression_level::CompressionLevel;
+    use crate::region_loader::region::gzip_file;
+    use crate::region_loader::test_region::{TestChunk, compound, int, string, test_region_bytes};
+    use flate2::Compression;
+    use flate2::read::ZlibEncoder;
+    use std::io::Read;
+
+    /// Region holding a single chunk with the given root tags, stored in the table slot of (x, z)
+    fn single_chunk_region((x, z): (i32, i32), tags: Vec<Tag>) -> Vec<u8> {
+        let nbt = compound("", tags);
+        let mut payload = Vec::new();
+        ZlibEncoder::new(&nbt.to_bytes()[..], Compression::fast())
+            .read_to_end(&mut payload)
+            .unwrap();
+
+        let mut bytes = vec![0_u8; HEADER_SIZE];
+        let slot = 4 * ((x & 31) + (z & 31) * 32) as usize;
+        bytes[slot..slot + 4].copy_from_slice(&[0, 0, 2, 1]);
+        bytes.extend(((payload.len() + 1) as u32).to_be_bytes());
+        bytes.push(2);
+        bytes.extend(payload);
+        bytes.resize(3 * 4096, 0);
+        bytes
+    }
+
+    fn status(status: &str) -> Tag {
+        string("Status", status)
+    }
+
+    /// Region with a single proto-chunk at (x, z): never inhabited and not fully generated
+    fn proto_chunk_region(x: i32, z: i32) -> Vec<u8> {
+        single_chunk_region(
+            (x, z),
+            vec![int("xPos", x), int("zPos", z), status("minecraft:features")],
+        )
+    }
+
+    #[test]
+    fn test_list_deletable_chunks() {
+        let path = std::env::temp_dir().join(format!("mwt_list_{}.mca", std::process::id()));
+        std::fs::write(&path, proto_chunk_region(-3, 5)).unwrap();
+
+        let (result, listed) = optimize_read(&path, &TrimOptions::default(), true);
+        assert_eq!(result.deleted_chunks, 1);
+        assert_eq!(result.saved_bytes, 3 * 4096);
+        assert_eq!(listed, vec![((-3, 5), true)]);
+
+        let (_, not_listed) = optimize_read(&path, &TrimOptions::default(), false);
+        assert!(not_listed.is_empty());
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_ignore_inhabited() {
+        let path = std::env::temp_dir().join(format!("mwt_inhabited_{}.mca", std::process::id()));
+        let inhabited_time = Tag::Long {
+            name: Some("InhabitedTime".to_string()),
+            value: 200,
+        };
+        std::fs::write(
+            &path,
+            single_chunk_region(
+                (0, 0),
+                vec![
+                    int("xPos", 0),
+                    int("zPos", 0),
+                    status("minecraft:features"),
+                    inhabited_time,
+                ],
+            ),
+        )
+        .unwrap();
+
+        let (result, _) = optimize_read(&path, &TrimOptions::default(), false);
+        assert_eq!(result.deleted_chunks, 0);
+
+        let options = TrimOptions {
+            ignore_inhabited: true,
+            ..TrimOptions::default()
+        };
+        let (result, _) = optimize_read(&path, &options, false);
+        assert_eq!(result.deleted_chunks, 1);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_gzipped_header_only_region_is_unchanged() {
+        let path =
+            std::env::temp_dir().join(format!("mwt_header_only_{}.mca.gz", std::process::id()));
+        let header = gzip_file(CompressionLevel::fast(), |encoder| {
+            encoder.write_all(&[0; HEADER_SIZE])
+        })
+        .unwrap();
+        std::fs::write(&path, header).unwrap();
+        let options = TrimOptions {
+            keep_empty_regions: true,
+            ..TrimOptions::default()
+        };
+
+        let (result, _) = optimize_read(&path, &options, false);
+        assert_eq!(result.emptied_regions, 0);
+        assert_eq!(result.unchanged_regions, 1);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_malformed_chunks() {
+        let path = std::env::temp_dir().join(format!("mwt_malformed_{}.mca", std::process::id()));
+        std::fs::write(
+            &path,
+            single_chunk_region((0, 0), vec![status("minecraft:full")]),
+        )
+        .unwrap();
+
+        let (result, _) = optimize_read(&path, &TrimOptions::default(), false);
+        assert_eq!(result.malformed_chunks, 1);
+        assert_eq!(result.deleted_chunks, 0);
+
+        let options = TrimOptions {
+            delete_malformed: true,
+            ..TrimOptions::default()
+        };
+        let (result, _) = optimize_read(&path, &options, false);
+        assert_eq!(result.malformed_chunks, 1);
+        assert_eq!(result.deleted_chunks, 1);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_region_with_malformed_chunk_is_left_untouched() {
+        let path = std::env::temp_dir().join(format!("mwt_unplaceable_{}.mca", std::process::id()));
+        // A malformed chunk in slot (0, 0) next to a proto-chunk in slot (1, 0)
+        let mut region = single_chunk_region((0, 0), vec![status("minecraft:full")]);
+        let proto_chunk = proto_chunk_region(1, 0);
+        region[4..8].copy_from_slice(&[0, 0, 3, 1]);
+        region.extend(&proto_chunk[HEADER_SIZE..HEADER_SIZE + 4096]);
+        std::fs::write(&path, &region).unwrap();
+
+        let (checked, _) = optimize_read(&path, &TrimOptions::default(), false);
+        assert_eq!(
+            (checked.deleted_chunks, checked.header_write_failures),
+            (0, 1)
+        );
+        // Rewriting the region without the proto-chunk would lose the malformed chunk
+        let written = optimize_write(&path, &TrimOptions::default(), false);
+        assert_eq!(
+            (written.deleted_chunks, written.header_write_failures),
+            (0, 1)
+        );
+        assert!(write_matches_check(&checked, &written));
+        assert_eq!(std::fs::read(&path).unwrap(), region);
+
+        let options = TrimOptions {
+            delete_malformed: true,
+            ..TrimOptions::default()
+        };
+        assert_eq!(optimize_write(&path, &options, false).deleted_chunks, 2);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(
+            csv_field("world/region/r.0.0.mca", ','),
+            "world/region/r.0.0.mca"
+        );
+        assert_eq!(
+            csv_field(r"C:\My World, old\region", ','),
+            r#""C:\My World, old\region""#
+        );
+        assert_eq!(
+            csv_field(r"C:\My World, old\region", ';'),
+            r"C:\My World, old\region"
+        );
+        assert_eq!(
+            csv_field(r#"the "best" world"#, ';'),
+            r#""the ""best"" world""#
+        );
+    }
+
+    #[test]
+    fn test_forward_slashes() {
+        assert_eq!(
+            forward_slashes(r"C:\saves\My World\region\r.0.0.mca", '\\'),
+            "C:/saves/My World/region/r.0.0.mca"
+        );
+        assert_eq!(
+            forward_slashes(r"saves/odd\name/r.0.0.mca", '/'),
+            r"saves/odd\name/r.0.0.mca"
+        );
+    }
+
+    #[test]
+    fn test_check_matches_dry_run_of_write() {
+        let path = std::env::temp_dir().join(format!("mwt_self_check_{}.mca", std::process::id()));
+        let options = TrimOptions {
+            keep_empty_regions: true,
+            ..TrimOptions::default()
+        };
+
+        let trimmed_region = test_region_bytes(&[
+            TestChunk::full(0, 0),
+            TestChunk::full(1, 0).status("minecraft:features"),
+        ]);
+        let regions = [
+            (proto_chunk_region(1, 2), (0, 0)),
+            (vec![0_u8; HEADER_SIZE], (0, 1)),
+            (trimmed_region, (1, 0)),
+            (test_region_bytes(&[TestChunk::full(0, 0)]), (0, 1)),
+        ];
+        for (region, rewritten_and_unchanged) in regions {
+            std::fs::write(&path, &region).unwrap();
+            let (checked, _) = optimize_read(&path, &options, false);
+            let written = optimize_write(&path, &options, true);
+            assert!(write_matches_check(&checked, &written));
+            assert_eq!(
+                (written.rewritten_regions, written.unchanged_regions),
+                rewritten_and_unchanged
+            );
+            assert_eq!(std::fs::read(&path).unwrap(), region);
+        }
+
+        std::fs::remove_file(&path).ok();
+    }
 }
@@ -0,0 +1,283 @@
+use crate::commands::optimize_result::OptimizeResult;
+use crate::commands::write::atomic_write_region;
+use crate::world::forced_chunks::forced_chunks_file;
+use crate::{info, warn};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// Name of the cache file in the `--cache` directory
+const CACHE_FILE_NAME: &str = "clean-regions";
+
+/// Identity of a region file: it is assumed unchanged as long as its size and modification time are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileIdentity {
+    len: u64,
+    /// Nanoseconds since the Unix epoch
+    modified: u128,
+}
+
+impl FileIdentity {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        let modified = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+        Some(Self {
+            len: metadata.len(),
+            modified,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CleanRegion {
+    identity: FileIdentity,
+    /// Identity of the `chunks.dat` of the dimension when the region was found clean, `None` if it had none.
+    /// Unforcing a chunk can make it deletable without its region changing
+    forced_chunks: Option<FileIdentity>,
+    chunk_count: usize,
+}
+
+/// Identity of the list of force-loaded chunks of the dimension of a region file
+fn forced_chunks_identity(region_file_path: &Path) -> Option<FileIdentity> {
+    forced_chunks_file(region_file_path).and_then(|chunks_dat| FileIdentity::of(&chunks_dat))
+}
+
+/// Regions in which a write found nothing to change (`--cache`), so that later runs with the same options skip them
+/// until they change. The cache file starts with the options it was built with and is ignored when they differ
+pub(crate) struct RegionCache {
+    file: PathBuf,
+    /// Options deciding what is changed in a region, see [`crate::commands::trim_options::TrimOptions::cache_key`]
+    options: String,
+    /// Clean regions by canonical path
+    regions: Mutex<HashMap<PathBuf, CleanRegion>>,
+}
+
+impl RegionCache {
+    /// Loads the cache of the directory, empty when there is none yet or when it was built with other options
+    pub(crate) fn open(directory: &Path, options: String) -> Self {
+        let file = directory.join(CACHE_FILE_NAME);
+        let regions = match std::fs::read_to_string(&file) {
+            Ok(content) => match parse_cache(&content, &options) {
+                Some(regions) => regions,
+                None => {
+                    info!(
+                        "The options changed since {:?} was written, processing every region again",
+                        file
+                    );
+                    HashMap::new()
+                }
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => {
+                warn!(
+                    "Cannot read the cache {:?}, processing every region: {err}",
+                    file
+                );
+                HashMap::new()
+            }
+        };
+        Self {
+            file,
+            options,
+            regions: Mutex::new(regions),
+        }
+    }
+
+    /// Result of a region found clean by an earlier run and unchanged since, `None` if it has to be processed
+    pub(crate) fn cached_result(&self, region_file_path: &Path) -> Option<OptimizeResult> {
+        let clean = *self
+            .regions
+            .lock()
+            .unwrap()
+            .get(&canonical(region_file_path))?;
+        let is_unchanged = FileIdentity::of(region_file_path) == Some(clean.identity)
+            && forced_chunks_identity(region_file_path) == clean.forced_chunks;
+        is_unchanged.then(|| OptimizeResult {
+            total_chunks: clean.chunk_count,
+            cached_regions: 1,
+            ..OptimizeResult::default()
+        })
+    }
+
+    /// Remembers a processed region as clean if nothing was changed or left to change in it, forgets it otherwise
+    pub(crate) fn record(&self, region_file_path: &Path, result: &OptimizeResult) {
+        let is_clean = !result.has_changes()
+            && result.io_errors == 0
+            && result.skipped_regions == 0
+            && result.header_write_failures == 0;
+        let identity = FileIdentity::of(region_file_path).filter(|_| is_clean);
+        let mut regions = self.regions.lock().unwrap();
+        match identity {
+            Some(identity) => regions.insert(
+                canonical(region_file_path),
+                CleanRegion {
+                    identity,
+                    forced_chunks: forced_chunks_identity(region_file_path),
+                    chunk_count: result.total_chunks,
+                },
+            ),
+            None => regions.remove(&canonical(region_file_path)),
+        };
+    }
+
+    /// Writes the cache file back, creating its directory if needed
+    pub(crate) fn save(&self) {
+        let regions = self.regions.lock().unwrap();
+        let saved =
+            std::fs::create_dir_all(self.file.parent().unwrap_or(Path::new("."))).and_then(|()| {
+                atomic_write_region(&self.file, |writer| {
+                    writeln!(writer, "options {}", self.options)?;
+                    for (path, clean) in regions.iter() {
+                        let path = path.to_string_lossy();
+                        // A line per region, a path holding a line break would break the file
+                        if !path.contains('\n') {
+                            let FileIdentity { len, modified } = clean.identity;
+                            let forced = match clean.forced_chunks {
+                                Some(forced) => format!("{}:{}", forced.len, forced.modified),
+                                None => "-".to_string(),
+                            };
+                            writeln!(
+                                writer,
+                                "{len}\t{modified}\t{forced}\t{}\t{path}",
+                                clean.chunk_count
+                            )?;
+                        }
+                    }
+                    Ok(())
+                })
+            });
+        if let Err(err) = saved {
+            warn!("Cannot write the cache {:?}: {err}", self.file);
+        }
+    }
+}
+
+/// Reads the clean regions of a cache file built with `options`, `None` if it was built with other options.
+/// Lines that cannot be read are skipped, their region is processed again
+fn parse_cache(content: &str, options: &str) -> Option<HashMap<PathBuf, CleanRegion>> {
+    let mut lines = content.lines();
+    if lines.next()?.strip_prefix("options ")? != options {
+        return None;
+    }
+    let regions = lines
+        .filter_map(|line| {
+            let mut fields = line.splitn(5, '\t');
+            let identity = FileIdentity {
+                len: fields.next()?.parse().ok()?,
+                modified: fields.next()?.parse().ok()?,
+            };
+            let forced_chunks = match fields.next()? {
+                "-" => None,
+                forced => {
+                    let (len, modified) = forced.split_once(':')?;
+                    Some(FileIdentity {
+                        len: len.parse().ok()?,
+                        modified: modified.parse().ok()?,
+                    })
+                }
+            };
+            let chunk_count = fields.next()?.parse().ok()?;
+            let path = PathBuf::from(fields.next()?);
+            Some((
+                path,
+                CleanRegion {
+                    identity,
+                    forced_chunks,
+                    chunk_count,
+                },
+            ))
+        })
+        .collect();
+    Some(regions)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_cache() {
+        let tmp_dir = std::env::temp_dir().join(format!("mwt_region_cache_{}", std::process::id()));
+        std::fs::create_dir_all(tmp_dir.join("region")).unwrap();
+        let region = tmp_dir.join("region/r.0.0.mca");
+        std::fs::write(&region, [0; 8192]).unwrap();
+        let cache_dir = tmp_dir.join("cache");
+        let clean = OptimizeResult {
+            total_chunks: 12,
+            orphaned_sectors: 2,
+            ..OptimizeResult::default()
+        };
+
+        let cache = RegionCache::open(&cache_dir, "a".to_string());
+        assert!(cache.cached_result(&region).is_none());
+        cache.record(&region, &clean);
+        cache.save();
+
+        let cache = RegionCache::open(&cache_dir, "a".to_string());
+        let cached = cache.cached_result(&region).unwrap();
+        assert_eq!(
+            (
+                cached.total_chunks,
+                cached.cached_regions,
+                cached.orphaned_sectors
+            ),
+            (12, 1, 0)
+        );
+        assert!(
+            RegionCache::open(&cache_dir, "b".to_string())
+                .cached_result(&region)
+                .is_none()
+        );
+
+        // Forcing or unforcing chunks in its dimension makes the region processed again
+        std::fs::create_dir_all(tmp_dir.join("data")).unwrap();
+        std::fs::write(tmp_dir.join("data/chunks.dat"), b"forced").unwrap();
+        assert!(cache.cached_result(&region).is_none());
+        cache.record(&region, &clean);
+        assert!(cache.cached_result(&region).is_some());
+
+        // Any change of the file makes it processed again
+        std::fs::write(&region, [0; 4096]).unwrap();
+        assert!(cache.cached_result(&region).is_none());
+
+        cache.record(
+            &region,
+            &OptimizeResult {
+                deleted_chunks: 1,
+                ..clean
+            },
+        );
+        cache.save();
+        assert_eq!(
+            std::fs::read_to_string(cache_dir.join(CACHE_FILE_NAME)).unwrap(),
+            "options a\n"
+        );
+
+        let regions = parse_cache(
+            "options a\n1\t2\t-\t3\tworld/r.0.0.mca\n1\t2\t5:6\t3\tr.1.0.mca\nbroken\n",
+            "a",
+        );
+        let regions = regions.unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(
+            regions[Path::new("r.1.0.mca")].forced_chunks,
+            Some(FileIdentity {
+                len: 5,
+                modified: 6
+            })
+        );
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+}
@@ -0,0 +1,239 @@
+use crate::commands::chunk_log::skip_if_unparsable;
+use crate::commands::progress::{RegionProgress, new_spinner};
+use crate::commands::write::{RegionPayload, atomic_write_region, confirm_write};
+use crate::logger::trace_region;
+use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+use crate::region_loader::compression_level::CompressionLevel;
+use crate::region_loader::region::{ParseRegionError, Region};
+use crate::world::get_region_files::{get_region_files, warn_if_no_region_files};
+use crate::world::scan_options::ScanOptions;
+use crate::{info, warn};
+use rayon::iter::ParallelIterator;
+use rayon::prelude::IntoParallelRefIterator;
+use std::error::Error;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+#[derive(Default, Clone)]
+struct SalvageResult {
+    scanned_regions: usize,
+    rebuilt_regions: usize,
+    recovered_chunks: usize,
+    unparsable_chunks: usize,
+    io_errors: usize,
+}
+
+impl Display for SalvageResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Salvage Result:\n\
+                   Scanned Regions: {}\n\
+                   Rebuilt Regions: {}\n\
+                   Recovered Chunks: {}\n\
+                   Unparsable Chunks: {}\n\
+                   I/O Errors: {}",
+            self.scanned_regions,
+            self.rebuilt_regions,
+            self.recovered_chunks,
+            self.unparsable_chunks,
+            self.io_errors
+        )
+    }
+}
+
+/// Rebuilds the regions whose location table lost track of chunks that are still readable in the file
+pub fn execute_salvage(
+    world_paths: &[PathBuf],
+    scan_options: &ScanOptions,
+    compression: CompressionLevel,
+    schemes: &SchemeMap,
+    assume_yes: bool,
+) -> Result<(), Box<dyn Error>> {
+    let spinner = new_spinner("Scanning worlds for region files...");
+    let entries = get_region_files(world_paths, scan_options);
+    spinner.finish_and_clear();
+    let entries = entries?;
+    info!("Found {} region file(s)", entries.len());
+    warn_if_no_region_files(world_paths, entries.len());
+
+    if !assume_yes
+        && !confirm_write(
+            world_paths,
+            entries.len(),
+            &mut std::io::stdin().lock(),
+            &mut std::io::stderr(),
+        )?
+    {
+        return Err(
+            "Salvage aborted, nothing was modified (use --yes to skip the confirmation)".into(),
+        );
+    }
+
+    let progress = RegionProgress::new(&entries);
+    let result = entries
+        .par_iter()
+        .map(|entry| {
+            let ticket = progress.start(entry);
+            trace_region(entry);
+            let result = salvage_region(entry, compression, schemes);
+            drop(ticket);
+            result
+        })
+        .reduce(SalvageResult::default, |mut acc, cur| {
+            acc.scanned_regions += cur.scanned_regions;
+            acc.rebuilt_regions += cur.rebuilt_regions;
+            acc.recovered_chunks += cur.recovered_chunks;
+            acc.unparsable_chunks += cur.unparsable_chunks;
+            acc.io_errors += cur.io_errors;
+            acc
+        });
+    progress.finish_and_clear();
+
+    println!("{result}");
+
+    Ok(())
+}
+
+fn salvage_region(
+    region_file_path: &Path,
+    compression: CompressionLevel,
+    schemes: &SchemeMap,
+) -> SalvageResult {
+    let mut result = SalvageResult {
+        scanned_regions: 1,
+        ..SalvageResult::default()
+    };
+
+    let (salvaged, recovered) = match Region::salvage_file(region_file_path, schemes) {
+        Ok(salvaged) => salvaged,
+        Err(ParseRegionError::HeaderError) => return result,
+        Err(ParseRegionError::ReadError) => {
+            warn!("Cannot read {:?}", region_file_path);
+            result.io_errors += 1;
+            return result;
+        }
+    };
+
+    // Only rebuild when the scan finds chunks at positions the location table does not hold
+    if recovered == 0 {
+        return result;
+    }
+    if skip_if_unparsable(region_file_path, &salvaged, &mut result.unparsable_chunks) {
+        return result;
+    }
+
+    let written = RegionPayload::new(&salvaged, compression).and_then(|payload| {
+        atomic_write_region(region_file_path, |writer| payload.write_to(writer))
+    });
+    match written {
+        Ok(()) => {
+            info!("Recovered {} chunk(s) in {:?}", recovered, region_file_path);
+            result.rebuilt_regions += 1;
+            result.recovered_chunks += recovered;
+        }
+        Err(err) => {
+            warn!("Cannot write {:?}: {err}", region_file_path);
+            result.io_errors += 1;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_loader::chunk_loader::compression_scheme::CompressionScheme;
+    use crate::region_loader::region::HEADER_SIZE;
+
+    #[test]
+    fn test_salvage_rebuilds_zeroed_header() {
+        let tmp_dir = std::env::temp_dir().join(format!("mwt_salvage_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let intact = tmp_dir.join("r.-1.-1.mca");
+        let zeroed = tmp_dir.join("r.-1.0.mca");
+        let mut bytes = include_bytes!("../../test_files/r.-1.-1.mca").to_vec();
+        std::fs::write(&intact, &bytes).unwrap();
+        bytes[0..HEADER_SIZE].fill(0);
+        std::fs::write(&zeroed, &bytes).unwrap();
+
+        let untouched = salvage_region(&intact, CompressionLevel::fast(), &SchemeMap::default());
+        assert_eq!(untouched.rebuilt_regions, 0);
+
+        let rebuilt = salvage_region(&zeroed, CompressionLevel::fast(), &SchemeMap::default());
+        assert_eq!(rebuilt.rebuilt_regions, 1);
+        assert_eq!(rebuilt.recovered_chunks, 1024);
+        let region = Region::from_file_name(&zeroed, &SchemeMap::default())
+            .expect("rebuilt region must parse");
+        assert_eq!(region.get_chunk_count(), 1024);
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+
+    #[test]
+    fn test_salvage_keeps_external_chunks() {
+        let sample = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let original = Region::from_file_name(&sample, &SchemeMap::default()).unwrap();
+        let slot = |(x, z): (i32, i32)| (4 * ((x & 31) + (z & 31) * 32)) as usize;
+        let external = &original.get_chunks()[0];
+        let lost = &original.get_chunks()[1];
+        let external_position = external.get_position().unwrap();
+        let external_frame = external.to_original_bytes();
+
+        // The external chunk sits in sector 2 and is listed in the table, the lost one follows without an entry
+        let mut bytes = vec![0_u8; HEADER_SIZE];
+        let external_slot = slot(external_position);
+        bytes[external_slot..external_slot + 4].copy_from_slice(&[0, 0, 2, 1]);
+        bytes.extend([
+            0,
+            0,
+            0,
+            1,
+            external_frame[4] | CompressionScheme::EXTERNAL_FLAG,
+        ]);
+        bytes.resize(3 * 4096, 0);
+        bytes.extend(lost.to_original_bytes());
+        bytes.resize(bytes.len().div_ceil(4096) * 4096, 0);
+
+        let tmp_dir = std::env::temp_dir().join(format!("mwt_salvage_mcc_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let region_file = tmp_dir.join("r.-1.-1.mca");
+        std::fs::write(&region_file, &bytes).unwrap();
+        let (x, z) = external_position;
+        let mcc_file = tmp_dir.join(format!("c.{x}.{z}.mcc"));
+        std::fs::write(&mcc_file, &external_frame[5..]).unwrap();
+
+        let rebuilt = salvage_region(
+            &region_file,
+            CompressionLevel::fast(),
+            &SchemeMap::default(),
+        );
+        assert_eq!(rebuilt.rebuilt_regions, 1);
+        assert_eq!(rebuilt.recovered_chunks, 1);
+        let region = Region::from_file_name(&region_file, &SchemeMap::default())
+            .expect("rebuilt region must parse");
+        assert_eq!(region.get_chunk_count(), 2);
+        let kept = region
+            .get_chunks()
+            .iter()
+            .find(|chunk| chunk.get_position() == Ok(external_position))
+            .expect("the external chunk must be kept");
+        assert!(kept.is_external());
+        assert_eq!(kept.nbt, external.nbt);
+        assert!(mcc_file.exists());
+
+        // Nothing is left to recover
+        assert_eq!(
+            salvage_region(
+                &region_file,
+                CompressionLevel::fast(),
+                &SchemeMap::default()
+            )
+            .rebuilt_regions,
+            0
+        );
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+}
@@ -0,0 +1,371 @@
+use crate::commands::progress::{new_progress_bar, new_spinner};
+use crate::commands::read::{csv_field, forward_slashes};
+use crate::logger::trace_region;
+use crate::region_loader::header_stats::occupied_slots;
+use crate::region_loader::region::{ParseRegionError, region_coordinates};
+use crate::world::get_region_files::{get_region_files, warn_if_no_region_files};
+use crate::world::scan_options::ScanOptions;
+use crate::{info, warn};
+use rayon::iter::ParallelIterator;
+use rayon::prelude::IntoParallelRefIterator;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::Display;
+use std::io::{BufWriter, Write};
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+
+/// Chunk coordinates of every region file of a snapshot, regions named the way `check --list` names them
+pub type Snapshot = BTreeMap<String, BTreeSet<(i32, i32)>>;
+
+/// A chunk of a region, as listed in a snapshot
+type ListedChunk = (String, i32, i32);
+
+/// What snapshot does with the chunks of the worlds
+#[derive(Default)]
+pub struct SnapshotOptions {
+    /// Save the chunks of the worlds to this file
+    pub save: Option<PathBuf>,
+    /// Report the chunks that appeared or disappeared since this earlier snapshot
+    pub compare: Option<PathBuf>,
+    /// Fail unless the chunks that disappeared since `compare` are exactly those of this `check --list` output
+    pub expect_removed: Option<PathBuf>,
+    /// Print every chunk that appeared or disappeared as a `change,region,x,z` CSV line
+    pub list: bool,
+    pub csv_delimiter: char,
+}
+
+/// Differences between two snapshots
+#[derive(Default)]
+struct SnapshotDiff {
+    chunks_before: usize,
+    chunks_now: usize,
+    appeared: Vec<ListedChunk>,
+    disappeared: Vec<ListedChunk>,
+}
+
+impl Display for SnapshotDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Snapshot Comparison:\n\
+                   Chunks Before: {}\n\
+                   Chunks Now: {}\n\
+                   Appeared Chunks: {}\n\
+                   Disappeared Chunks: {}",
+            self.chunks_before,
+            self.chunks_now,
+            self.appeared.len(),
+            self.disappeared.len()
+        )
+    }
+}
+
+/// Records which chunks the worlds hold from the region headers only, and compares them with an earlier snapshot.
+/// Snapshots are `region,x,z` CSV files like the output of `check --list`, so the chunks removed by a write can be
+/// checked against the chunks check listed beforehand
+pub fn execute_snapshot(
+    world_paths: &[PathBuf],
+    scan_options: &ScanOptions,
+    options: &SnapshotOptions,
+) -> Result<(), Box<dyn Error>> {
+    if options.save.is_none() && options.compare.is_none() {
+        return Err("Nothing to do, give --save-snapshot and/or --compare-snapshot".into());
+    }
+    let d = options.csv_delimiter;
+    // Read before saving, so that the same file can be compared with and then replaced
+    let before = options
+        .compare
+        .as_deref()
+        .map(|path| {
+            read_chunk_list(path, d)
+                .map_err(|err| format!("Cannot read the snapshot {:?}: {err}", path))
+        })
+        .transpose()?;
+    let expected_removals = options
+        .expect_removed
+        .as_deref()
+        .map(|path| {
+            read_chunk_list(path, d)
+                .map_err(|err| format!("Cannot read the chunk list {:?}: {err}", path))
+        })
+        .transpose()?;
+
+    let spinner = new_spinner("Scanning worlds for region files...");
+    let entries = get_region_files(world_paths, scan_options);
+    spinner.finish_and_clear();
+    let entries = entries?;
+    info!("Found {} region file(s)", entries.len());
+    warn_if_no_region_files(world_paths, entries.len());
+    let pb = new_progress_bar(entries.len() as u64);
+
+    let now: Snapshot = entries
+        .par_iter()
+        .filter_map(|entry| {
+            trace_region(entry);
+            let chunks = region_chunks(entry);
+            pb.inc(1);
+            chunks.map(|chunks| {
+                (
+                    forward_slashes(&entry.display().to_string(), MAIN_SEPARATOR),
+                    chunks,
+                )
+            })
+        })
+        .collect();
+    pb.finish_and_clear();
+
+    if let Some(before) = &before {
+        let diff = compare_snapshots(before, &now);
+        if options.list {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "change{d}region{d}x{d}z")?;
+            let changes = diff.appeared.iter().map(|chunk| ("added", chunk));
+            let changes = changes.chain(diff.disappeared.iter().map(|chunk| ("removed", chunk)));
+            for (change, (region, x, z)) in changes {
+                writeln!(stdout, "{change}{d}{}{d}{x}{d}{z}", csv_field(region, d))?;
+            }
+        }
+        println!("{diff}");
+
+        if let Some(expected) = &expected_removals {
+            let disappeared: BTreeSet<ListedChunk> = diff.disappeared.into_iter().collect();
+            let expected: BTreeSet<ListedChunk> = expected.iter().flat_map(listed_chunks).collect();
+            let unexpected = disappeared.difference(&expected).count();
+            let still_there = expected.difference(&disappeared).count();
+            if unexpected > 0 || still_there > 0 {
+                save_if_asked(options, &now)?;
+                return Err(format!(
+                    "The removed chunks differ from the expected ones: {unexpected} chunk(s) disappeared without \
+                     being expected, {still_there} expected chunk(s) did not disappear"
+                )
+                .into());
+            }
+            info!(
+                "Exactly the {} expected chunk(s) disappeared",
+                expected.len()
+            );
+        }
+    }
+
+    save_if_asked(options, &now)
+}
+
+fn save_if_asked(options: &SnapshotOptions, snapshot: &Snapshot) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = &options.save {
+        write_snapshot(path, snapshot, options.csv_delimiter)
+            .map_err(|err| format!("Cannot write the snapshot {:?}: {err}", path))?;
+        info!(
+            "Saved the chunks of {} region file(s) to {:?}",
+            snapshot.len(),
+            path
+        );
+    }
+    Ok(())
+}
+
+/// Absolute coordinates of the chunks of a region file, from its header. `None` when it cannot be read or its name
+/// does not tell where it is. A region whose header is invalid has no chunks, the game would not read any either
+fn region_chunks(region_file_path: &Path) -> Option<BTreeSet<(i32, i32)>> {
+    let Some((region_x, region_z)) = region_coordinates(region_file_path) else {
+        warn!(
+            "Cannot tell the coordinates of {:?} from its name, leaving it out of the snapshot",
+            region_file_path
+        );
+        return None;
+    };
+    let slots = match occupied_slots(region_file_path) {
+        Ok(slots) => slots,
+        Err(ParseRegionError::HeaderError) => Default::default(),
+        Err(ParseRegionError::ReadError) => {
+            warn!(
+                "Cannot read {:?}, leaving it out of the snapshot",
+                region_file_path
+            );
+            return None;
+        }
+    };
+    Some(
+        slots
+            .into_iter()
+            .map(|slot| {
+                (
+                    region_x * 32 + (slot % 32) as i32,
+                    region_z * 32 + (slot / 32) as i32,
+                )
+            })
+            .collect(),
+    )
+}
+
+fn listed_chunks<'a>(
+    (region, chunks): (&'a String, &'a BTreeSet<(i32, i32)>),
+) -> impl Iterator<Item = ListedChunk> + 'a {
+    chunks.iter().map(move |&(x, z)| (region.clone(), x, z))
+}
+
+fn compare_snapshots(before: &Snapshot, now: &Snapshot) -> SnapshotDiff {
+    let count = |snapshot: &Snapshot| snapshot.values().map(BTreeSet::len).sum();
+    let missing_from = |snapshot: &Snapshot, other: &Snapshot| -> Vec<ListedChunk> {
+        snapshot
+            .iter()
+            .flat_map(listed_chunks)
+            .filter(|(region, x, z)| {
+                !other
+                    .get(region)
+                    .is_some_and(|chunks| chunks.contains(&(*x, *z)))
+            })
+            .collect()
+    };
+    SnapshotDiff {
+        chunks_before: count(before),
+        chunks_now: count(now),
+        appeared: missing_from(now, before),
+        disappeared: missing_from(before, now),
+    }
+}
+
+fn write_snapshot(path: &Path, snapshot: &Snapshot, d: char) -> std::io::Result<()> {
+    let mut file = BufWriter::new(std::fs::File::create(path)?);
+    writeln!(file, "region{d}x{d}z")?;
+    for (region, x, z) in snapshot.iter().flat_map(listed_chunks) {
+        writeln!(file, "{}{d}{x}{d}{z}", csv_field(&region, d))?;
+    }
+    file.flush()
+}
+
+/// Reads a `region,x,z` CSV file, a snapshot or the output of `check --list`
+fn read_chunk_list(path: &Path, d: char) -> Result<Snapshot, Box<dyn Error>> {
+    parse_chunk_list(&std::fs::read_to_string(path)?, d)
+}
+
+fn parse_chunk_list(content: &str, d: char) -> Result<Snapshot, Box<dyn Error>> {
+    let mut lines = content.lines().enumerate();
+    match lines.next() {
+        Some((_, header)) if header == format!("region{d}x{d}z") => {}
+        _ => return Err(format!("Expected a region{d}x{d}z header").into()),
+    }
+
+    let mut snapshot = Snapshot::new();
+    // `check --list > list.csv` also saves the summary printed after the list
+    let lines = lines.take_while(|(_, line)| *line != "Optimization Result:");
+    for (index, line) in lines.filter(|(_, line)| !line.is_empty()) {
+        let (region, x, z) =
+            parse_csv_line(line, d).ok_or_else(|| format!("Invalid line {}: {line}", index + 1))?;
+        snapshot.entry(region).or_default().insert((x, z));
+    }
+    Ok(snapshot)
+}
+
+/// Parses a `region,x,z` line, the region possibly quoted as written by [`csv_field`]
+fn parse_csv_line(line: &str, d: char) -> Option<ListedChunk> {
+    let (region, coordinates) = match line.strip_prefix('"') {
+        Some(quoted) => {
+            let mut region = String::new();
+            let mut chars = quoted.char_indices();
+            loop {
+                match chars.next()? {
+                    (index, '"') if quoted[index + 1..].starts_with('"') => {
+                        region.push('"');
+                        chars.next();
+                    }
+                    (index, '"') => break (region, quoted[index + 1..].strip_prefix(d)?),
+                    (_, c) => region.push(c),
+                }
+            }
+        }
+        None => {
+            let (region, coordinates) = line.split_once(d)?;
+            (region.to_string(), coordinates)
+        }
+    };
+    let (x, z) = coordinates.split_once(d)?;
+    Some((region, x.parse().ok()?, z.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_line() {
+        assert_eq!(
+            parse_csv_line("world/region/r.0.0.mca,3,-4", ','),
+            Some(("world/region/r.0.0.mca".into(), 3, -4))
+        );
+        assert_eq!(
+            parse_csv_line(r#""my ""best"", world/r.0.0.mca",1,2"#, ','),
+            Some((r#"my "best", world/r.0.0.mca"#.into(), 1, 2))
+        );
+        assert_eq!(parse_csv_line("a;b/r.0.0.mca;1;2", ','), None);
+        assert_eq!(
+            parse_csv_line("world/r.0.0.mca;1;2", ';'),
+            Some(("world/r.0.0.mca".into(), 1, 2))
+        );
+        assert_eq!(parse_csv_line(r#""unterminated,1,2"#, ','), None);
+    }
+
+    #[test]
+    fn test_snapshot_round_trip_and_comparison() {
+        let before = Snapshot::from([
+            (
+                "world/region/r.0.0.mca".to_string(),
+                BTreeSet::from([(0, 0), (1, 0), (2, 5)]),
+            ),
+            (
+                "My, World/region/r.-1.0.mca".to_string(),
+                BTreeSet::from([(-1, 3)]),
+            ),
+        ]);
+        let path = std::env::temp_dir().join(format!("mwt_snapshot_{}.csv", std::process::id()));
+        write_snapshot(&path, &before, ',').unwrap();
+        assert_eq!(read_chunk_list(&path, ',').unwrap(), before);
+        std::fs::remove_file(&path).ok();
+
+        let now = Snapshot::from([
+            (
+                "world/region/r.0.0.mca".to_string(),
+                BTreeSet::from([(0, 0), (2, 5), (7, 7)]),
+            ),
+            (
+                "world/region/r.1.0.mca".to_string(),
+                BTreeSet::from([(32, 0)]),
+            ),
+        ]);
+        let diff = compare_snapshots(&before, &now);
+        assert_eq!((diff.chunks_before, diff.chunks_now), (4, 4));
+        assert_eq!(
+            diff.appeared,
+            vec![
+                ("world/region/r.0.0.mca".into(), 7, 7),
+                ("world/region/r.1.0.mca".into(), 32, 0)
+            ]
+        );
+        assert_eq!(
+            diff.disappeared,
+            vec![
+                ("My, World/region/r.-1.0.mca".into(), -1, 3),
+                ("world/region/r.0.0.mca".into(), 1, 0)
+            ]
+        );
+        assert!(parse_chunk_list("x,z\n1,2\n", ',').is_err());
+        let check_output =
+            "region,x,z\nworld/region/r.0.0.mca,1,0\nOptimization Result:\nTotal Chunks: 3\n";
+        assert_eq!(
+            parse_chunk_list(check_output, ',').unwrap(),
+            Snapshot::from([(
+                "world/region/r.0.0.mca".to_string(),
+                BTreeSet::from([(1, 0)])
+            )])
+        );
+    }
+
+    #[test]
+    fn test_region_chunks() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let chunks = region_chunks(&path).unwrap();
+        assert_eq!(chunks.len(), 1024);
+        assert_eq!(chunks.first(), Some(&(-32, -32)));
+        assert_eq!(chunks.last(), Some(&(-1, -1)));
+        assert_eq!(region_chunks(Path::new("test_files/bigtest.nbt")), None);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::commands::progress::{new_progress_bar, new_spinner};
+use crate::info;
+use crate::logger::trace_region;
+use crate::region_loader::header_stats::HeaderStats;
+use crate::region_loader::region::ParseRegionError;
+use crate::world::get_region_files::{get_region_files, warn_if_no_region_files};
+use crate::world::scan_options::ScanOptions;
+use rayon::iter::ParallelIterator;
+use rayon::prelude::IntoParallelRefIterator;
+use std::error::Error;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+#[derive(Default, Clone)]
+struct StatsResult {
+    region_files: usize,
+    chunks: usize,
+    used_sectors: usize,
+    file_sectors: usize,
+    invalid_regions: usize,
+    io_errors: usize,
+}
+
+impl Display for StatsResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Statistics Result:\n\
+                   Region Files: {}\n\
+                   Chunks: {}\n\
+                   Used Sectors: {} ({:.2} MiB)\n\
+                   File Sectors: {} ({:.2} MiB)\n\
+                   Invalid Regions: {}\n\
+                   I/O Errors: {}",
+            self.region_files,
+            self.chunks,
+            self.used_sectors,
+            sectors_to_mib(self.used_sectors),
+            self.file_sectors,
+            sectors_to_mib(self.file_sectors),
+            self.invalid_regions,
+            self.io_errors
+        )
+    }
+}
+
+fn sectors_to_mib(sectors: usize) -> f64 {
+    (sectors * 4096) as f64 / 1_048_576.0
+}
+
+/// Counts chunks and sectors from the region headers only, which is much faster than a check
+pub fn execute_stats(
+    world_paths: &[PathBuf],
+    scan_options: &ScanOptions,
+) -> Result<(), Box<dyn Error>> {
+    let spinner = new_spinner("Scanning worlds for region files...");
+    let entries = get_region_files(world_paths, scan_options);
+    spinner.finish_and_clear();
+    let entries = entries?;
+    info!("Found {} region file(s)", entries.len());
+    warn_if_no_region_files(world_paths, entries.len());
+    let pb = new_progress_bar(entries.len() as u64);
+
+    let result = entries
+        .par_iter()
+        .map(|entry| {
+            trace_region(entry);
+            let result = region_stats(entry);
+            pb.inc(1);
+            result
+        })
+        .reduce(StatsResult::default, |mut acc, cur| {
+            acc.region_files += cur.region_files;
+            acc.chunks += cur.chunks;
+            acc.used_sectors += cur.used_sectors;
+            acc.file_sectors += cur.file_sectors;
+            acc.invalid_regions += cur.invalid_regions;
+            acc.io_errors += cur.io_errors;
+            acc
+        });
+
+    println!("{result}");
+
+    Ok(())
+}
+
+fn region_stats(region_file_path: &Path) -> StatsResult {
+    let mut result = StatsResult {
+        region_files: 1,
+        ..StatsResult::default()
+    };
+
+    match HeaderStats::from_file_name(region_file_path) {
+        Ok(stats) => {
+            result.chunks = stats.chunk_count;
+            result.used_sectors = stats.used_sectors;
+            result.file_sectors = stats.file_sectors;
+        }
+        Err(ParseRegionError::HeaderError) => result.invalid_regions += 1,
+        Err(ParseRegionError::ReadError) => result.io_errors += 1,
+    }
+
+    result
+}
@@ -0,0 +1,165 @@
+use crate::commands::optimize_result::OptimizeResult;
+use crate::commands::read::{csv_field, forward_slashes};
+use crate::commands::trim_options::TrimOptions;
+use crate::warn;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Prints the summary of a check or write run, as text or as a CSV header and row with `summary_csv_delimiter`
+pub fn print_summary(world_paths: &[PathBuf], result: &OptimizeResult, options: &TrimOptions) {
+    let summary = match options.summary_csv_delimiter {
+        Some(delimiter) => summary_csv(unix_now(), world_paths, result, delimiter),
+        None => result.to_string(),
+    };
+    if options.decisions_as_jsonl {
+        // Keep stdout made of JSON lines only
+        eprintln!("{summary}");
+    } else {
+        println!("{summary}");
+    }
+}
+
+/// A CSV header and a single row of the main counts of the run, to paste into a spreadsheet tracking runs.
+/// The worlds are one field, separated by `;`
+fn summary_csv(
+    unix_seconds: u64,
+    world_paths: &[PathBuf],
+    result: &OptimizeResult,
+    delimiter: char,
+) -> String {
+    let worlds: Vec<String> = world_paths
+        .iter()
+        .map(|path| forward_slashes(&path.display().to_string(), MAIN_SEPARATOR))
+        .collect();
+    let d = delimiter;
+    format!(
+        "timestamp{d}worlds{d}total_chunks{d}deleted_chunks{d}deleted_regions{d}bytes_saved\n\
+         {}{d}{}{d}{}{d}{}{d}{}{d}{}",
+        format_utc(unix_seconds),
+        csv_field(&worlds.join(";"), d),
+        result.total_chunks,
+        result.deleted_chunks,
+        result.deleted_regions,
+        result.saved_bytes
+    )
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Appends a one line summary of the run to `summary_file`, building a history that can be charted over time.
+///
+/// The line is a UTC timestamp followed by space separated `key=value` fields, new fields are only ever appended:
+/// `2024-05-01T03:00:00Z mode=write total_chunks=1024 deleted_chunks=12 ... saved_bytes=49152`
+pub fn append_summary(summary_file: &Path, mode: &str, result: &OptimizeResult) {
+    let line = summary_line(unix_now(), mode, result);
+
+    let appended = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(summary_file)
+        .and_then(|mut file| writeln!(file, "{line}"));
+    if let Err(err) = appended {
+        warn!("Cannot append the summary to {:?}: {err}", summary_file);
+    }
+}
+
+fn summary_line(unix_seconds: u64, mode: &str, result: &OptimizeResult) -> String {
+    format!(
+        "{} mode={mode} total_chunks={} deleted_chunks={} deleted_regions={} emptied_regions={} \
+         skipped_regions={} malformed_chunks={} io_errors={} orphaned_sectors={} saved_bytes={}",
+        format_utc(unix_seconds),
+        result.total_chunks,
+        result.deleted_chunks,
+        result.deleted_regions,
+        result.emptied_regions,
+        result.skipped_regions,
+        result.malformed_chunks,
+        result.io_errors,
+        result.orphaned_sectors,
+        result.saved_bytes
+    )
+}
+
+/// Formats seconds since the Unix epoch as an RFC 3339 UTC timestamp
+fn format_utc(unix_seconds: u64) -> String {
+    let days = (unix_seconds / 86_400) as i64;
+    let seconds_of_day = unix_seconds % 86_400;
+
+    // Converts days since 1970-01-01 to a proleptic Gregorian date, counting eras of 400 years from 0000-03-01
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_utc(1_714_532_461), "2024-05-01T03:01:01Z");
+    }
+
+    #[test]
+    fn test_summary_line() {
+        let result = OptimizeResult {
+            total_chunks: 1024,
+            deleted_chunks: 12,
+            saved_bytes: 49152,
+            ..OptimizeResult::default()
+        };
+        assert_eq!(
+            summary_line(0, "write", &result),
+            "1970-01-01T00:00:00Z mode=write total_chunks=1024 deleted_chunks=12 deleted_regions=0 \
+             emptied_regions=0 skipped_regions=0 malformed_chunks=0 io_errors=0 orphaned_sectors=0 saved_bytes=49152"
+        );
+    }
+
+    #[test]
+    fn test_summary_csv() {
+        let result = OptimizeResult {
+            total_chunks: 1024,
+            deleted_chunks: 12,
+            deleted_regions: 1,
+            saved_bytes: 49152,
+            ..OptimizeResult::default()
+        };
+        let worlds = [
+            PathBuf::from("saves/My World"),
+            PathBuf::from("saves/Other"),
+        ];
+        assert_eq!(
+            summary_csv(1_714_532_461, &worlds, &result, ','),
+            "timestamp,worlds,total_chunks,deleted_chunks,deleted_regions,bytes_saved\n\
+             2024-05-01T03:01:01Z,saves/My World;saves/Other,1024,12,1,49152"
+        );
+        assert!(summary_csv(0, &worlds, &result, ';').contains(";\"saves/My World;saves/Other\";"));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::commands::progress::file_len;
+use crate::logger::trace_region;
+use crate::region_loader::header_stats::HeaderStats;
+use crate::world::archive::is_archive;
+use crate::world::get_region_files::get_region_files;
+use crate::world::scan_options::ScanOptions;
+use rayon::iter::ParallelIterator;
+use rayon::prelude::IntoParallelRefIterator;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+struct RegionSize {
+    path: PathBuf,
+    bytes: u64,
+    /// `None` when the header cannot be read
+    chunks: Option<usize>,
+}
+
+/// Prints the `count` region files taking the most disk space, with their chunk counts.
+/// Only region headers are read, and region files inside world archives are not listed
+pub fn report_top_regions(
+    world_paths: &[PathBuf],
+    scan_options: &ScanOptions,
+    count: usize,
+) -> Result<(), Box<dyn Error>> {
+    let world_paths: Vec<PathBuf> = world_paths
+        .iter()
+        .filter(|path| !(path.is_file() && is_archive(path)))
+        .cloned()
+        .collect();
+    let entries = get_region_files(&world_paths, scan_options)?;
+    let regions = entries.par_iter().map(|entry| region_size(entry)).collect();
+
+    println!("Largest Region Files:");
+    for region in largest_regions(regions, count) {
+        let chunks = region.chunks.map_or_else(
+            || "unreadable header".to_string(),
+            |chunks| format!("{chunks} chunks"),
+        );
+        println!(
+            "{:>10.2} MiB  {:>17}  {}",
+            region.bytes as f64 / 1_048_576.0,
+            chunks,
+            region.path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn region_size(region_file_path: &Path) -> RegionSize {
+    trace_region(region_file_path);
+    RegionSize {
+        path: region_file_path.to_path_buf(),
+        bytes: file_len(region_file_path),
+        chunks: HeaderStats::from_file_name(region_file_path)
+            .ok()
+            .map(|stats| stats.chunk_count),
+    }
+}
+
+/// Largest first, regions of the same size in path order
+fn largest_regions(mut regions: Vec<RegionSize>, count: usize) -> Vec<RegionSize> {
+    regions.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+    regions.truncate(count);
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_largest_regions() {
+        let region = |name: &str, bytes| RegionSize {
+            path: PathBuf::from(name),
+            bytes,
+            chunks: None,
+        };
+        let regions = vec![
+            region("r.0.0.mca", 8192),
+            region("r.3.-5.mca", 60_000_000),
+            region("r.1.0.mca", 12_288),
+            region("r.0.1.mca", 12_288),
+        ];
+
+        let largest: Vec<PathBuf> = largest_regions(regions, 3)
+            .into_iter()
+            .map(|region| region.path)
+            .collect();
+        assert_eq!(
+            largest,
+            vec![
+                PathBuf::from("r.3.-5.mca"),
+                PathBuf::from("r.0.1.mca"),
+                PathBuf::from("r.1.0.mca")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_region_size() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let size = region_size(&path);
+        assert_eq!(size.bytes, std::fs::metadata(&path).unwrap().len());
+        assert_eq!(size.chunks, Some(1024));
+    }
+}
@@ -0,0 +1,400 @@
+use crate::region_loader::chunk_loader::chunk::Chunk;
+use crate::region_loader::chunk_loader::chunk_status::ChunkStatus;
+use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+use crate::region_loader::compression_level::CompressionLevel;
+use crate::warn;
+use crate::world::dimension::Dimension;
+use crate::world::forced_chunks::{forced_chunks_file, read_forced_chunks};
+use crate::world::level_dat::{WorldBorder, find_level_dat, read_game_time, read_world_border};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Settings shared by the check and write modes.
+/// Check mode uses them to predict what write would do with the same settings.
+#[derive(Default, Clone)]
+pub struct TrimOptions {
+    /// Compression level of re-serialized chunks, only used when writing
+    pub compression: CompressionLevel,
+    /// Schemes assumed for nonstandard scheme bytes when reading chunks
+    pub schemes: SchemeMap,
+    /// Replace regions left without chunks by a header-only region file instead of deleting them
+    pub keep_empty_regions: bool,
+    /// Also delete never inhabited nether chunks containing a nether portal, even when fully generated
+    pub aggressive_nether: bool,
+    /// Delete chunks containing any of these biomes, even when fully generated or inhabited
+    pub delete_biomes: Vec<String>,
+    /// Delete chunks last saved more than this many game ticks before the current time of their world
+    pub stale_update: Option<i64>,
+    /// Delete chunks with fewer sections holding blocks than this, even when fully generated or inhabited
+    pub min_sections: Option<usize>,
+    /// Delete chunks with more sections holding blocks than this, even when fully generated or inhabited
+    pub max_sections: Option<usize>,
+    /// Delete chunks lying entirely outside of the world border stored in `level.dat`, even when fully generated or
+    /// inhabited
+    pub respect_border: bool,
+    /// Keep chunks holding a structure start or covered by a structure, even when not fully generated or inhabited.
+    /// Only the explicit deletion filters (malformed, stale, biomes, sections, border) still apply to them
+    pub keep_structures: bool,
+    /// Keep chunks holding at least one block entity (chest, furnace, sign...), even when not fully generated or
+    /// inhabited. Only the explicit deletion filters still apply to them
+    pub keep_block_entities: bool,
+    /// Also keep chunks whose generation reached at least this stage, not only fully generated ones
+    pub min_status: Option<ChunkStatus>,
+    /// Only keep fully generated chunks, however long players stayed in the others
+    pub ignore_inhabited: bool,
+    /// Remove the sections lying entirely below this Y from the kept chunks, keeping the chunks themselves
+    pub delete_below_y: Option<i32>,
+    /// Delete chunks that parse but have no position
+    pub delete_malformed: bool,
+    /// Minimum size reduction, in percent, for write to rewrite a modified region. 0 rewrites every modified region
+    pub min_savings: f64,
+    /// After writing a region, delete the POI chunks of the chunks it lost and the POI records lying outside of
+    /// their chunk, in the matching `poi/` region file
+    pub trim_poi: bool,
+    /// After writing a region, remove the entities with these namespaced ids from the matching `entities/` region file
+    pub trim_entity_types: Vec<String>,
+    /// Write the chunks of rewritten regions as they are compressed instead of once the whole region is, only used
+    /// when writing regions that are not gzipped
+    pub stream_writes: bool,
+    /// Parse written region files again before they replace the regions, only used when writing
+    pub verify_after_write: bool,
+    /// Give rewritten region files the modification time they had before, only used when writing
+    pub preserve_mtime: bool,
+    /// Memory budget in MiB limiting how many regions are processed at once, `None` processes as many as there are threads
+    pub max_memory: Option<u64>,
+    /// Print the decision taken for every chunk as JSON lines on stdout, the summary then goes to stderr
+    pub decisions_as_jsonl: bool,
+    /// Print the decision taken for every chunk in check mode with the rule that took it, see [`Verdict`]
+    pub explain: bool,
+    /// Print the summary as a CSV header and row with this delimiter instead of text
+    pub summary_csv_delimiter: Option<char>,
+    /// Append a one line summary of the run to this file
+    pub summary_file: Option<PathBuf>,
+    /// Directory of the cache of the regions write found nothing to change in, skipped by the next runs until they
+    /// change
+    pub cache: Option<PathBuf>,
+}
+
+/// Whether a chunk is kept or deleted, with the rule that decided it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Keep(&'static str),
+    Delete(&'static str),
+}
+
+impl Verdict {
+    pub fn keep_reason(self) -> Option<&'static str> {
+        match self {
+            Verdict::Keep(reason) => Some(reason),
+            Verdict::Delete(_) => None,
+        }
+    }
+}
+
+/// What the options need to know about the region a chunk comes from
+pub struct RegionContext {
+    pub dimension: Dimension,
+    /// Current game time of the world of the region, only read when an option needs it
+    pub game_time: Option<i64>,
+    /// World border of the world of the region, only read with `respect_border`
+    pub border: Option<WorldBorder>,
+    /// Chunks force-loaded in the dimension of the region (`/forceload`), always kept
+    pub forced_chunks: HashSet<(i32, i32)>,
+}
+
+impl TrimOptions {
+    pub fn region_context(&self, region_file_path: &Path) -> RegionContext {
+        let game_time = match self.stale_update {
+            Some(_) => {
+                let game_time = find_level_dat(region_file_path)
+                    .and_then(|level_dat| read_game_time(&level_dat));
+                if game_time.is_none() {
+                    warn!(
+                        "Cannot read the game time of the world of {:?}, ignoring --stale-update",
+                        region_file_path
+                    );
+                }
+                game_time
+            }
+            None => None,
+        };
+        let border = if self.respect_border {
+            let border = find_level_dat(region_file_path)
+                .and_then(|level_dat| read_world_border(&level_dat));
+            if border.is_none() {
+                warn!(
+                    "Cannot read the world border of the world of {:?}, ignoring --respect-border",
+                    region_file_path
+                );
+            }
+            border
+        } else {
+            None
+        };
+
+        let forced_chunks = read_forced_chunks(region_file_path).unwrap_or_else(|| {
+            if forced_chunks_file(region_file_path).is_some_and(|chunks_dat| chunks_dat.is_file()) {
+                warn!(
+                    "Cannot read the force-loaded chunks of the dimension of {:?}",
+                    region_file_path
+                );
+            }
+            HashSet::new()
+        });
+        RegionContext {
+            dimension: Dimension::from_region_path(region_file_path),
+            game_time,
+            border,
+            forced_chunks,
+        }
+    }
+
+    /// Tells why the chunk is kept with these settings, or `None` if it can be deleted
+    pub fn keep_reason(&self, chunk: &Chunk, context: &RegionContext) -> Option<&'static str> {
+        self.verdict(chunk, context).keep_reason()
+    }
+
+    /// Decides whether the chunk is kept or deleted with these settings, along with the rule that decided it
+    pub fn verdict(&self, chunk: &Chunk, context: &RegionContext) -> Verdict {
+        if self.delete_malformed && chunk.get_position().is_err() {
+            return Verdict::Delete("no position (--delete-malformed)");
+        }
+
+        // Admins force-load chunks on purpose, farms and machines stop working without them
+        if chunk
+            .get_position()
+            .is_ok_and(|position| context.forced_chunks.contains(&position))
+        {
+            return Verdict::Keep("force-loaded");
+        }
+
+        if let (Some(max_age), Some(game_time), Some(last_update)) =
+            (self.stale_update, context.game_time, chunk.last_update())
+        {
+            if game_time - last_update > max_age {
+                return Verdict::Delete("not saved for too long (--stale-update)");
+            }
+        }
+
+        if self
+            .delete_biomes
+            .iter()
+            .any(|biome| chunk.count_biome(biome) > 0)
+        {
+            return Verdict::Delete("holds a deleted biome (--biome)");
+        }
+
+        if self.min_sections.is_some() || self.max_sections.is_some() {
+            let sections = chunk.section_count();
+            if self.min_sections.is_some_and(|min| sections < min) {
+                return Verdict::Delete("too few sections (--min-sections)");
+            }
+            if self.max_sections.is_some_and(|max| sections > max) {
+                return Verdict::Delete("too many sections (--max-sections)");
+            }
+        }
+
+        // Nobody can reach chunks beyond the border without leaving it first
+        if let (Some(border), Ok(position)) = (context.border, chunk.get_position()) {
+            if border.excludes_chunk(position, context.dimension) {
+                return Verdict::Delete("outside of the world border (--respect-border)");
+            }
+        }
+
+        if self.keep_structures && chunk.has_structures() {
+            return Verdict::Keep("part of a structure");
+        }
+
+        if self.keep_block_entities && chunk.has_block_entities() {
+            return Verdict::Keep("holds block entities");
+        }
+
+        // Portal travel generates full chunks around the exit portal that nobody ever stays in
+        if self.aggressive_nether
+            && context.dimension == Dimension::Nether
+            && (self.ignore_inhabited || !chunk.has_been_inhabited())
+            && chunk.count_block("minecraft:nether_portal") > 0
+        {
+            return Verdict::Delete("nether portal chunk nobody stayed in (--aggressive-nether)");
+        }
+
+        if self
+            .min_status
+            .is_some_and(|min_status| chunk.status().is_some_and(|status| status >= min_status))
+        {
+            return Verdict::Keep("past the minimum status");
+        }
+
+        if self.ignore_inhabited {
+            return if chunk.is_fully_generated() {
+                Verdict::Keep("fully generated")
+            } else {
+                Verdict::Delete("not fully generated (--ignore-inhabited)")
+            };
+        }
+        match chunk.keep_reason() {
+            Some(reason) => Verdict::Keep(reason),
+            None => Verdict::Delete("not fully generated and never inhabited"),
+        }
+    }
+
+    /// The options deciding what write changes in a region, which a cache of clean regions is only valid for.
+    /// `None` with `stale_update`, since chunks become stale as the game time goes on, and with `respect_border`,
+    /// since the border can move without any region changing
+    pub fn cache_key(&self) -> Option<String> {
+        if self.stale_update.is_some() || self.respect_border {
+            return None;
+        }
+        Some(format!(
+            "version={} keep_empty_regions={} assumed_schemes={:?} aggressive_nether={} biomes={:?} \
+             min_sections={:?} max_sections={:?} keep_structures={} keep_block_entities={} min_status={:?} \
+             ignore_inhabited={} delete_below_y={:?} delete_malformed={} trim_poi={} trim_entity_types={:?}",
+            env!("CARGO_PKG_VERSION"),
+            self.keep_empty_regions,
+            self.schemes.assumed(),
+            self.aggressive_nether,
+            self.delete_biomes,
+            self.min_sections,
+            self.max_sections,
+            self.keep_structures,
+            self.keep_block_entities,
+            self.min_status,
+            self.ignore_inhabited,
+            self.delete_below_y,
+            self.delete_malformed,
+            self.trim_poi,
+            self.trim_entity_types
+        ))
+    }
+
+    /// Tells if replacing a region file of `original_len` bytes by `new_len` bytes saves at least `min_savings`
+    pub fn is_worth_rewriting(&self, original_len: u64, new_len: u64) -> bool {
+        if self.min_savings <= 0.0 || original_len == 0 {
+            return true;
+        }
+        let savings = (original_len as f64 - new_len as f64) / original_len as f64 * 100.0;
+        savings >= self.min_savings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_loader::chunk_loader::compression_scheme::CompressionScheme;
+    use crate::region_loader::test_region::TestChunk;
+
+    #[test]
+    fn test_cache_key() {
+        let key = TrimOptions::default().cache_key();
+        let keep_empty_regions = TrimOptions {
+            keep_empty_regions: true,
+            ..TrimOptions::default()
+        };
+        assert!(key.is_some() && keep_empty_regions.cache_key() != key);
+        let mut assumed_schemes = TrimOptions::default();
+        assumed_schemes.schemes.assume(100, CompressionScheme::Zlib);
+        assert_ne!(assumed_schemes.cache_key(), key);
+        let respect_border = TrimOptions {
+            respect_border: true,
+            ..TrimOptions::default()
+        };
+        assert_eq!(respect_border.cache_key(), None);
+    }
+
+    #[test]
+    fn test_is_worth_rewriting() {
+        let always = TrimOptions::default();
+        assert!(always.is_worth_rewriting(1000, 1000));
+        assert!(always.is_worth_rewriting(1000, 2000));
+
+        let options = TrimOptions {
+            min_savings: 10.0,
+            ..TrimOptions::default()
+        };
+        assert!(options.is_worth_rewriting(1000, 900));
+        assert!(!options.is_worth_rewriting(1000, 901));
+        assert!(!options.is_worth_rewriting(1000, 1200));
+    }
+
+    #[test]
+    fn test_keep_block_entities() {
+        let context = RegionContext {
+            dimension: Dimension::Overworld,
+            game_time: None,
+            border: None,
+            forced_chunks: HashSet::new(),
+        };
+        let built = TestChunk::full(0, 0)
+            .status("minecraft:features")
+            .block_entity("minecraft:chest")
+            .to_chunk();
+        let untouched = TestChunk::full(1, 0)
+            .status("minecraft:features")
+            .to_chunk();
+        assert!(built.has_block_entities());
+        assert!(!untouched.has_block_entities());
+
+        let options = TrimOptions {
+            keep_block_entities: true,
+            ..TrimOptions::default()
+        };
+        assert_eq!(
+            options.keep_reason(&built, &context),
+            Some("holds block entities")
+        );
+        assert_eq!(options.keep_reason(&untouched, &context), None);
+        assert_eq!(TrimOptions::default().keep_reason(&built, &context), None);
+    }
+
+    #[test]
+    fn test_verdict_names_the_deciding_rule() {
+        let context = RegionContext {
+            dimension: Dimension::Overworld,
+            game_time: None,
+            border: None,
+            forced_chunks: HashSet::new(),
+        };
+        let proto = TestChunk::full(0, 0)
+            .status("minecraft:features")
+            .to_chunk();
+        let inhabited = TestChunk::full(0, 0)
+            .status("minecraft:features")
+            .inhabited_time(340)
+            .to_chunk();
+        let default = TrimOptions::default();
+        assert_eq!(
+            default.verdict(&proto, &context),
+            Verdict::Delete("not fully generated and never inhabited")
+        );
+        assert_eq!(
+            default.verdict(&inhabited, &context),
+            Verdict::Keep("inhabited")
+        );
+        let forced = RegionContext {
+            forced_chunks: HashSet::from([(0, 0)]),
+            ..context
+        };
+        assert_eq!(
+            default.verdict(&proto, &forced),
+            Verdict::Keep("force-loaded")
+        );
+
+        let options = TrimOptions {
+            max_sections: Some(0),
+            min_sections: Some(1),
+            ..TrimOptions::default()
+        };
+        assert_eq!(
+            options.verdict(&inhabited, &context),
+            Verdict::Delete("too few sections (--min-sections)")
+        );
+        let options = TrimOptions {
+            ignore_inhabited: true,
+            ..TrimOptions::default()
+        };
+        assert_eq!(
+            options.verdict(&inhabited, &context),
+            Verdict::Delete("not fully generated (--ignore-inhabited)")
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use crate::commands::chunk_log::warn_parse_failures;
+use crate::commands::entities::entities_file_for;
+use crate::commands::progress::{new_progress_bar, new_spinner};
+use crate::logger::trace_region;
+use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+use crate::region_loader::region::{ParseRegionError, Region, RegionIssue};
+use crate::world::get_region_files::{get_mca_files, get_region_files, warn_if_no_region_files};
+use crate::world::scan_options::ScanOptions;
+use crate::{info, warn};
+use rayon::iter::ParallelIterator;
+use rayon::prelude::IntoParallelRefIterator;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::error::Error;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+#[derive(Default, Clone)]
+struct VerifyResult {
+    checked_regions: usize,
+    terrain_chunks_without_entities: usize,
+    entity_chunks_without_terrain: usize,
+    /// Chunks of the location tables that could not be parsed, in either file
+    unparsable_chunks: usize,
+    /// Locations out of the file, chunks sharing sectors and chunks stored in the wrong slot, in either file
+    location_issues: usize,
+    io_errors: usize,
+}
+
+impl Display for VerifyResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Verification Result:\n\
+                   Checked Regions: {}\n\
+                   Terrain Chunks Without Entity Data: {}\n\
+                   Entity Chunks Without Terrain: {}\n\
+                   Unparsable Chunks: {}\n\
+                   Location Table Issues: {}\n\
+                   I/O Errors: {}",
+            self.checked_regions,
+            self.terrain_chunks_without_entities,
+            self.entity_chunks_without_terrain,
+            self.unparsable_chunks,
+            self.location_issues,
+            self.io_errors
+        )
+    }
+}
+
+/// A region and the entities file covering the same coordinates, either of which may be missing
+struct RegionPair {
+    region: Option<PathBuf>,
+    entities: Option<PathBuf>,
+}
+
+/// Runs integrity checks over the worlds without modifying them
+pub fn execute_verify(
+    world_paths: &[PathBuf],
+    scan_options: &ScanOptions,
+    schemes: &SchemeMap,
+) -> Result<(), Box<dyn Error>> {
+    let spinner = new_spinner("Scanning worlds for region files...");
+    let pairs = get_region_files(world_paths, scan_options)
+        .map(|entries| pair_with_entities(&entries, scan_options));
+    spinner.finish_and_clear();
+    let pairs = pairs?;
+    info!("Found {} region file(s) to verify", pairs.len());
+    warn_if_no_region_files(world_paths, pairs.len());
+    let pb = new_progress_bar(pairs.len() as u64);
+
+    let result = pairs
+        .par_iter()
+        .map(|pair| {
+            if let Some(region_file) = pair.region.as_ref().or(pair.entities.as_ref()) {
+                trace_region(region_file);
+            }
+            let result = verify_pair(pair, schemes);
+            pb.inc(1);
+            result
+        })
+        .reduce(VerifyResult::default, |mut acc, cur| {
+            acc.checked_regions += cur.checked_regions;
+            acc.terrain_chunks_without_entities += cur.terrain_chunks_without_entities;
+            acc.entity_chunks_without_terrain += cur.entity_chunks_without_terrain;
+            acc.unparsable_chunks += cur.unparsable_chunks;
+            acc.location_issues += cur.location_issues;
+            acc.io_errors += cur.io_errors;
+            acc
+        });
+    pb.finish_and_clear();
+
+    println!("{result}");
+
+    Ok(())
+}
+
+fn pair_with_entities(region_files: &[PathBuf], scan_options: &ScanOptions) -> Vec<RegionPair> {
+    let mut pairs: BTreeMap<PathBuf, RegionPair> = BTreeMap::new();
+    let mut entities_dirs = BTreeSet::new();
+
+    for region_file in region_files {
+        let entities = entities_file_for(region_file);
+        if let Some(dir) = entities.as_ref().and_then(|path| path.parent()) {
+            entities_dirs.insert(dir.to_path_buf());
+        }
+        pairs.insert(
+            region_file.clone(),
+            RegionPair {
+                region: Some(region_file.clone()),
+                entities: entities.filter(|path| path.is_file()),
+            },
+        );
+    }
+
+    // Entity files whose terrain region is gone entirely
+    let paired: HashSet<PathBuf> = pairs
+        .values()
+        .filter_map(|pair| pair.entities.clone())
+        .collect();
+    for dir in entities_dirs {
+        for entities_file in get_mca_files(dir) {
+            if !paired.contains(&entities_file)
+                && scan_options.accepts(&entities_file)
+                && scan_options.is_recent(&entities_file)
+            {
+                pairs.insert(
+                    entities_file.clone(),
+                    RegionPair {
+                        region: None,
+                        entities: Some(entities_file),
+                    },
+                );
+            }
+        }
+    }
+
+    pairs.into_values().collect()
+}
+
+/// Chunks of a region file and its integrity issues
+#[derive(Default)]
+struct RegionChunks {
+    /// Positions of the chunks, including those that failed to parse
+    positions: HashSet<(i32, i32)>,
+    unparsable_chunks: usize,
+    location_issues: usize,
+}
+
+fn read_region_chunks(
+    file: Option<&PathBuf>,
+    schemes: &SchemeMap,
+) -> Result<RegionChunks, ParseRegionError> {
+    match file {
+        None => Ok(RegionChunks::default()),
+        Some(file) => match Region::from_file_name(file, schemes) {
+            Ok(region) => {
+                let failures = region.get_parse_failures();
+                warn_parse_failures(file, failures);
+                let mut location_issues = 0;
+                for issue in region.validate() {
+                    // Parse failures were just reported
+                    if !matches!(
+                        issue,
+                        RegionIssue::UnsupportedCompression(_) | RegionIssue::UnparsableChunk(_)
+                    ) {
+                        warn!("{file:?}: {issue}");
+                        location_issues += 1;
+                    }
+                }
+                let positions = region
+                    .get_chunks()
+                    .iter()
+                    .filter_map(|chunk| chunk.get_position().ok())
+                    // The chunk is there, only unreadable, it does not count as missing from the other file
+                    .chain(failures.iter().filter_map(|failure| failure.position))
+                    .collect();
+                Ok(RegionChunks {
+                    positions,
+                    unparsable_chunks: failures.len(),
+                    location_issues,
+                })
+            }
+            // Unreadable headers hold no chunk, same as a missing file
+            Err(ParseRegionError::HeaderError) => Ok(RegionChunks::default()),
+            Err(err) => Err(err),
+        },
+    }
+}
+
+fn verify_pair(pair: &RegionPair, schemes: &SchemeMap) -> VerifyResult {
+    let mut result = VerifyResult {
+        checked_regions: 1,
+        ..VerifyResult::default()
+    };
+
+    let (terrain, entities) = match (
+        read_region_chunks(pair.region.as_ref(), schemes),
+        read_region_chunks(pair.entities.as_ref(), schemes),
+    ) {
+        (Ok(terrain), Ok(entities)) => {
+            result.unparsable_chunks = terrain.unparsable_chunks + entities.unparsable_chunks;
+            result.location_issues = terrain.location_issues + entities.location_issues;
+            (terrain.positions, entities.positions)
+        }
+        _ => {
+            warn!("Cannot read {:?} or {:?}", pair.region, pair.entities);
+            result.io_errors += 1;
+            return result;
+        }
+    };
+
+    result.terrain_chunks_without_entities = terrain.difference(&entities).count();
+    result.entity_chunks_without_terrain = entities.difference(&terrain).count();
+    if result.entity_chunks_without_terrain > 0 {
+        warn!(
+            "{} entity chunk(s) without terrain in {:?}",
+            result.entity_chunks_without_terrain,
+            pair.entities.as_deref().unwrap_or(Path::new("?"))
+        );
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_pair_against_itself_and_nothing() {
+        let region = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+
+        let schemes = SchemeMap::default();
+
+        let same = verify_pair(
+            &RegionPair {
+                region: Some(region.clone()),
+                entities: Some(region.clone()),
+            },
+            &schemes,
+        );
+        assert_eq!(same.terrain_chunks_without_entities, 0);
+        assert_eq!(same.entity_chunks_without_terrain, 0);
+        assert_eq!(same.location_issues, 0);
+
+        let alone = verify_pair(
+            &RegionPair {
+                region: None,
+                entities: Some(region),
+            },
+            &schemes,
+        );
+        assert_eq!(alone.entity_chunks_without_terrain, 1024);
+        assert_eq!(alone.io_errors, 0);
+    }
+}
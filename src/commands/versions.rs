@@ -0,0 +1,177 @@
+use crate::commands::progress::{RegionProgress, new_spinner};
+use crate::info;
+use crate::logger::trace_region;
+use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+use crate::region_loader::region::{ParseRegionError, Region};
+use crate::world::get_region_files::{get_region_files, warn_if_no_region_files};
+use crate::world::level_dat::find_level_dat;
+use crate::world::minecraft_version::version_name;
+use crate::world::scan_options::ScanOptions;
+use rayon::iter::ParallelIterator;
+use rayon::prelude::IntoParallelRefIterator;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// Chunks decoded per region file, spread over the region
+const SAMPLED_CHUNKS_PER_REGION: usize = 8;
+
+/// `DataVersion` counts of the sampled chunks of a world
+#[derive(Default)]
+struct WorldVersions {
+    /// Number of sampled chunks per `DataVersion`, `None` for chunks saved before 1.9
+    counts: BTreeMap<Option<i32>, usize>,
+    io_errors: usize,
+}
+
+impl WorldVersions {
+    fn merge(&mut self, other: WorldVersions) {
+        for (data_version, count) in other.counts {
+            *self.counts.entry(data_version).or_default() += count;
+        }
+        self.io_errors += other.io_errors;
+    }
+}
+
+impl Display for WorldVersions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = |data_version: Option<i32>| match data_version {
+            Some(data_version) => format!(
+                "{} (DataVersion {data_version})",
+                version_name(data_version)
+            ),
+            None => "before 1.9 (no DataVersion)".to_string(),
+        };
+        let sampled: usize = self.counts.values().sum();
+        let (Some((&oldest, _)), Some((&newest, _))) =
+            (self.counts.first_key_value(), self.counts.last_key_value())
+        else {
+            return write!(
+                f,
+                "  No chunk could be read ({} I/O errors)",
+                self.io_errors
+            );
+        };
+        let most_common = self
+            .counts
+            .iter()
+            .max_by_key(|&(data_version, count)| (*count, *data_version))
+            .map(|(&data_version, _)| data_version)
+            .unwrap_or(newest);
+
+        write!(
+            f,
+            "  Sampled Chunks: {sampled}\n  \
+               Most Common: {}\n  \
+               Oldest: {}\n  \
+               Newest: {}",
+            name(most_common),
+            name(oldest),
+            name(newest)
+        )?;
+        if self.counts.len() > 1 {
+            write!(
+                f,
+                "\n  Mixed versions, parts of the world were saved by older versions"
+            )?;
+        }
+        if self.io_errors > 0 {
+            write!(f, "\n  I/O Errors: {}", self.io_errors)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reports which Minecraft versions saved the chunks of each world, from a sample of chunks of every region,
+/// to confirm the version of a world and spot a partial upgrade
+pub fn execute_versions(
+    world_paths: &[PathBuf],
+    scan_options: &ScanOptions,
+    schemes: &SchemeMap,
+) -> Result<(), Box<dyn Error>> {
+    let spinner = new_spinner("Scanning worlds for region files...");
+    let entries = get_region_files(world_paths, scan_options);
+    spinner.finish_and_clear();
+    let entries = entries?;
+    info!("Found {} region file(s)", entries.len());
+    warn_if_no_region_files(world_paths, entries.len());
+    let progress = RegionProgress::new(&entries);
+
+    let region_versions: Vec<(PathBuf, WorldVersions)> = entries
+        .par_iter()
+        .map(|entry| {
+            let ticket = progress.start(entry);
+            trace_region(entry);
+            let versions = region_versions(entry, schemes);
+            drop(ticket);
+            (world_of(entry), versions)
+        })
+        .collect();
+    progress.finish_and_clear();
+
+    let mut worlds: BTreeMap<PathBuf, WorldVersions> = BTreeMap::new();
+    for (world, versions) in region_versions {
+        worlds.entry(world).or_default().merge(versions);
+    }
+
+    println!("World Versions:");
+    for (world, versions) in worlds {
+        println!("{}:\n{versions}", world.display());
+    }
+
+    Ok(())
+}
+
+/// World directory of a region file, or its directory when it is not part of a world
+fn world_of(region_file_path: &Path) -> PathBuf {
+    find_level_dat(region_file_path)
+        .as_deref()
+        .or(Some(region_file_path))
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+fn region_versions(region_file_path: &Path, schemes: &SchemeMap) -> WorldVersions {
+    let mut versions = WorldVersions::default();
+    match Region::sample_chunks(region_file_path, SAMPLED_CHUNKS_PER_REGION, schemes) {
+        Ok(chunks) => {
+            for chunk in chunks {
+                *versions.counts.entry(chunk.data_version()).or_default() += 1;
+            }
+        }
+        // Without a header there are no chunks to sample
+        Err(ParseRegionError::HeaderError) => {}
+        Err(ParseRegionError::ReadError) => versions.io_errors += 1,
+    }
+    versions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_versions() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let versions = region_versions(&path, &SchemeMap::default());
+        assert_eq!(
+            versions.counts.values().sum::<usize>(),
+            SAMPLED_CHUNKS_PER_REGION
+        );
+        assert_eq!(versions.io_errors, 0);
+    }
+
+    #[test]
+    fn test_mixed_versions() {
+        let versions = WorldVersions {
+            counts: BTreeMap::from([(Some(3337), 2), (Some(3465), 5)]),
+            io_errors: 0,
+        };
+        let report = versions.to_string();
+        assert!(report.contains("Most Common: 1.20.1 (DataVersion 3465)"));
+        assert!(report.contains("Oldest: 1.19.4 (DataVersion 3337)"));
+        assert!(report.contains("Mixed versions"));
+    }
+}
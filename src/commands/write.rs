@@ -1,13 +1,29 @@
+use crate::commands::chunk_log::{log_chunk_decision, print_chunk_decision_jsonl};
+use crate::commands::deletion_plan::{DeletionPlan, read_plan};
+use crate::commands::entities::trim_entities;
+use crate::commands::interrupt::{install_interrupt_handler, interrupted};
+use crate::commands::memory_budget::MemoryBudget;
 use crate::commands::optimize_result::{reduce_optimize_results, OptimizeResult};
-use crate::region_loader::region::{ParseRegionError, Region};
-use crate::world::get_region_files::get_region_files;
-use flate2::Compression;
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::commands::poi::trim_poi;
+use crate::commands::progress::{RegionProgress, new_spinner};
+use crate::commands::region_cache::RegionCache;
+use crate::commands::summary_file::{append_summary, print_summary};
+use crate::commands::trim_options::TrimOptions;
+use crate::logger::trace_region;
+use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+use crate::region_loader::compression_level::CompressionLevel;
+use crate::region_loader::region::{
+    HEADER_SIZE, ParseRegionError, Region, SerializedRegion, UnplacedChunk, gzip_file,
+};
+use crate::world::get_region_files::{get_region_files, warn_if_no_region_files};
+use crate::world::scan_options::ScanOptions;
+use crate::{info, warn};
 use rayon::iter::ParallelIterator;
 use rayon::prelude::IntoParallelRefIterator;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::error::Error;
 use std::fs::{File, Permissions};
-use std::io::{BufWriter, Write};
+use std::io::{BufRead, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 
@@ -15,90 +31,600 @@ use std::sync::atomic::{AtomicU64, Ordering};
 /// (was previously derived from parsing `Debug` of `ThreadId`, which is not stable API).
 static TEMPFILE_SEQ: AtomicU64 = AtomicU64::new(0);
 
+/// Trims the worlds in place. Unless `assume_yes` is set, the user has to confirm first.
+/// With a deletion `plan` saved by check, exactly the chunks of the plan are deleted from the region files of the
+/// worlds, without deciding again; the other region files are left untouched. Returns the summary of the run
 pub fn execute_write(
     world_paths: &[PathBuf],
-    compression: Compression,
-) -> Result<(), Box<dyn Error>> {
-    let entries = get_region_files(world_paths)?;
-    let pb = ProgressBar::new(entries.len() as u64);
-    let style = ProgressStyle::with_template(
-        "{percent}% {bar} {pos}/{len} [{elapsed_precise}>{eta_precise}, {per_sec}]",
-    )
-    .unwrap();
-    pb.set_style(style);
+    scan_options: &ScanOptions,
+    options: &TrimOptions,
+    assume_yes: bool,
+    plan: Option<&Path>,
+) -> Result<OptimizeResult, Box<dyn Error>> {
+    let plan = plan.map(read_plan).transpose()?;
+    write_worlds(world_paths, scan_options, options, assume_yes, plan)
+}
+
+/// [`execute_write`] with the deletion plan already read, or made in this run such as the chunks marked in browse
+/// mode
+pub(crate) fn write_worlds(
+    world_paths: &[PathBuf],
+    scan_options: &ScanOptions,
+    options: &TrimOptions,
+    assume_yes: bool,
+    plan: Option<DeletionPlan>,
+) -> Result<OptimizeResult, Box<dyn Error>> {
+    let spinner = new_spinner("Scanning worlds for region files...");
+    let entries = get_region_files(world_paths, scan_options);
+    spinner.finish_and_clear();
+    let mut entries = entries?;
+    info!("Found {} region file(s)", entries.len());
+    warn_if_no_region_files(world_paths, entries.len());
+
+    // Plans store canonical paths, the regions found here are matched whatever path led to them
+    let plan: Option<HashMap<PathBuf, BTreeSet<(i32, i32)>>> = plan.map(|plan| {
+        plan.into_iter()
+            .map(|(region, positions)| (canonical(&region), positions))
+            .collect()
+    });
+    if let Some(plan) = &plan {
+        // The same region can be reached twice, from a world given twice or a world and one of its region files
+        let mut replayed = HashSet::new();
+        entries.retain(|entry| {
+            let region = canonical(entry);
+            plan.contains_key(&region) && replayed.insert(region)
+        });
+        let outside = plan
+            .keys()
+            .filter(|region| !replayed.contains(*region))
+            .count();
+        if outside > 0 {
+            warn!(
+                "{outside} region file(s) of the plan are not part of the given worlds, leaving them alone"
+            );
+        }
+        info!("Replaying the plan on {} region file(s)", entries.len());
+    }
+
+    if !assume_yes
+        && !confirm_write(
+            world_paths,
+            entries.len(),
+            &mut std::io::stdin().lock(),
+            &mut std::io::stderr(),
+        )?
+    {
+        return Err(
+            "Write aborted, nothing was modified (use --yes to skip the confirmation)".into(),
+        );
+    }
+
+    let cache = match (&options.cache, options.cache_key()) {
+        (Some(_), None) if options.respect_border => {
+            warn!(
+                "--cache is ignored with --respect-border, the border can move without any region changing"
+            );
+            None
+        }
+        (Some(_), None) => {
+            warn!(
+                "--cache is ignored with --stale-update, the chunks of unchanged regions still get older"
+            );
+            None
+        }
+        (Some(directory), Some(key)) if plan.is_none() => Some(RegionCache::open(directory, key)),
+        _ => None,
+    };
+
+    let progress = RegionProgress::new(&entries);
+    let budget = MemoryBudget::new(options.max_memory);
+    install_interrupt_handler();
 
     let mut results = entries
         .par_iter()
-        .map(|entry| {
-            let result = optimize_write(entry, compression);
-            pb.inc(1);
-            result
+        .filter_map(|entry| {
+            // After Ctrl-C the files in progress are finished, rewrites are atomic but POI files follow their region
+            if interrupted() {
+                return None;
+            }
+            if let Some(result) = cache.as_ref().and_then(|cache| cache.cached_result(entry)) {
+                // Counted as done right away in the progress
+                drop(progress.start(entry));
+                return Some(result);
+            }
+            let permit = budget.acquire(entry);
+            let ticket = progress.start(entry);
+            trace_region(entry);
+            let result = match &plan {
+                Some(plan) => replay_plan(entry, &plan[&canonical(entry)], options),
+                None => optimize_write(entry, options, false),
+            };
+            if let Some(cache) = &cache {
+                cache.record(entry, &result);
+            }
+            drop(ticket);
+            drop(permit);
+            Some(result)
         })
         .collect::<Vec<OptimizeResult>>();
+    progress.finish_and_clear();
+    if let Some(cache) = &cache {
+        cache.save();
+    }
+    let skipped = entries.len() - results.len();
 
     let result = reduce_optimize_results(&mut results);
-    println!("{result}");
+    print_summary(world_paths, &result, options);
+    if let Some(summary_file) = &options.summary_file {
+        append_summary(summary_file, "write", &result);
+    }
 
-    Ok(())
+    if interrupted() {
+        return Err(format!(
+            "Write interrupted, {skipped} region file(s) were left untouched, the summary only covers the others"
+        )
+        .into());
+    }
+    Ok(result)
+}
+
+/// Asks the user to type `yes` before modifying the worlds, anything else (including EOF) declines
+pub(crate) fn confirm_write(
+    world_paths: &[PathBuf],
+    region_count: usize,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> std::io::Result<bool> {
+    writeln!(
+        output,
+        "About to modify {region_count} region file(s) in place in:"
+    )?;
+    for world_path in world_paths {
+        writeln!(output, "  {}", world_path.display())?;
+    }
+    write!(
+        output,
+        "Make sure you have a backup. Type \"yes\" to continue: "
+    )?;
+    output.flush()?;
+
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(answer.trim() == "yes")
+}
+
+/// Trims a region file. With `dry_run`, everything is computed but no file is modified
+pub(crate) fn optimize_write(
+    region_file_path: &Path,
+    options: &TrimOptions,
+    dry_run: bool,
+) -> OptimizeResult {
+    let mut result = optimize_region(region_file_path, options, dry_run);
+    // POI chunks are matched with the terrain chunks left on disk, so there is nothing to compare in a dry run
+    if options.trim_poi && !dry_run {
+        trim_poi(region_file_path, options, &mut result);
+    }
+    if !options.trim_entity_types.is_empty() && !dry_run {
+        trim_entities(region_file_path, options, &mut result);
+    }
+    result
 }
 
-fn optimize_write(region_file_path: &Path, compression: Compression) -> OptimizeResult {
+fn optimize_region(
+    region_file_path: &Path,
+    options: &TrimOptions,
+    dry_run: bool,
+) -> OptimizeResult {
     let mut result = OptimizeResult::default();
+    let context = options.region_context(region_file_path);
 
-    match Region::from_file_name(region_file_path) {
+    match Region::from_file_name(region_file_path, &options.schemes) {
         Ok(mut region) => {
             result.total_chunks += region.get_chunk_count();
+            result.add_orphaned_sectors(region.get_orphaned_sectors());
+            result.add_malformed_chunks(region_file_path, region.get_chunks());
+            result.add_unparsable_chunks(region_file_path, region.get_parse_failures());
 
             let chunks_to_delete_indices: Vec<_> = region
                 .get_chunks()
                 .iter()
                 .enumerate()
-                .filter_map(|(i, chunk)| if chunk.should_delete() { Some(i) } else { None })
+                .filter_map(|(i, chunk)| {
+                    let keep_reason = options.keep_reason(chunk, &context);
+                    log_chunk_decision(region_file_path, chunk, keep_reason);
+                    if options.decisions_as_jsonl {
+                        print_chunk_decision_jsonl(region_file_path, chunk, keep_reason);
+                    }
+                    if keep_reason.is_none() { Some(i) } else { None }
+                })
                 .collect();
             result.deleted_chunks += chunks_to_delete_indices.len();
 
             for &index in chunks_to_delete_indices.iter().rev() {
                 region.remove_chunk_by_index(index);
             }
+            if let Some(y) = options.delete_below_y {
+                result.removed_sections += region.remove_sections_below(y);
+            }
 
-            if region.is_empty() {
-                match std::fs::remove_file(region_file_path) {
-                    Ok(()) => result.deleted_regions += 1,
-                    Err(_) => result.io_errors += 1,
-                }
-            } else if region.is_modified() {
-                let to_bytes = region.to_bytes(compression);
-                if to_bytes.compression_fallbacks > 0 {
-                    result.compression_failures += to_bytes.compression_fallbacks;
-                    result.regions_with_compression_issues += 1;
-                    eprintln!(
-                        "Compression fallback in {} chunk(s) for {:?}",
-                        to_bytes.compression_fallbacks, region_file_path
-                    );
-                }
-                if to_bytes.header_write_failures > 0 {
-                    result.header_write_failures += to_bytes.header_write_failures;
-                    result.regions_with_header_issues += 1;
-                    eprintln!(
-                        "Header write failure: skipped payload for {} chunk(s) in {:?}",
-                        to_bytes.header_write_failures, region_file_path
+            save_region(region_file_path, &region, options, dry_run, &mut result);
+        }
+        Err(ParseRegionError::HeaderError) => {
+            discard_region(region_file_path, options, dry_run, &mut result);
+        }
+        Err(ParseRegionError::ReadError) => {
+            warn!("Cannot read {:?}", region_file_path);
+            result.io_errors += 1;
+        }
+    }
+
+    result
+}
+
+/// Deletes the chunks of a deletion plan from a region file. Chunks of the plan that are no longer in the region
+/// are skipped, and a region that cannot be parsed is left alone since the plan does not cover it
+fn replay_plan(
+    region_file_path: &Path,
+    positions: &BTreeSet<(i32, i32)>,
+    options: &TrimOptions,
+) -> OptimizeResult {
+    let mut result = OptimizeResult::default();
+    match Region::from_file_name(region_file_path, &options.schemes) {
+        Ok(mut region) => {
+            result.total_chunks += region.get_chunk_count();
+            result.add_orphaned_sectors(region.get_orphaned_sectors());
+            result.add_unparsable_chunks(region_file_path, region.get_parse_failures());
+            let deleted = positions
+                .iter()
+                .filter(|&&(x, z)| region.remove_chunk(x, z))
+                .count();
+            if deleted < positions.len() {
+                warn!(
+                    "{} chunk(s) of the plan are no longer in {:?}",
+                    positions.len() - deleted,
+                    region_file_path
+                );
+            }
+            result.deleted_chunks += deleted;
+            if let Some(y) = options.delete_below_y {
+                result.removed_sections += region.remove_sections_below(y);
+            }
+            save_region(region_file_path, &region, options, false, &mut result);
+        }
+        Err(ParseRegionError::HeaderError) => {
+            warn!(
+                "Cannot parse {:?} any more, leaving it alone",
+                region_file_path
+            );
+        }
+        Err(ParseRegionError::ReadError) => {
+            warn!("Cannot read {:?}", region_file_path);
+            result.io_errors += 1;
+        }
+    }
+
+    if options.trim_poi {
+        trim_poi(region_file_path, options, &mut result);
+    }
+    if !options.trim_entity_types.is_empty() {
+        trim_entities(region_file_path, options, &mut result);
+    }
+    result
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Saves a region after chunks were removed from it: it is discarded when empty and rewritten when modified.
+/// With `dry_run`, only the result is computed
+fn save_region(
+    region_file_path: &Path,
+    region: &Region,
+    options: &TrimOptions,
+    dry_run: bool,
+    result: &mut OptimizeResult,
+) {
+    if region.is_empty() {
+        // A header-only region is already the smallest valid empty region, there is no need to rewrite it
+        if options.keep_empty_regions && region.is_header_only() {
+            result.unchanged_regions += 1;
+        } else {
+            discard_region(region_file_path, options, dry_run, result);
+        }
+    } else if region.is_modified() && options.stream_writes && !region.is_gzipped() && !dry_run {
+        stream_region(region_file_path, region, options, result);
+    } else if region.is_modified() {
+        let serialized = region.serialize(options.compression);
+        if !serialized.unplaced_chunks.is_empty() {
+            leave_unplaced(region_file_path, &serialized.unplaced_chunks, result);
+            return;
+        }
+        let payload = match RegionPayload::from_serialized(
+            serialized,
+            region.is_gzipped(),
+            options.compression,
+        ) {
+            Ok(payload) => payload,
+            Err(err) => {
+                warn!("Cannot compress {:?}: {err}", region_file_path);
+                result.io_errors += 1;
+                return;
+            }
+        };
+
+        let original_len = std::fs::metadata(region_file_path)
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let new_len = payload.len();
+        if !options.is_worth_rewriting(original_len, new_len) {
+            skip_rewrite(region_file_path, original_len, new_len, result);
+            return;
+        }
+
+        count_compression_fallbacks(
+            region_file_path,
+            payload.serialized.compression_fallbacks,
+            result,
+        );
+        let written = if dry_run {
+            Ok(())
+        } else {
+            let expected_chunks = region.get_chunk_count();
+            write_region_with(region_file_path, options, expected_chunks, |writer| {
+                payload.write_to(writer)
+            })
+        };
+        match written {
+            Ok(()) => {
+                if !dry_run {
+                    info!(
+                        "Rewrote {:?} without {} chunk(s)",
+                        region_file_path, result.deleted_chunks
                     );
                 }
-                if atomic_write_region(region_file_path, &to_bytes.bytes).is_err() {
-                    result.io_errors += 1;
-                }
+                result.rewritten_regions += 1;
+                result.saved_bytes += original_len.saturating_sub(new_len);
+            }
+            Err(err) => {
+                warn!("Cannot write {:?}: {err}", region_file_path);
+                result.io_errors += 1;
             }
         }
-        Err(ParseRegionError::HeaderError) => match std::fs::remove_file(region_file_path) {
-            Ok(()) => result.deleted_regions += 1,
-            Err(_) => result.io_errors += 1,
+    } else {
+        result.unchanged_regions += 1;
+    }
+}
+
+/// Rewrites a modified region like [`save_region`], with its chunks written as they are compressed (`--stream-writes`).
+/// What would make [`save_region`] leave the region untouched is only known once the new file is written, the
+/// temporary file is then dropped instead of replacing the region
+fn stream_region(
+    region_file_path: &Path,
+    region: &Region,
+    options: &TrimOptions,
+    result: &mut OptimizeResult,
+) {
+    let original_len = std::fs::metadata(region_file_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let mut streamed = None;
+    let written = write_region_with(
+        region_file_path,
+        options,
+        region.get_chunk_count(),
+        |writer| {
+            let region = region.serialize_to(options.compression, writer)?;
+            let is_kept = region.unplaced_chunks.is_empty()
+                && options.is_worth_rewriting(original_len, region.len);
+            streamed = Some(region);
+            if is_kept {
+                Ok(())
+            } else {
+                Err(std::io::Error::other("the rewritten region is not kept"))
+            }
         },
-        Err(ParseRegionError::ReadError) => {
+    );
+
+    match (written, streamed) {
+        (Ok(()), streamed) => {
+            let streamed = streamed.expect("A written region was streamed");
+            count_compression_fallbacks(region_file_path, streamed.compression_fallbacks, result);
+            info!(
+                "Rewrote {:?} without {} chunk(s)",
+                region_file_path, result.deleted_chunks
+            );
+            result.rewritten_regions += 1;
+            result.saved_bytes += original_len.saturating_sub(streamed.len);
+        }
+        (Err(_), Some(streamed)) if !streamed.unplaced_chunks.is_empty() => {
+            leave_unplaced(region_file_path, &streamed.unplaced_chunks, result);
+        }
+        (Err(_), Some(streamed)) if !options.is_worth_rewriting(original_len, streamed.len) => {
+            skip_rewrite(region_file_path, original_len, streamed.len, result);
+        }
+        (Err(err), _) => {
+            warn!("Cannot write {:?}: {err}", region_file_path);
             result.io_errors += 1;
         }
     }
+}
 
-    result
+/// Leaves a region untouched because some of its chunks cannot be placed in the rewritten file
+fn leave_unplaced(
+    region_file_path: &Path,
+    unplaced_chunks: &[UnplacedChunk],
+    result: &mut OptimizeResult,
+) {
+    if let Some(unplaced) = unplaced_chunks.first() {
+        warn!(
+            "Cannot place {} chunk(s) in the rewritten {:?}, such as {unplaced}. Leaving the region untouched, \
+             --delete-malformed deletes the chunks without a position",
+            unplaced_chunks.len(),
+            region_file_path
+        );
+    }
+    result.header_write_failures += unplaced_chunks.len();
+    result.regions_with_header_issues += 1;
+    result.deleted_chunks = 0;
+    result.removed_sections = 0;
+}
+
+/// Leaves a region untouched because rewriting it would not save enough space, see `--min-savings`
+fn skip_rewrite(
+    region_file_path: &Path,
+    original_len: u64,
+    new_len: u64,
+    result: &mut OptimizeResult,
+) {
+    info!(
+        "Skipped {:?}, rewriting would only shrink it from {} to {} bytes",
+        region_file_path, original_len, new_len
+    );
+    // The region is left untouched, so none of its chunks get deleted
+    result.deleted_chunks = 0;
+    result.removed_sections = 0;
+    result.skipped_regions += 1;
+}
+
+fn count_compression_fallbacks(
+    region_file_path: &Path,
+    compression_fallbacks: usize,
+    result: &mut OptimizeResult,
+) {
+    if compression_fallbacks > 0 {
+        result.compression_failures += compression_fallbacks;
+        result.regions_with_compression_issues += 1;
+        warn!(
+            "Compression fallback in {} chunk(s) for {:?}",
+            compression_fallbacks, region_file_path
+        );
+    }
+}
+
+/// Rewrites a region file kept next to the trimmed ones (`poi/`, `entities/`), gzipped again if it was.
+/// Returns the new length of the file
+pub(crate) fn rewrite_companion_region(
+    path: &Path,
+    region: &Region,
+    options: &TrimOptions,
+) -> std::io::Result<u64> {
+    let payload = RegionPayload::new(region, options.compression)?;
+    write_region_with(path, options, region.get_chunk_count(), |writer| {
+        payload.write_to(writer)
+    })?;
+    Ok(payload.len())
+}
+
+/// Content of a rewritten region file: the serialized region, written chunk by chunk, or its gzipped copy when the
+/// region was read from a gzipped file. Both are held in memory until written, unlike with `--stream-writes`
+pub(crate) struct RegionPayload {
+    pub serialized: SerializedRegion,
+    gzipped: Option<Vec<u8>>,
+}
+
+impl RegionPayload {
+    /// Fails when chunks of the region cannot be placed in the rewritten file, writing it would lose them
+    pub(crate) fn new(region: &Region, compression: CompressionLevel) -> std::io::Result<Self> {
+        Self::from_serialized(
+            region.serialize(compression),
+            region.is_gzipped(),
+            compression,
+        )
+    }
+
+    pub(crate) fn from_serialized(
+        serialized: SerializedRegion,
+        is_gzipped: bool,
+        compression: CompressionLevel,
+    ) -> std::io::Result<Self> {
+        if let Some(unplaced) = serialized.unplaced_chunks.first() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "{} chunk(s) cannot be placed in the region, such as {unplaced}",
+                    serialized.unplaced_chunks.len()
+                ),
+            ));
+        }
+        let gzipped = if is_gzipped {
+            Some(gzip_file(compression, |encoder| {
+                serialized.write_to(encoder)
+            })?)
+        } else {
+            None
+        };
+        Ok(Self {
+            serialized,
+            gzipped,
+        })
+    }
+
+    /// Length of the file once written
+    pub(crate) fn len(&self) -> u64 {
+        match &self.gzipped {
+            Some(bytes) => bytes.len() as u64,
+            None => self.serialized.file_len(),
+        }
+    }
+
+    pub(crate) fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        match &self.gzipped {
+            Some(bytes) => writer.write_all(bytes),
+            None => self.serialized.write_to(writer),
+        }
+    }
+}
+
+/// Deletes a region file, or replaces it with a header-only region when empty regions are kept
+/// (some server plugins treat a missing region differently from an empty one).
+pub(crate) fn discard_region(
+    region_file_path: &Path,
+    options: &TrimOptions,
+    dry_run: bool,
+    result: &mut OptimizeResult,
+) {
+    let is_gzipped = region_file_path.extension().is_some_and(|ext| ext == "gz");
+    let original_len = std::fs::metadata(region_file_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+    // Length of what is left in place of the region
+    let discarded = if dry_run {
+        Ok(if options.keep_empty_regions {
+            HEADER_SIZE as u64
+        } else {
+            0
+        })
+    } else if options.keep_empty_regions && is_gzipped {
+        gzip_file(options.compression, |encoder| {
+            encoder.write_all(&[0; HEADER_SIZE])
+        })
+        .and_then(|bytes| {
+            write_region(region_file_path, &bytes, options).map(|()| bytes.len() as u64)
+        })
+    } else if options.keep_empty_regions {
+        write_region(region_file_path, &[0; HEADER_SIZE], options).map(|()| HEADER_SIZE as u64)
+    } else {
+        // Like rewrites, a deletion is only durable once the directory entry is flushed
+        std::fs::remove_file(region_file_path).map(|()| {
+            sync_parent_dir(region_file_path);
+            0
+        })
+    };
+
+    match discarded {
+        Ok(new_len) => {
+            if !dry_run {
+                info!("Discarded empty or invalid region {:?}", region_file_path);
+            }
+            result.add_discarded_region(options.keep_empty_regions);
+            result.saved_bytes += original_len.saturating_sub(new_len);
+        }
+        Err(err) => {
+            warn!("Cannot discard {:?}: {err}", region_file_path);
+            result.io_errors += 1;
+        }
+    }
 }
 
 /// Atomic + durable replacement of a region file.
@@ -119,7 +645,22 @@ fn optimize_write(region_file_path: &Path, compression: Compression) -> Optimize
 ///      acceptable here — on platforms where opening a directory or fsyncing it is
 ///      not supported (some Windows configurations), the journaling filesystem
 ///      already provides equivalent ordering guarantees.
-fn atomic_write_region(region_file_path: &Path, payload: &[u8]) -> std::io::Result<()> {
+///
+/// The payload is written by `write`, so it never has to be concatenated into one buffer.
+pub(crate) fn atomic_write_region(
+    region_file_path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    atomic_write_region_checked(region_file_path, write, |_| Ok(()))
+}
+
+/// Same as [`atomic_write_region`], with `check` run on the written tempfile before it replaces the file.
+/// When it fails, the tempfile is removed and the file is left untouched
+fn atomic_write_region_checked(
+    region_file_path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+    check: impl FnOnce(&Path) -> std::io::Result<()>,
+) -> std::io::Result<()> {
     let tmp_path = tempfile_path_for(region_file_path);
     let original_permissions: Option<Permissions> = std::fs::metadata(region_file_path)
         .ok()
@@ -128,11 +669,11 @@ fn atomic_write_region(region_file_path: &Path, payload: &[u8]) -> std::io::Resu
     let write_result = (|| -> std::io::Result<()> {
         let file = File::create(&tmp_path)?;
         let mut writer = BufWriter::with_capacity(32 * 1024 * 1024, file);
-        writer.write_all(payload)?;
+        write(&mut writer)?;
         writer.flush()?;
         let file = writer.into_inner().map_err(|e| e.into_error())?;
         file.sync_all()?;
-        Ok(())
+        check(&tmp_path)
     })();
     if let Err(e) = write_result {
         let _ = std::fs::remove_file(&tmp_path);
@@ -149,20 +690,105 @@ fn atomic_write_region(region_file_path: &Path, payload: &[u8]) -> std::io::Resu
         return Err(e);
     }
 
-    // Best-effort directory fsync for durability of the rename itself.
-    // On POSIX this is the standard atomic-rename idiom. On Windows/some FSes
-    // opening a directory or fsyncing it may not be supported — we treat any
-    // failure as non-fatal because journaled filesystems already enforce the
-    // necessary ordering.
-    if let Some(dir) = region_file_path.parent() {
-        if let Ok(dir_handle) = File::open(dir) {
-            let _ = dir_handle.sync_all();
+    sync_parent_dir(region_file_path);
+
+    Ok(())
+}
+
+/// Replaces a region file with [`atomic_write_region`]. With `preserve_mtime`, the new file gets the modification
+/// time of the one it replaces, so that backup tools comparing modification times do not upload it again
+pub(crate) fn write_region(
+    region_file_path: &Path,
+    payload: &[u8],
+    options: &TrimOptions,
+) -> std::io::Result<()> {
+    // Only header-only files are written from raw bytes
+    write_region_with(region_file_path, options, 0, |writer| {
+        writer.write_all(payload)
+    })
+}
+
+/// Same as [`write_region`], with the payload written by `write` instead of passed as one buffer.
+/// With `verify_after_write`, the written file must parse back into `expected_chunks` chunks to replace the region
+pub(crate) fn write_region_with(
+    region_file_path: &Path,
+    options: &TrimOptions,
+    expected_chunks: usize,
+    write: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let original_mtime = options
+        .preserve_mtime
+        .then(|| {
+            std::fs::metadata(region_file_path)
+                .and_then(|m| m.modified())
+                .ok()
+        })
+        .flatten();
+    atomic_write_region_checked(region_file_path, write, |written| {
+        if options.verify_after_write {
+            verify_written_region(written, expected_chunks, &options.schemes)
+        } else {
+            Ok(())
+        }
+    })?;
+
+    if let Some(mtime) = original_mtime {
+        // The content is already safely written, failing to restore the time only costs a backup upload
+        let restored = File::options()
+            .write(true)
+            .open(region_file_path)
+            .and_then(|file| file.set_modified(mtime));
+        if let Err(err) = restored {
+            warn!(
+                "Cannot restore the modification time of {:?}: {err}",
+                region_file_path
+            );
         }
     }
+    Ok(())
+}
 
+/// Parses a written region file again (`--verify-after-write`), failing unless it holds `expected_chunks` chunks
+/// that all decode
+fn verify_written_region(
+    written: &Path,
+    expected_chunks: usize,
+    schemes: &SchemeMap,
+) -> std::io::Result<()> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+    let region = Region::from_file_name(written, schemes).map_err(|err| {
+        invalid(format!(
+            "the rewritten region does not parse ({err}), leaving it untouched"
+        ))
+    })?;
+    let failures = region.get_parse_failures().len();
+    if failures > 0 {
+        return Err(invalid(format!(
+            "{failures} chunk(s) of the rewritten region do not parse, leaving it untouched"
+        )));
+    }
+    if region.get_chunk_count() != expected_chunks {
+        return Err(invalid(format!(
+            "the rewritten region holds {} chunk(s) instead of {expected_chunks}, leaving it untouched",
+            region.get_chunk_count()
+        )));
+    }
     Ok(())
 }
 
+/// Best-effort directory fsync for durability of a rename or deletion in it.
+/// On POSIX this is the standard atomic-rename idiom. On Windows/some FSes
+/// opening a directory or fsyncing it may not be supported — we treat any
+/// failure as non-fatal because journaled filesystems already enforce the
+/// necessary ordering.
+fn sync_parent_dir(path: &Path) {
+    if let Some(dir) = path.parent() {
+        if let Ok(dir_handle) = File::open(dir) {
+            let _ = dir_handle.sync_all();
+        }
+    }
+}
+
 /// Build a sibling tempfile path: `r.X.Z.mca` → `r.X.Z.mca.tmp.<pid>.<seq>`.
 /// Sibling (same directory) keeps `rename` on a single filesystem so it stays atomic.
 /// pid + monotonic counter avoids collisions when rayon writes many regions in parallel
@@ -177,6 +803,23 @@ fn tempfile_path_for(target: &Path) -> PathBuf {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::region_loader::compression_level::CompressionLevel;
+    use crate::region_loader::test_region::{TestChunk, test_region_bytes};
+
+    #[test]
+    fn test_confirm_write_requires_yes() {
+        let worlds = [PathBuf::from("/saves/MyWorld")];
+        let mut output = Vec::new();
+
+        assert!(confirm_write(&worlds, 3, &mut &b"yes\n"[..], &mut output).unwrap());
+        let prompt = String::from_utf8(output).unwrap();
+        assert!(prompt.contains("3 region file(s)"));
+        assert!(prompt.contains("/saves/MyWorld"));
+
+        for answer in [&b"y\n"[..], b"no\n", b""] {
+            assert!(!confirm_write(&worlds, 3, &mut &answer[..], &mut Vec::new()).unwrap());
+        }
+    }
 
     #[test]
     fn test_tempfile_path_is_sibling() {
@@ -184,11 +827,12 @@ mod tests {
         let tmp = tempfile_path_for(target);
         assert_eq!(tmp.parent(), target.parent(), "tempfile must be a sibling");
         assert!(tmp.file_name().unwrap().to_string_lossy().contains(".tmp."));
-        assert!(tmp
-            .file_name()
-            .unwrap()
-            .to_string_lossy()
-            .starts_with("r.0.0.mca"));
+        assert!(
+            tmp.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("r.0.0.mca")
+        );
     }
 
     #[test]
@@ -220,12 +864,17 @@ mod tests {
         let original_bytes = include_bytes!("../../test_files/r.-1.-1.mca");
         std::fs::write(&target, original_bytes).unwrap();
 
-        let result = optimize_write(&target, Compression::fast());
+        let options = TrimOptions {
+            compression: CompressionLevel::fast(),
+            ..TrimOptions::default()
+        };
+        let result = optimize_write(&target, &options, false);
         assert!(result.total_chunks > 0);
         assert_eq!(result.io_errors, 0, "no I/O errors expected on healthy sample");
 
         // The file still exists (was not removed) and re-parses cleanly.
-        let reparsed = Region::from_file_name(&target).expect("written file must re-parse");
+        let reparsed = Region::from_file_name(&target, &SchemeMap::default())
+            .expect("written file must re-parse");
         let expected_remaining = result.total_chunks - result.deleted_chunks;
         assert_eq!(reparsed.get_chunk_count(), expected_remaining);
 
@@ -244,6 +893,50 @@ mod tests {
         std::fs::remove_dir_all(&tmp_dir).ok();
     }
 
+    #[test]
+    fn test_keep_empty_regions_writes_header_only_file() {
+        let tmp_dir = std::env::temp_dir().join(format!(
+            "mwt_keep_empty_{}_{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let kept = tmp_dir.join("r.0.0.mca");
+        let deleted = tmp_dir.join("r.0.1.mca");
+        // Too small to hold a header: discarded by write
+        std::fs::write(&kept, b"corrupted").unwrap();
+        std::fs::write(&deleted, b"corrupted").unwrap();
+
+        let keep_options = TrimOptions {
+            keep_empty_regions: true,
+            ..TrimOptions::default()
+        };
+        let result = optimize_write(&kept, &keep_options, false);
+        assert_eq!(result.emptied_regions, 1);
+        assert_eq!(result.deleted_regions, 0);
+        assert_eq!(std::fs::read(&kept).unwrap(), vec![0; HEADER_SIZE]);
+
+        // Running again on the header-only file leaves it untouched
+        let result = optimize_write(&kept, &keep_options, false);
+        assert_eq!(result.emptied_regions, 0);
+        assert!(kept.exists());
+
+        let result = optimize_write(&deleted, &TrimOptions::default(), false);
+        assert_eq!(result.deleted_regions, 1);
+        assert!(!deleted.exists());
+
+        // Without the option, a header-only region is deleted like any empty region
+        std::fs::write(&deleted, vec![0; HEADER_SIZE]).unwrap();
+        let result = optimize_write(&deleted, &TrimOptions::default(), false);
+        assert_eq!(result.deleted_regions, 1);
+        assert!(!deleted.exists());
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+
     /// Verifies that atomic_write_region preserves the file mode of the original file.
     /// Critical for server worlds where region files have non-default permissions
     /// (e.g. group-readable for a `minecraft` system user).
@@ -271,7 +964,7 @@ mod tests {
         let mode_before = std::fs::metadata(&target).unwrap().permissions().mode() & 0o777;
         assert_eq!(mode_before, 0o640);
 
-        atomic_write_region(&target, b"replacement payload").unwrap();
+        atomic_write_region(&target, |writer| writer.write_all(b"replacement payload")).unwrap();
 
         let mode_after = std::fs::metadata(&target).unwrap().permissions().mode() & 0o777;
         assert_eq!(
@@ -295,8 +988,8 @@ mod tests {
         std::fs::write(&target, original_bytes).unwrap();
 
         // Pre-build the payload we'll write — we want to isolate I/O cost, not compression.
-        let region = Region::from_file_name(&target).unwrap();
-        let payload = region.to_bytes(Compression::fast()).bytes;
+        let region = Region::from_file_name(&target, &SchemeMap::default()).unwrap();
+        let payload = region.to_bytes(CompressionLevel::fast()).bytes;
         println!(
             "\npayload size: {} bytes ({:.2} MB)",
             payload.len(),
@@ -307,14 +1000,14 @@ mod tests {
 
         // Warmup
         for _ in 0..5 {
-            atomic_write_region(&target, &payload).unwrap();
+            atomic_write_region(&target, |writer| writer.write_all(&payload)).unwrap();
         }
 
         // A. Atomic write: tempfile + write + flush + fsync + rename + dir-fsync
         let mut atomic_total = std::time::Duration::ZERO;
         for _ in 0..ITERS {
             let start = std::time::Instant::now();
-            atomic_write_region(&target, &payload).unwrap();
+            atomic_write_region(&target, |writer| writer.write_all(&payload)).unwrap();
             atomic_total += start.elapsed();
         }
 
@@ -344,4 +1037,286 @@ mod tests {
 
         std::fs::remove_dir_all(&tmp_dir).ok();
     }
+
+    #[test]
+    fn test_optimize_write_deletes_unused_chunks() {
+        let tmp_dir =
+            std::env::temp_dir().join(format!("mwt_unused_chunks_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let target = tmp_dir.join("r.0.0.mca");
+        std::fs::write(
+            &target,
+            test_region_bytes(&[
+                TestChunk::full(0, 0),
+                TestChunk::full(1, 0).status("minecraft:features"),
+                TestChunk::full(2, 0)
+                    .status("minecraft:features")
+                    .inhabited_time(600),
+            ]),
+        )
+        .unwrap();
+
+        let result = optimize_write(&target, &TrimOptions::default(), false);
+        assert_eq!(result.total_chunks, 3);
+        assert_eq!(result.deleted_chunks, 1);
+        assert_eq!((result.rewritten_regions, result.unchanged_regions), (1, 0));
+        let region = Region::from_file_name(&target, &SchemeMap::default()).unwrap();
+        let mut positions: Vec<_> = region
+            .get_chunks()
+            .iter()
+            .map(|chunk| chunk.get_position().unwrap())
+            .collect();
+        positions.sort();
+        assert_eq!(positions, [(0, 0), (2, 0)]);
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+
+    #[test]
+    fn test_stream_writes() {
+        let tmp_dir =
+            std::env::temp_dir().join(format!("mwt_stream_writes_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let target = tmp_dir.join("r.0.0.mca");
+        let original = test_region_bytes(&[
+            TestChunk::full(0, 0),
+            TestChunk::full(1, 0).status("minecraft:features"),
+            TestChunk::full(2, 0),
+        ]);
+        let stream_options = TrimOptions {
+            stream_writes: true,
+            ..TrimOptions::default()
+        };
+
+        std::fs::write(&target, &original).unwrap();
+        let result = optimize_write(&target, &TrimOptions::default(), false);
+        let rewritten = std::fs::read(&target).unwrap();
+        std::fs::write(&target, &original).unwrap();
+        let streamed_result = optimize_write(&target, &stream_options, false);
+        assert_eq!(streamed_result.deleted_chunks, 1);
+        assert_eq!(streamed_result.saved_bytes, result.saved_bytes);
+        assert_eq!(std::fs::read(&target).unwrap(), rewritten);
+
+        // A rewrite that does not save enough is dropped once written, the region and its directory are untouched
+        std::fs::write(&target, &original).unwrap();
+        let options = TrimOptions {
+            min_savings: 99.0,
+            ..stream_options
+        };
+        let result = optimize_write(&target, &options, false);
+        assert_eq!(
+            (
+                result.skipped_regions,
+                result.deleted_chunks,
+                result.io_errors
+            ),
+            (1, 0, 0)
+        );
+        assert_eq!(std::fs::read(&target).unwrap(), original);
+        assert_eq!(std::fs::read_dir(&tmp_dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+
+    #[test]
+    fn test_verify_after_write() {
+        let tmp_dir =
+            std::env::temp_dir().join(format!("mwt_verify_after_write_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let target = tmp_dir.join("r.0.0.mca");
+        let original = test_region_bytes(&[
+            TestChunk::full(0, 0),
+            TestChunk::full(1, 0).status("minecraft:features"),
+        ]);
+        let options = TrimOptions {
+            verify_after_write: true,
+            ..TrimOptions::default()
+        };
+
+        std::fs::write(&target, &original).unwrap();
+        let result = optimize_write(&target, &options, false);
+        assert_eq!((result.deleted_chunks, result.io_errors), (1, 0));
+        assert_eq!(
+            Region::from_file_name(&target, &SchemeMap::default())
+                .unwrap()
+                .get_chunk_count(),
+            1
+        );
+
+        // A written file missing chunks, or that does not parse, never replaces the region
+        std::fs::write(&target, &original).unwrap();
+        let rewritten = test_region_bytes(&[TestChunk::full(0, 0)]);
+        let err = write_region_with(&target, &options, 2, |writer| writer.write_all(&rewritten))
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let err = write_region_with(&target, &options, 0, |writer| {
+            writer.write_all(b"not a region")
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(std::fs::read(&target).unwrap(), original);
+        assert_eq!(std::fs::read_dir(&tmp_dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+
+    #[test]
+    fn test_delete_below_y() {
+        let tmp_dir =
+            std::env::temp_dir().join(format!("mwt_delete_below_y_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let target = tmp_dir.join("r.-1.-1.mca");
+        std::fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca"),
+            &target,
+        )
+        .unwrap();
+        let options = TrimOptions {
+            delete_below_y: Some(0),
+            ..TrimOptions::default()
+        };
+
+        let result = optimize_write(&target, &options, false);
+        assert!(result.removed_sections > 0);
+        assert_eq!(result.io_errors, 0);
+        let region = Region::from_file_name(&target, &SchemeMap::default()).unwrap();
+        assert_eq!(
+            region.get_chunk_count(),
+            result.total_chunks - result.deleted_chunks
+        );
+        for chunk in region.get_chunks() {
+            assert_eq!(chunk.sections_below(0), 0);
+            assert!(chunk.nbt.find_tag("Heightmaps").is_some());
+        }
+        assert_eq!(optimize_write(&target, &options, false).removed_sections, 0);
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+
+    #[test]
+    fn test_replay_plan() {
+        let tmp_dir = std::env::temp_dir().join(format!("mwt_replay_plan_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let target = tmp_dir.join("r.0.0.mca");
+        std::fs::write(
+            &target,
+            test_region_bytes(&[
+                TestChunk::full(0, 0),
+                TestChunk::full(1, 0),
+                TestChunk::full(2, 0),
+            ]),
+        )
+        .unwrap();
+
+        // Chunks no longer in the region are skipped, whatever the options would decide the others go
+        let result = replay_plan(
+            &target,
+            &BTreeSet::from([(1, 0), (7, 7)]),
+            &TrimOptions::default(),
+        );
+        assert_eq!(result.total_chunks, 3);
+        assert_eq!(result.deleted_chunks, 1);
+        let region = Region::from_file_name(&target, &SchemeMap::default()).unwrap();
+        assert_eq!(region.get_chunk_count(), 2);
+        assert!(
+            region
+                .get_chunks()
+                .iter()
+                .all(|chunk| chunk.get_position() != Ok((1, 0)))
+        );
+
+        let result = replay_plan(
+            &target,
+            &BTreeSet::from([(0, 0), (2, 0)]),
+            &TrimOptions::default(),
+        );
+        assert_eq!(result.deleted_regions, 1);
+        assert!(!target.exists());
+
+        // A region reached twice is replayed once
+        std::fs::write(
+            &target,
+            test_region_bytes(&[TestChunk::full(0, 0), TestChunk::full(1, 0)]),
+        )
+        .unwrap();
+        let plan = DeletionPlan::from([(canonical(&target), BTreeSet::from([(1, 0)]))]);
+        let paths = [target.clone(), target.clone()];
+        let result = write_worlds(
+            &paths,
+            &ScanOptions::default(),
+            &TrimOptions::default(),
+            true,
+            Some(plan),
+        )
+        .unwrap();
+        assert_eq!((result.total_chunks, result.deleted_chunks), (2, 1));
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+
+    #[test]
+    fn test_preserve_mtime() {
+        let tmp_dir =
+            std::env::temp_dir().join(format!("mwt_preserve_mtime_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let target = tmp_dir.join("r.0.0.mca");
+        let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let write_with_mtime = || {
+            std::fs::write(&target, b"corrupted").unwrap();
+            File::options()
+                .write(true)
+                .open(&target)
+                .unwrap()
+                .set_modified(mtime)
+                .unwrap();
+        };
+        let modified = || std::fs::metadata(&target).unwrap().modified().unwrap();
+
+        let mut options = TrimOptions {
+            keep_empty_regions: true,
+            preserve_mtime: true,
+            ..TrimOptions::default()
+        };
+        write_with_mtime();
+        let result = optimize_write(&target, &options, false);
+        assert_eq!(result.emptied_regions, 1);
+        assert_eq!(modified(), mtime);
+
+        options.preserve_mtime = false;
+        write_with_mtime();
+        optimize_write(&target, &options, false);
+        assert_ne!(modified(), mtime);
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+
+    #[test]
+    fn test_keep_empty_regions_keeps_gzipped_files_gzipped() {
+        let tmp_dir =
+            std::env::temp_dir().join(format!("mwt_keep_empty_gz_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let target = tmp_dir.join("r.0.0.mca.gz");
+        std::fs::write(&target, b"corrupted").unwrap();
+
+        let options = TrimOptions {
+            keep_empty_regions: true,
+            ..TrimOptions::default()
+        };
+        let result = optimize_write(&target, &options, false);
+        assert_eq!(result.emptied_regions, 1);
+
+        let region = Region::from_file_name(&target, &SchemeMap::default())
+            .expect("emptied region must re-parse");
+        assert!(region.is_gzipped());
+        assert!(region.is_empty());
+
+        // The gzipped header-only region is left as it is by the next runs
+        let emptied = std::fs::read(&target).unwrap();
+        let result = optimize_write(&target, &options, false);
+        assert_eq!(result.emptied_regions, 0);
+        assert_eq!(result.unchanged_regions, 1);
+        assert_eq!(std::fs::read(&target).unwrap(), emptied);
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
 }
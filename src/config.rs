@@ -0,0 +1,406 @@
+use crate::cli::{Cli, Mode};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command, CommandFactory, FromArgMatches, ValueEnum};
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// Prefix of the environment variables holding option defaults, `MINECRAFT_WORLD_TRIMMER_COMPRESSION_LEVEL=9`
+/// stands for `--compression-level 9`
+const ENV_PREFIX: &str = "MINECRAFT_WORLD_TRIMMER_";
+
+/// Options only some modes use, with those modes. Any other mode would silently ignore them
+const MODE_ONLY_OPTIONS: &[(&str, &[Mode])] = &[
+    ("list", &[Mode::Check, Mode::Snapshot, Mode::Diff]),
+    ("map", &[Mode::Check]),
+    ("plan", &[Mode::Check, Mode::Write]),
+    ("save_snapshot", &[Mode::Snapshot]),
+    ("compare_snapshot", &[Mode::Snapshot]),
+    ("expect_removed", &[Mode::Snapshot]),
+    ("self_check", &[Mode::Check]),
+    ("fail_on_change", &[Mode::Check, Mode::Write]),
+    (
+        "report_top_regions",
+        &[Mode::Check, Mode::Write, Mode::Stats],
+    ),
+    ("output_dir", &[Mode::Write, Mode::Browse]),
+    ("ids_from", &[Mode::Palette]),
+];
+
+/// Parses the command line, taking the options of the config file (`--config`) and of the environment variables as
+/// defaults. Options on the command line win over environment variables, which win over the config file
+pub fn parse_cli() -> Cli {
+    let mut args = std::env::args_os();
+    let program = args.next().unwrap_or_default();
+    let cli_args: Vec<OsString> = args.collect();
+    let mut command = Cli::command().args_override_self(true);
+
+    let defaults = match default_args(&command, &cli_args, |name| std::env::var(name).ok()) {
+        Ok(defaults) => defaults,
+        Err(message) => command.error(ErrorKind::InvalidValue, message).exit(),
+    };
+    // Arguments past the program and the defaults come from the command line
+    let first_cli_arg = 1 + defaults.len();
+    let matches =
+        command.get_matches_from(std::iter::once(program).chain(defaults).chain(cli_args));
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if let Some(message) = mode_conflict(cli.mode, &matches, first_cli_arg) {
+        Cli::command()
+            .error(ErrorKind::ArgumentConflict, message)
+            .exit();
+    }
+    cli
+}
+
+/// Tells which option of the command line the mode would silently ignore, such as `--plan` in browse mode, see
+/// [`MODE_ONLY_OPTIONS`]. Those of the config file and the environment variables, from the arguments before
+/// `first_cli_arg`, are defaults shared by every mode and are not checked
+fn mode_conflict(mode: Mode, matches: &ArgMatches, first_cli_arg: usize) -> Option<String> {
+    let (id, modes) = MODE_ONLY_OPTIONS.iter().find(|(id, modes)| {
+        matches.value_source(id) == Some(ValueSource::CommandLine)
+            && matches
+                .index_of(id)
+                .is_some_and(|index| index >= first_cli_arg)
+            && !modes.contains(&mode)
+    })?;
+    let names: Vec<String> = modes.iter().map(|&mode| mode_name(mode)).collect();
+    Some(format!(
+        "--{} cannot be used in {} mode, only in: {}",
+        id.replace('_', "-"),
+        mode_name(mode),
+        names.join(", ")
+    ))
+}
+
+fn mode_name(mode: Mode) -> String {
+    mode.to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// Arguments standing for the options of the config file followed by those of the environment variables,
+/// to be placed before the arguments of the command line
+fn default_args(
+    command: &Command,
+    cli_args: &[OsString],
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<OsString>, String> {
+    let mut args = Vec::new();
+
+    if let Some(config_path) =
+        config_path(cli_args).or_else(|| env(&env_name("config")).map(PathBuf::from))
+    {
+        let content = std::fs::read_to_string(&config_path)
+            .map_err(|err| format!("Cannot read the config file {config_path:?}: {err}"))?;
+        let settings = parse_config(&content).map_err(|err| format!("{config_path:?}: {err}"))?;
+        for (line, key, values) in settings {
+            let long = key.replace('_', "-");
+            match option_args(command, &long, &values) {
+                Some(option_args) => args.extend(option_args?),
+                None => {
+                    return Err(format!(
+                        "{config_path:?}: line {line}: unknown option `{key}`"
+                    ));
+                }
+            }
+        }
+    }
+
+    for long in command.get_arguments().filter_map(|arg| arg.get_long()) {
+        let name = env_name(long);
+        // `config` gives no option args, it was read above
+        if let Some(option_args) = env(&name).and_then(|value| option_args(command, long, &[value]))
+        {
+            args.extend(option_args.map_err(|err| format!("{name}: {err}"))?);
+        }
+    }
+
+    Ok(args)
+}
+
+/// `--config <PATH>` of the command line, read before clap parses it since it changes the arguments
+fn config_path(cli_args: &[OsString]) -> Option<PathBuf> {
+    let mut args = cli_args.iter();
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+fn env_name(long: &str) -> String {
+    format!("{ENV_PREFIX}{}", long.replace('-', "_").to_uppercase())
+}
+
+/// Arguments giving these values to the option named `long`, `None` if there is no such option.
+/// Flags take `true` or `false`, and `--verbose` the number of times it is repeated
+fn option_args(
+    command: &Command,
+    long: &str,
+    values: &[String],
+) -> Option<Result<Vec<OsString>, String>> {
+    let arg = command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(long) && long != "config")?;
+    let flag = OsString::from(format!("--{long}"));
+
+    Some(match arg.get_action() {
+        ArgAction::SetTrue => match values {
+            [value] if value == "true" || value == "1" => Ok(vec![flag]),
+            [value] if value == "false" || value == "0" || value.is_empty() => Ok(Vec::new()),
+            _ => Err(format!("`{long}` is a flag, expected true or false")),
+        },
+        ArgAction::Count => match values {
+            [value] => value
+                .parse::<usize>()
+                .map(|count| vec![flag; count])
+                .map_err(|_| format!("`{long}` counts, expected a number")),
+            _ => Err(format!("`{long}` counts, expected a number")),
+        },
+        _ => Ok(values
+            .iter()
+            .map(|value| OsString::from(format!("--{long}={value}")))
+            .collect()),
+    })
+}
+
+/// Parses the `key = value` lines of a config file, a subset of TOML: values are strings (`"..."` or `'...'`),
+/// bare numbers and booleans, or one line arrays of those. Returns the line number, key and values of every setting
+fn parse_config(content: &str) -> Result<Vec<(usize, String, Vec<String>)>, String> {
+    let mut settings = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("line {line_number}: {message}");
+        if line.starts_with('[') {
+            return Err(error(
+                "tables are not supported, options go at the top level",
+            ));
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected `option = value`"))?;
+        let key = key.trim();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(error("invalid option name"));
+        }
+        let values = parse_value(value.trim()).map_err(|message| error(&message))?;
+        settings.push((line_number, key.to_string(), values));
+    }
+    Ok(settings)
+}
+
+/// Reads a value or an array of values, up to an optional trailing comment
+fn parse_value(value: &str) -> Result<Vec<String>, String> {
+    let mut chars = value.chars().peekable();
+    let mut values = Vec::new();
+    if chars.peek() == Some(&'[') {
+        chars.next();
+        loop {
+            skip_spaces(&mut chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                break;
+            }
+            values.push(parse_scalar(&mut chars)?);
+            skip_spaces(&mut chars);
+            match chars.next() {
+                Some(',') => {}
+                Some(']') => break,
+                _ => return Err("expected `,` or `]` in the array".to_string()),
+            }
+        }
+    } else {
+        values.push(parse_scalar(&mut chars)?);
+    }
+
+    skip_spaces(&mut chars);
+    match chars.next() {
+        None | Some('#') => Ok(values),
+        Some(_) => {
+            Err("unexpected characters after the value, quote strings holding spaces".to_string())
+        }
+    }
+}
+
+fn parse_scalar(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+    let mut scalar = String::new();
+    match chars.peek() {
+        // Literal strings, handy for Windows paths
+        Some('\'') => {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('\'') => return Ok(scalar),
+                    Some(c) => scalar.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+        }
+        Some('"') => {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(scalar),
+                    Some('\\') => match chars.next() {
+                        Some('n') => scalar.push('\n'),
+                        Some('t') => scalar.push('\t'),
+                        Some(c @ ('"' | '\\')) => scalar.push(c),
+                        _ => return Err("invalid escape sequence in string".to_string()),
+                    },
+                    Some(c) => scalar.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+        }
+        _ => {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || matches!(c, ',' | ']' | '#') {
+                    break;
+                }
+                scalar.push(c);
+                chars.next();
+            }
+            if scalar.is_empty() {
+                Err("missing value".to_string())
+            } else {
+                Ok(scalar)
+            }
+        }
+    }
+}
+
+fn skip_spaces(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let content = "# Nightly trim\n\
+                       compression-level = 9\n\
+                       keep_empty_regions = true # for the map plugin\n\
+                       summary-file = 'C:\\trims\\summary.log'\n\
+                       biome = [\"custom:a\", \"custom:b\"]\n\
+                       \n\
+                       include-glob = []\n";
+        let settings = parse_config(content).unwrap();
+        let values = |key: &str| {
+            settings
+                .iter()
+                .find(|(_, k, _)| k == key)
+                .map(|(_, _, v)| v.clone())
+        };
+        assert_eq!(values("compression-level"), Some(vec!["9".to_string()]));
+        assert_eq!(values("keep_empty_regions"), Some(vec!["true".to_string()]));
+        assert_eq!(
+            values("summary-file"),
+            Some(vec!["C:\\trims\\summary.log".to_string()])
+        );
+        assert_eq!(
+            values("biome"),
+            Some(vec!["custom:a".to_string(), "custom:b".to_string()])
+        );
+        assert_eq!(values("include-glob"), Some(Vec::new()));
+        assert_eq!(settings[1].0, 3);
+
+        for invalid in [
+            "[write]",
+            "yes",
+            "plan = \"unterminated",
+            "map = a b",
+            "biome = [\"a\" \"b\"]",
+            " = 1",
+        ] {
+            assert!(parse_config(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_command_line_wins_over_env_and_config() {
+        let config = std::env::temp_dir().join(format!("mwt_config_{}.toml", std::process::id()));
+        std::fs::write(
+            &config,
+            "compression-level = 9\nmin-savings = 5\nkeep-empty-regions = true\nverbose = 1\n",
+        )
+        .unwrap();
+        let command = Cli::command().args_override_self(true);
+        let cli_args: Vec<OsString> = ["check", "world", "--min-savings", "20", "--config"]
+            .iter()
+            .map(OsString::from)
+            .chain([config.clone().into_os_string()])
+            .collect();
+        let env = |name: &str| match name {
+            "MINECRAFT_WORLD_TRIMMER_COMPRESSION_LEVEL" => Some("3".to_string()),
+            "MINECRAFT_WORLD_TRIMMER_MIN_SAVINGS" => Some("10".to_string()),
+            "MINECRAFT_WORLD_TRIMMER_TRIM_POI" => Some("false".to_string()),
+            _ => None,
+        };
+
+        let defaults = default_args(&command, &cli_args, env).unwrap();
+        let args = std::iter::once(OsString::from("minecraft_world_trimmer"))
+            .chain(defaults)
+            .chain(cli_args.iter().cloned());
+        let cli = Cli::from_arg_matches(&command.try_get_matches_from(args).unwrap()).unwrap();
+        assert!(cli.mode == Mode::Check);
+        assert_eq!(cli.compression_level.to_compression().level(), 3);
+        assert_eq!(cli.min_savings, 20.0);
+        assert!(cli.keep_empty_regions);
+        assert!(!cli.trim_poi);
+        assert_eq!(cli.verbose, 1);
+
+        std::fs::write(&config, "unknown-option = 1\n").unwrap();
+        let err = default_args(&Cli::command(), &cli_args, |_| None).unwrap_err();
+        assert!(
+            err.contains("line 1: unknown option `unknown-option`"),
+            "{err}"
+        );
+
+        std::fs::remove_file(&config).ok();
+    }
+
+    #[test]
+    fn test_mode_only_options() {
+        // `defaults` stand for the options of the config file, placed before the command line
+        let conflict = |defaults: &[&str], args: &[&str]| {
+            let matches = Cli::command()
+                .args_override_self(true)
+                .try_get_matches_from(
+                    ["minecraft_world_trimmer"]
+                        .iter()
+                        .chain(defaults)
+                        .chain(args),
+                )
+                .unwrap();
+            let mode = Cli::from_arg_matches(&matches).unwrap().mode;
+            mode_conflict(mode, &matches, 1 + defaults.len())
+        };
+        assert_eq!(
+            conflict(&[], &["browse", "world", "--plan", "plan.jsonl"]).as_deref(),
+            Some("--plan cannot be used in browse mode, only in: check, write")
+        );
+        assert!(conflict(&[], &["browse", "world", "--output-dir", "trimmed"]).is_none());
+        assert!(conflict(&[], &["check", "world", "--list", "--self-check"]).is_none());
+        assert!(conflict(&[], &["diff", "backup", "world", "--list"]).is_none());
+        assert!(conflict(&[], &["write", "world", "--list"]).is_some());
+        assert!(conflict(&[], &["verify", "world", "--plan", "plan.jsonl"]).is_some());
+        assert!(conflict(&[], &["stats", "world", "--map", "map.png"]).is_some());
+        assert!(conflict(&["--list"], &["write", "world"]).is_none());
+        assert!(conflict(&["--list"], &["write", "world", "--list"]).is_some());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod cli;
+pub mod commands;
+pub mod config;
+pub mod logger;
+pub mod nbt;
+pub mod region_loader;
+pub mod world;
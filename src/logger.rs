@@ -0,0 +1,112 @@
+//! Minimal leveled logging to stderr.
+//! Use the `error!`, `warn!`, `info!` and `debug!` macros; messages above the configured level are not formatted.
+
+use std::fmt::Arguments;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Warn as u8);
+/// Set in `--single-thread` mode, where a crash can be traced back to the last region file printed
+static TRACE_REGIONS: AtomicBool = AtomicBool::new(false);
+
+impl Level {
+    /// Maps the number of `-v` flags to a level: warnings by default, `-v` for info and `-vv` for debug
+    pub fn from_verbosity(verbosity: u8) -> Self {
+        match verbosity {
+            0 => Level::Warn,
+            1 => Level::Info,
+            _ => Level::Debug,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+}
+
+pub fn set_max_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+pub fn set_trace_regions(enabled: bool) {
+    TRACE_REGIONS.store(enabled, Ordering::Relaxed);
+}
+
+/// Prints the region file about to be processed when region tracing is enabled, whatever the level
+pub fn trace_region(region_file_path: &Path) {
+    if TRACE_REGIONS.load(Ordering::Relaxed) {
+        eprintln!("[TRACE] Processing {}", region_file_path.display());
+    }
+}
+
+#[doc(hidden)]
+pub fn log(level: Level, args: Arguments) {
+    if enabled(level) {
+        eprintln!("[{}] {}", level.label(), args);
+    }
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::logger::log($crate::logger::Level::Error, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::logger::log($crate::logger::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::logger::log($crate::logger::Level::Info, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::logger::log($crate::logger::Level::Debug, format_args!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_verbosity() {
+        assert_eq!(Level::from_verbosity(0), Level::Warn);
+        assert_eq!(Level::from_verbosity(1), Level::Info);
+        assert_eq!(Level::from_verbosity(2), Level::Debug);
+        assert_eq!(Level::from_verbosity(10), Level::Debug);
+    }
+
+    #[test]
+    fn test_level_ordering() {
+        assert!(Level::Error < Level::Warn);
+        assert!(Level::Warn < Level::Info);
+        assert!(Level::Info < Level::Debug);
+    }
+}
@@ -1,31 +1,237 @@
-mod cli;
-mod commands;
-mod nbt;
-mod region_loader;
-mod world;
-
-use crate::cli::{Cli, Mode};
-use crate::commands::read::execute_read;
-use crate::commands::write::execute_write;
-use clap::Parser;
-use flate2::Compression;
+use minecraft_world_trimmer::cli::{Cli, Mode, OutputFormat, ReportFormat};
+#[cfg(feature = "tui")]
+use minecraft_world_trimmer::commands::browse::execute_browse;
+use minecraft_world_trimmer::commands::diff::execute_diff;
+use minecraft_world_trimmer::commands::fix_heightmaps::execute_fix_heightmaps;
+use minecraft_world_trimmer::commands::output_dir::copy_to_output_dir;
+use minecraft_world_trimmer::commands::palette::execute_palette;
+use minecraft_world_trimmer::commands::read::{CheckOutputs, execute_read};
+use minecraft_world_trimmer::commands::salvage::execute_salvage;
+use minecraft_world_trimmer::commands::snapshot::{SnapshotOptions, execute_snapshot};
+use minecraft_world_trimmer::commands::stats::execute_stats;
+use minecraft_world_trimmer::commands::top_regions::report_top_regions;
+use minecraft_world_trimmer::commands::trim_options::TrimOptions;
+use minecraft_world_trimmer::commands::verify::execute_verify;
+use minecraft_world_trimmer::commands::versions::execute_versions;
+use minecraft_world_trimmer::commands::write::execute_write;
+use minecraft_world_trimmer::config::parse_cli;
+use minecraft_world_trimmer::logger::{Level, set_max_level, set_trace_regions};
+use minecraft_world_trimmer::region_loader::chunk_loader::compression_scheme::SchemeMap;
+use minecraft_world_trimmer::world::scan_options::ScanOptions;
+use minecraft_world_trimmer::{error, warn};
 use rayon::ThreadPoolBuilder;
+use std::num::NonZeroUsize;
+use std::process::ExitCode;
 
-fn main() {
-    let cli = Cli::parse();
+/// Exit code of a check or write run with `--fail-on-change` that deleted, or would delete, anything.
+/// Clap exits with 2 on usage errors and errors exit with 1
+const EXIT_CHANGED: u8 = 3;
 
-    let cpus = std::thread::available_parallelism()
-        .map(|n| n.get())
-        .unwrap_or(1);
-    let threads = cpus.saturating_sub(2).max(1);
-    let _ = ThreadPoolBuilder::new().num_threads(threads).build_global();
+fn main() -> ExitCode {
+    let cli = parse_cli();
+    set_max_level(Level::from_verbosity(cli.verbose));
+
+    let pool = if cli.single_thread {
+        // Every parallel iterator then runs in order on the main thread, and each region is printed
+        // before being processed, so a crash points at its region file
+        set_trace_regions(true);
+        ThreadPoolBuilder::new().num_threads(1).use_current_thread()
+    } else {
+        let cpus = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        ThreadPoolBuilder::new().num_threads(cpus.saturating_sub(2).max(1))
+    };
+    // Not built as the global pool: rayon only lets that be set up once, even when it fails
+    match pool.build() {
+        Ok(pool) => pool.install(|| run(cli)),
+        Err(err) => {
+            // Rayon would otherwise build its default pool on first use, and panic when it cannot create threads
+            // either. A pool of the current thread alone creates none
+            warn!(
+                "Cannot start the worker threads ({err}), processing everything on the main thread"
+            );
+            if let Err(err) = ThreadPoolBuilder::new()
+                .num_threads(1)
+                .use_current_thread()
+                .build_global()
+            {
+                error!("Cannot set up processing on the main thread: {err}");
+                return ExitCode::FAILURE;
+            }
+            run(cli)
+        }
+    }
+}
+
+fn run(mut cli: Cli) -> ExitCode {
+    let scan_options = ScanOptions {
+        include_globs: cli.include_globs,
+        exclude_globs: cli.exclude_globs,
+        max_depth: cli.max_depth,
+        detect_worlds: cli.paths_from_level_dat,
+        modified_since: cli.modified_since,
+        skip_dimensions: cli.skip_dimensions,
+        trial_regions: cli.trial_regions.map(NonZeroUsize::get),
+    };
+
+    if let (Some(min), Some(max)) = (cli.min_sections, cli.max_sections) {
+        if min > max {
+            // Every chunk would be out of range and deleted
+            error!("--min-sections ({min}) cannot be greater than --max-sections ({max})");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let mut schemes = SchemeMap::default();
+    for &(scheme, byte) in &cli.assume_scheme {
+        schemes.assume(byte, scheme);
+    }
+
+    let options = TrimOptions {
+        compression: cli.compression_level,
+        schemes,
+        keep_empty_regions: cli.keep_empty_regions,
+        aggressive_nether: cli.aggressive_nether,
+        delete_biomes: cli.delete_biomes,
+        stale_update: cli.stale_update,
+        min_sections: cli.min_sections,
+        max_sections: cli.max_sections,
+        respect_border: cli.respect_border,
+        keep_structures: cli.keep_structures,
+        keep_block_entities: cli.keep_block_entities,
+        min_status: cli.min_status,
+        ignore_inhabited: cli.ignore_inhabited,
+        delete_below_y: cli.delete_below_y,
+        delete_malformed: cli.delete_malformed,
+        trim_poi: cli.trim_poi,
+        trim_entity_types: cli.trim_entity_types,
+        stream_writes: cli.stream_writes,
+        verify_after_write: cli.verify_after_write,
+        preserve_mtime: cli.preserve_mtime,
+        min_savings: cli.min_savings,
+        max_memory: cli.max_memory,
+        decisions_as_jsonl: cli.format == OutputFormat::Jsonl,
+        explain: cli.explain,
+        summary_csv_delimiter: (cli.report_format == ReportFormat::Csv)
+            .then_some(cli.csv_delimiter),
+        summary_file: cli.summary_file,
+        cache: cli.cache,
+    };
+
+    // Write and browse then trim the copies, fresh copies need no confirmation
+    let mut assume_yes = cli.yes;
+    if let (Mode::Write | Mode::Browse, Some(output_dir)) = (cli.mode, &cli.output_dir) {
+        match copy_to_output_dir(&cli.world_paths, output_dir) {
+            Ok(copies) => {
+                cli.world_paths = copies;
+                assume_yes = true;
+            }
+            Err(err) => {
+                error!("{err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
 
     let result = match cli.mode {
-        Mode::Write => execute_write(&cli.world_paths, Compression::new(cli.compression_level)),
-        Mode::Check => execute_read(&cli.world_paths),
+        #[cfg(feature = "tui")]
+        Mode::Browse => execute_browse(&cli.world_paths, &scan_options, &options, assume_yes),
+        #[cfg(not(feature = "tui"))]
+        Mode::Browse => Err(
+            "browse is not part of this build, install the trimmer with `--features tui`".into(),
+        ),
+        Mode::Write => execute_write(
+            &cli.world_paths,
+            &scan_options,
+            &options,
+            assume_yes,
+            cli.plan.as_deref(),
+        )
+        .map(Some),
+        Mode::Check => execute_read(
+            &cli.world_paths,
+            &scan_options,
+            &options,
+            &CheckOutputs {
+                list: cli.list,
+                csv_delimiter: cli.csv_delimiter,
+                self_check: cli.self_check,
+                map: cli.map,
+                plan: cli.plan,
+            },
+        )
+        .map(Some),
+        Mode::FixHeightmaps => execute_fix_heightmaps(
+            &cli.world_paths,
+            &scan_options,
+            options.compression,
+            &options.schemes,
+            cli.yes,
+        )
+        .map(|()| None),
+        Mode::Salvage => execute_salvage(
+            &cli.world_paths,
+            &scan_options,
+            options.compression,
+            &options.schemes,
+            cli.yes,
+        )
+        .map(|()| None),
+        Mode::Snapshot => execute_snapshot(
+            &cli.world_paths,
+            &scan_options,
+            &SnapshotOptions {
+                save: cli.save_snapshot,
+                compare: cli.compare_snapshot,
+                expect_removed: cli.expect_removed,
+                list: cli.list,
+                csv_delimiter: cli.csv_delimiter,
+            },
+        )
+        .map(|()| None),
+        Mode::Diff => execute_diff(
+            &cli.world_paths,
+            &scan_options,
+            &options.schemes,
+            cli.list,
+            cli.csv_delimiter,
+        )
+        .map(|()| None),
+        Mode::Palette => {
+            execute_palette(&cli.world_paths, &options.schemes, cli.ids_from.as_deref())
+                .map(|()| None)
+        }
+        Mode::Stats => execute_stats(&cli.world_paths, &scan_options).map(|()| None),
+        Mode::Verify => {
+            execute_verify(&cli.world_paths, &scan_options, &options.schemes).map(|()| None)
+        }
+        Mode::Versions => {
+            execute_versions(&cli.world_paths, &scan_options, &options.schemes).map(|()| None)
+        }
+    };
+    let result = match (cli.mode, cli.report_top_regions) {
+        (Mode::Check | Mode::Write | Mode::Stats, Some(count)) => result.and_then(|summary| {
+            report_top_regions(&cli.world_paths, &scan_options, count).map(|()| summary)
+        }),
+        _ => result,
     };
 
-    if let Err(err) = result {
-        eprintln!("{err}");
+    match result {
+        Err(err) => {
+            error!("{err}");
+            ExitCode::FAILURE
+        }
+        Ok(Some(summary)) if summary.io_errors > 0 => {
+            error!(
+                "{} I/O error(s), some region files were not processed",
+                summary.io_errors
+            );
+            ExitCode::FAILURE
+        }
+        Ok(Some(summary)) if cli.fail_on_change && summary.has_changes() => {
+            ExitCode::from(EXIT_CHANGED)
+        }
+        Ok(_) => ExitCode::SUCCESS,
     }
 }
@@ -1,12 +1,10 @@
-use std::string::FromUtf8Error;
+use crate::nbt::mutf8;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum ReaderError {
     #[error("Unexpected end of file")]
     UnexpectedEof,
-    #[error("Invalid UTF-8 string")]
-    InvalidUtf8(#[from] FromUtf8Error),
 }
 
 macro_rules! impl_read_number {
@@ -34,10 +32,13 @@ macro_rules! impl_read_array {
     ($fn_name:ident, $type:ty, $reader:ident) => {
         pub fn $fn_name(&mut self) -> Vec<$type> {
             let size = match self.read_i32() {
-                Ok(s) => s as usize,
+                // Negative lengths are read as empty arrays, like the game does
+                Ok(s) => s.max(0) as usize,
                 Err(_) => return Vec::new(), // Return empty array on error
             };
-            let mut values = Vec::with_capacity(size);
+            // A corrupted length must not reserve more than the remaining bytes can hold
+            let remaining = (self.raw.len() - self.index) / std::mem::size_of::<$type>();
+            let mut values = Vec::with_capacity(size.min(remaining));
 
             for _ in 0..size {
                 match self.$reader() {
@@ -71,8 +72,8 @@ impl<'a> BinaryReader<'a> {
         
         let bytes = &self.raw[self.index..end];
         self.index = end;
-        String::from_utf8(bytes.to_vec())
-            .map_err(ReaderError::InvalidUtf8)
+        // Invalid bytes only damage this string, the tree around it still parses
+        Ok(mutf8::decode(bytes))
     }
 
     pub fn read_name(&mut self) -> Option<String> {
@@ -142,6 +143,33 @@ mod tests {
         let parsed = reader.read_string().unwrap();
 
         assert_eq!(parsed, "HELLO");
+
+        // A corrupted name no longer hides the tags after it
+        let data = [0, 2, 0xFF, b'x', 0, 6, b'S', b't', b'a', b't', b'u', b's'];
+        let mut reader = BinaryReader::new(&data);
+        assert_eq!(reader.read_name().as_deref(), Some("\u{FFFD}x"));
+        assert_eq!(reader.read_name().as_deref(), Some("Status"));
+    }
+
+    #[test]
+    fn test_read_long_array() {
+        let data = [
+            0, 0, 0, 2, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0xFF, 0xFF, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xFF, 0xFE,
+        ];
+        let mut reader = BinaryReader::new(&data);
+        assert_eq!(reader.read_long_array(), vec![0x0102030405060708, -2]);
+    }
+
+    #[test]
+    fn test_corrupted_array_length() {
+        // Negative length
+        let mut reader = BinaryReader::new(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        assert!(reader.read_long_array().is_empty());
+
+        // The length claims far more values than the data holds
+        let mut reader = BinaryReader::new(&[0x7F, 0xFF, 0xFF, 0xFF, 0, 0, 0, 7]);
+        assert_eq!(reader.read_int_array(), vec![7]);
     }
 
     #[test]
@@ -0,0 +1,171 @@
+use crate::nbt::tag::Tag;
+use std::fmt::{Debug, Display, Formatter, Result, Write};
+
+/// Compact SNBT, as used by the `/data` command: `{xPos:-3,Status:"minecraft:full",sections:[...]}`
+impl Display for Tag {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write_value(f, self, None)
+    }
+}
+
+/// SNBT spread over several lines, with nested compounds and lists indented by 2 spaces
+impl Debug for Tag {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write_value(f, self, Some(0))
+    }
+}
+
+/// Writes the value of a tag, its name is written by the enclosing compound.
+/// `indent` is `None` for the compact form, or the current nesting depth for the pretty one.
+fn write_value(f: &mut Formatter, tag: &Tag, indent: Option<usize>) -> Result {
+    match tag {
+        Tag::End => Ok(()),
+        Tag::Byte { value, .. } => write!(f, "{value}b"),
+        Tag::Short { value, .. } => write!(f, "{value}s"),
+        Tag::Int { value, .. } => write!(f, "{value}"),
+        Tag::Long { value, .. } => write!(f, "{value}L"),
+        Tag::Float { value, .. } => write!(f, "{value:?}f"),
+        Tag::Double { value, .. } => write!(f, "{value:?}d"),
+        Tag::String { value, .. } => write_quoted(f, value),
+        Tag::ByteArray { value, .. } => write_array(f, "B", value.iter().map(|v| format!("{v}b"))),
+        Tag::IntArray { value, .. } => write_array(f, "I", value.iter().map(|v| v.to_string())),
+        Tag::LongArray { value, .. } => write_array(f, "L", value.iter().map(|v| format!("{v}L"))),
+        Tag::List { value, .. } => {
+            write_nested(f, ('[', ']'), value, indent, |f, element, indent| {
+                write_value(f, element, indent)
+            })
+        }
+        Tag::Compound { value, .. } => {
+            write_nested(f, ('{', '}'), value, indent, |f, child, indent| {
+                write_name(f, child.get_name().unwrap_or_default())?;
+                f.write_str(if indent.is_some() { ": " } else { ":" })?;
+                write_value(f, child, indent)
+            })
+        }
+    }
+}
+
+fn write_nested(
+    f: &mut Formatter,
+    (open, close): (char, char),
+    elements: &[Tag],
+    indent: Option<usize>,
+    write_element: impl Fn(&mut Formatter, &Tag, Option<usize>) -> Result,
+) -> Result {
+    f.write_char(open)?;
+    match indent {
+        None => {
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    f.write_char(',')?;
+                }
+                write_element(f, element, None)?;
+            }
+        }
+        Some(depth) if !elements.is_empty() => {
+            for element in elements {
+                write!(f, "\n{:width$}", "", width = (depth + 1) * 2)?;
+                write_element(f, element, Some(depth + 1))?;
+                f.write_char(',')?;
+            }
+            write!(f, "\n{:width$}", "", width = depth * 2)?;
+        }
+        Some(_) => {}
+    }
+    f.write_char(close)
+}
+
+fn write_array(f: &mut Formatter, prefix: &str, values: impl Iterator<Item = String>) -> Result {
+    write!(f, "[{prefix};")?;
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            f.write_char(',')?;
+        }
+        f.write_str(&value)?;
+    }
+    f.write_char(']')
+}
+
+/// Names made of SNBT-safe characters are written bare, the others are quoted
+fn write_name(f: &mut Formatter, name: &str) -> Result {
+    let is_bare = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+'));
+    if is_bare {
+        f.write_str(name)
+    } else {
+        write_quoted(f, name)
+    }
+}
+
+fn write_quoted(f: &mut Formatter, text: &str) -> Result {
+    f.write_char('"')?;
+    for c in text.chars() {
+        if matches!(c, '"' | '\\') {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    f.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Tag {
+        Tag::Compound {
+            name: Some(String::new()),
+            value: vec![
+                Tag::Int {
+                    name: Some("xPos".to_string()),
+                    value: -3,
+                },
+                Tag::String {
+                    name: Some("minecraft:id".to_string()),
+                    value: "say \"hi\"".to_string(),
+                },
+                Tag::List {
+                    name: Some("Pos".to_string()),
+                    value: vec![
+                        Tag::Double {
+                            name: None,
+                            value: 1.0,
+                        },
+                        Tag::Double {
+                            name: None,
+                            value: 0.5,
+                        },
+                    ],
+                    tag_type: 6,
+                },
+                Tag::LongArray {
+                    name: Some("data".to_string()),
+                    value: vec![1, -2],
+                },
+                Tag::Compound {
+                    name: Some("empty".to_string()),
+                    value: Vec::new(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_display_is_compact_snbt() {
+        assert_eq!(
+            sample().to_string(),
+            r#"{xPos:-3,"minecraft:id":"say \"hi\"",Pos:[1.0d,0.5d],data:[L;1L,-2L],empty:{}}"#
+        );
+    }
+
+    #[test]
+    fn test_debug_is_indented() {
+        assert_eq!(
+            format!("{:?}", sample()),
+            "{\n  xPos: -3,\n  \"minecraft:id\": \"say \\\"hi\\\"\",\n  Pos: [\n    1.0d,\n    0.5d,\n  ],\n  \
+             data: [L;1L,-2L],\n  empty: {},\n}"
+        );
+    }
+}
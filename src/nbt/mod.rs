@@ -1,4 +1,6 @@
 pub mod binary_reader;
+mod display;
+mod mutf8;
 pub mod parse;
 mod parsers;
 pub mod tag;
@@ -0,0 +1,111 @@
+use std::borrow::Cow;
+
+/// Decodes an NBT string. Java writes them in modified UTF-8: `NUL` as `C0 80` and characters past U+FFFF as
+/// two 3 byte surrogates, neither of which is valid UTF-8. Bytes that are invalid in both encodings, such as a
+/// corrupted tag name, are replaced by U+FFFD so the rest of the tree still parses
+pub fn decode(bytes: &[u8]) -> String {
+    if let Ok(string) = std::str::from_utf8(bytes) {
+        return string.to_string();
+    }
+
+    let mut units: Vec<u16> = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    let continuation = |index: usize| {
+        bytes
+            .get(index)
+            .filter(|&&byte| byte & 0xC0 == 0x80)
+            .map(|&byte| u16::from(byte & 0x3F))
+    };
+    while index < bytes.len() {
+        let byte = bytes[index];
+        let (unit, len) = match byte {
+            0x00..=0x7F => (Some(u16::from(byte)), 1),
+            0xC0..=0xDF => (
+                continuation(index + 1).map(|low| u16::from(byte & 0x1F) << 6 | low),
+                2,
+            ),
+            0xE0..=0xEF => match (continuation(index + 1), continuation(index + 2)) {
+                (Some(middle), Some(low)) => {
+                    (Some(u16::from(byte & 0x0F) << 12 | middle << 6 | low), 3)
+                }
+                _ => (None, 1),
+            },
+            // Standard 4 byte sequences, written by tools other than the game
+            0xF0..=0xF7 => match bytes
+                .get(index..index + 4)
+                .and_then(|sequence| std::str::from_utf8(sequence).ok())
+            {
+                Some(sequence) => {
+                    units.extend(sequence.encode_utf16());
+                    index += 4;
+                    continue;
+                }
+                None => (None, 1),
+            },
+            _ => (None, 1),
+        };
+        match unit {
+            Some(unit) => {
+                units.push(unit);
+                index += len;
+            }
+            None => {
+                units.push(char::REPLACEMENT_CHARACTER as u16);
+                index += 1;
+            }
+        }
+    }
+    // Surrogate pairs join into their character, lone surrogates become U+FFFD
+    String::from_utf16_lossy(&units)
+}
+
+/// Encodes a string the way Java writes NBT strings, see [`decode`]. Most strings are the same in both encodings
+pub fn encode(string: &str) -> Cow<'_, [u8]> {
+    if !string.chars().any(|c| c == '\0' || u32::from(c) > 0xFFFF) {
+        return Cow::Borrowed(string.as_bytes());
+    }
+
+    let mut bytes = Vec::with_capacity(string.len() + 4);
+    for c in string.chars() {
+        match c {
+            '\0' => bytes.extend([0xC0, 0x80]),
+            c if u32::from(c) > 0xFFFF => {
+                // Each surrogate is written as a 3 byte sequence
+                for &mut unit in c.encode_utf16(&mut [0; 2]) {
+                    bytes.extend([
+                        0xE0 | (unit >> 12) as u8,
+                        0x80 | (unit >> 6 & 0x3F) as u8,
+                        0x80 | (unit & 0x3F) as u8,
+                    ]);
+                }
+            }
+            c => bytes.extend(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    Cow::Owned(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modified_utf8() {
+        // What Java writes for "a\0😀é"
+        let java = [
+            b'a', 0xC0, 0x80, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80, 0xC3, 0xA9,
+        ];
+        assert_eq!(decode(&java), "a\0😀é");
+        assert_eq!(encode("a\0😀é"), &java[..]);
+        assert!(matches!(encode("minecraft:full é"), Cow::Borrowed(_)));
+
+        // Standard UTF-8 from other tools is read as is
+        assert_eq!(decode("😀".as_bytes()), "😀");
+        // Corrupted bytes, including a lone surrogate, only lose themselves
+        assert_eq!(
+            decode(&[b'S', 0xFF, b't', 0xED, 0xA0, 0xBD]),
+            "S\u{FFFD}t\u{FFFD}"
+        );
+        assert_eq!(decode(&[0xE2, 0x82]), "\u{FFFD}\u{FFFD}");
+    }
+}
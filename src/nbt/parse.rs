@@ -177,4 +177,71 @@ mod tests {
         let serialized = result.to_bytes();
         assert_eq!(serialized, data);
     }
+
+    #[test]
+    fn test_int_and_long_arrays() {
+        #[rustfmt::skip]
+        let data = [
+            10, 0, 0,
+            // Int array "i": [1, -1]
+            11, 0, 1, b'i', 0, 0, 0, 2, 0, 0, 0, 1, 0xFF, 0xFF, 0xFF, 0xFF,
+            // Long array "l": [0x0102030405060708, i64::MIN]
+            12, 0, 1, b'l', 0, 0, 0, 2, 1, 2, 3, 4, 5, 6, 7, 8, 0x80, 0, 0, 0, 0, 0, 0, 0,
+            0,
+        ];
+        let mut reader = BinaryReader::new(&data);
+        let result = parse_tag(&mut reader).unwrap();
+
+        assert_eq!(
+            result.find_tag("i").and_then(|tag| tag.get_int_array()),
+            Some(&[1, -1][..])
+        );
+        assert_eq!(
+            result.find_tag("l").and_then(|tag| tag.get_long_array()),
+            Some(&[0x0102030405060708, i64::MIN][..])
+        );
+        assert_eq!(result.to_bytes(), data);
+    }
+
+    #[test]
+    fn test_packed_block_states_round_trip() {
+        use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+        use crate::region_loader::region::Region;
+
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let region = Region::from_file_name(&path, &SchemeMap::default()).unwrap();
+        let block_states = region
+            .get_chunks()
+            .iter()
+            .filter_map(|chunk| {
+                chunk
+                    .nbt
+                    .find_tag("sections")
+                    .and_then(|tag| tag.get_list())
+            })
+            .flatten()
+            .filter_map(|section| section.find_tag("block_states"))
+            .find(|block_states| block_states.find_tag("data").is_some())
+            .expect("The sample holds sections with packed block states");
+
+        let serialized = block_states.to_bytes();
+        let parsed = parse_tag(&mut BinaryReader::new(&serialized)).unwrap();
+        assert_eq!(&parsed, block_states);
+        assert_eq!(parsed.to_bytes(), serialized);
+
+        // The first long of `data` follows the name, the element count and the array length
+        let data = parsed
+            .find_tag("data")
+            .and_then(|tag| tag.get_long_array())
+            .unwrap();
+        let data_start = serialized
+            .windows(6)
+            .position(|window| window == [12, 0, 4, b'd', b'a', b't'])
+            .unwrap()
+            + 11;
+        assert_eq!(
+            &serialized[data_start..data_start + 8],
+            &data[0].to_be_bytes()
+        );
+    }
 }
@@ -1,8 +1,29 @@
 use crate::nbt::writers::{
     size_to_i32_bytes, write_array_i32, write_array_i64, write_array_i8, write_string,
 };
+use thiserror::Error;
 
-#[derive(PartialEq, Debug, Clone)]
+/// Why an edit of a compound tag failed, see [`Tag::insert`], [`Tag::remove`] and [`Tag::replace`]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TagEditError {
+    /// Entries can only be edited in compounds, the path leads to the tag that is not one (empty for the edited tag)
+    #[error("{0:?} is not a compound tag")]
+    NotACompound(String),
+    #[error("no tag at {0:?}")]
+    NotFound(String),
+    #[error("cannot replace the {expected} tag at {path:?} with a {found} tag")]
+    TypeMismatch {
+        path: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// `TAG_End` only closes compounds, it cannot be an entry
+    #[error("TAG_End cannot be stored in a compound")]
+    EndTag,
+}
+
+/// `Display` and `Debug` render SNBT, see `nbt::display`
+#[derive(PartialEq, Clone)]
 pub enum Tag {
     End,
     Byte {
@@ -68,6 +89,20 @@ impl Tag {
         }
     }
 
+    pub fn get_double(&self) -> Option<&f64> {
+        match self {
+            Tag::Double { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_byte(&self) -> Option<&i8> {
+        match self {
+            Tag::Byte { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
     pub fn get_int(&self) -> Option<&i32> {
         match self {
             Tag::Int { value, .. } => Some(value),
@@ -82,6 +117,36 @@ impl Tag {
         }
     }
 
+    /// Elements of a list tag
+    pub fn get_list(&self) -> Option<&[Tag]> {
+        match self {
+            Tag::List { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Entries of a compound tag
+    pub fn get_compound(&self) -> Option<&[Tag]> {
+        match self {
+            Tag::Compound { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_int_array(&self) -> Option<&[i32]> {
+        match self {
+            Tag::IntArray { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_long_array(&self) -> Option<&[i64]> {
+        match self {
+            Tag::LongArray { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
     pub fn find_tag(&self, name: &str) -> Option<&Tag> {
         match self {
             Self::Compound { value, .. } => {
@@ -91,6 +156,80 @@ impl Tag {
         }
     }
 
+    pub fn find_tag_mut(&mut self, name: &str) -> Option<&mut Tag> {
+        match self {
+            Self::Compound { value, .. } => value.iter_mut().find(|v| v.get_name() == Some(name)),
+            _ => None,
+        }
+    }
+
+    /// Adds `value` to a compound under `name`, in place of the entry already named so if any.
+    /// Returns the entry it replaced
+    pub fn insert(&mut self, name: &str, mut value: Tag) -> Result<Option<Tag>, TagEditError> {
+        let Self::Compound { value: entries, .. } = self else {
+            return Err(TagEditError::NotACompound(String::new()));
+        };
+        let Some(entry_name) = value.name_mut() else {
+            return Err(TagEditError::EndTag);
+        };
+        *entry_name = Some(name.to_string());
+        match entries
+            .iter_mut()
+            .find(|entry| entry.get_name() == Some(name))
+        {
+            Some(entry) => Ok(Some(std::mem::replace(entry, value))),
+            None => {
+                entries.push(value);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Removes the entry named `name` from a compound, keeping the order of the others. Returns the entry removed
+    pub fn remove(&mut self, name: &str) -> Result<Option<Tag>, TagEditError> {
+        let Self::Compound { value: entries, .. } = self else {
+            return Err(TagEditError::NotACompound(String::new()));
+        };
+        Ok(entries
+            .iter()
+            .position(|entry| entry.get_name() == Some(name))
+            .map(|index| entries.remove(index)))
+    }
+
+    /// Replaces the tag at `path`, the names of the nested compounds leading to it, with `value` of the same type.
+    /// Returns the tag it replaced
+    pub fn replace(&mut self, path: &[&str], mut value: Tag) -> Result<Tag, TagEditError> {
+        let Some((name, parents)) = path.split_last() else {
+            return Err(TagEditError::NotFound(String::new()));
+        };
+        let mut compound = self;
+        for (depth, parent) in parents.iter().enumerate() {
+            if !matches!(compound, Self::Compound { .. }) {
+                return Err(TagEditError::NotACompound(path[..depth].join(".")));
+            }
+            compound = compound
+                .find_tag_mut(parent)
+                .ok_or_else(|| TagEditError::NotFound(path[..=depth].join(".")))?;
+        }
+        if !matches!(compound, Self::Compound { .. }) {
+            return Err(TagEditError::NotACompound(parents.join(".")));
+        }
+        let entry = compound
+            .find_tag_mut(name)
+            .ok_or_else(|| TagEditError::NotFound(path.join(".")))?;
+        if entry.get_tag_type() != value.get_tag_type() {
+            return Err(TagEditError::TypeMismatch {
+                path: path.join("."),
+                expected: entry.type_name(),
+                found: value.type_name(),
+            });
+        }
+        if let Some(entry_name) = value.name_mut() {
+            *entry_name = Some(name.to_string());
+        }
+        Ok(std::mem::replace(entry, value))
+    }
+
     fn get_tag_type(&self) -> u8 {
         match self {
             Tag::End => 0,
@@ -109,7 +248,44 @@ impl Tag {
         }
     }
 
-    fn get_name(&self) -> Option<&str> {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Tag::End => "TAG_End",
+            Tag::Byte { .. } => "TAG_Byte",
+            Tag::Short { .. } => "TAG_Short",
+            Tag::Int { .. } => "TAG_Int",
+            Tag::Long { .. } => "TAG_Long",
+            Tag::Float { .. } => "TAG_Float",
+            Tag::Double { .. } => "TAG_Double",
+            Tag::ByteArray { .. } => "TAG_Byte_Array",
+            Tag::String { .. } => "TAG_String",
+            Tag::List { .. } => "TAG_List",
+            Tag::Compound { .. } => "TAG_Compound",
+            Tag::IntArray { .. } => "TAG_Int_Array",
+            Tag::LongArray { .. } => "TAG_Long_Array",
+        }
+    }
+
+    /// Name of the tag, `None` for `TAG_End` which has none
+    fn name_mut(&mut self) -> Option<&mut Option<String>> {
+        match self {
+            Tag::End => None,
+            Tag::Byte { name, .. }
+            | Tag::Short { name, .. }
+            | Tag::Int { name, .. }
+            | Tag::Long { name, .. }
+            | Tag::Float { name, .. }
+            | Tag::Double { name, .. }
+            | Tag::ByteArray { name, .. }
+            | Tag::String { name, .. }
+            | Tag::List { name, .. }
+            | Tag::Compound { name, .. }
+            | Tag::IntArray { name, .. }
+            | Tag::LongArray { name, .. } => Some(name),
+        }
+    }
+
+    pub(crate) fn get_name(&self) -> Option<&str> {
         match self {
             Tag::End => None,
             Tag::Byte { name, .. }
@@ -202,3 +378,82 @@ impl Tag {
         base
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_loader::test_region::{compound, int};
+
+    #[test]
+    fn test_edit_compound() {
+        let mut chunk = compound(
+            "",
+            vec![
+                int("xPos", 1),
+                compound("Level", vec![int("zPos", 2)]),
+                int("yPos", -4),
+            ],
+        );
+
+        assert_eq!(
+            chunk.insert("xPos", int("ignored", 5)),
+            Ok(Some(int("xPos", 1)))
+        );
+        assert_eq!(chunk.insert("DataVersion", int("", 3955)), Ok(None));
+        assert_eq!(chunk.find_tag("xPos"), Some(&int("xPos", 5)));
+        assert_eq!(
+            chunk.get_compound().unwrap().last(),
+            Some(&int("DataVersion", 3955))
+        );
+
+        assert_eq!(chunk.remove("xPos"), Ok(Some(int("xPos", 5))));
+        assert_eq!(chunk.remove("xPos"), Ok(None));
+        let names: Vec<_> = chunk
+            .get_compound()
+            .unwrap()
+            .iter()
+            .map(Tag::get_name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![Some("Level"), Some("yPos"), Some("DataVersion")]
+        );
+
+        assert_eq!(
+            chunk.replace(&["Level", "zPos"], int("", 7)),
+            Ok(int("zPos", 2))
+        );
+        assert_eq!(
+            chunk
+                .find_tag("Level")
+                .and_then(|level| level.find_tag("zPos")),
+            Some(&int("zPos", 7))
+        );
+
+        let long = Tag::Long {
+            name: None,
+            value: 7,
+        };
+        assert_eq!(
+            chunk.replace(&["Level", "zPos"], long),
+            Err(TagEditError::TypeMismatch {
+                path: "Level.zPos".to_string(),
+                expected: "TAG_Int",
+                found: "TAG_Long",
+            })
+        );
+        assert_eq!(
+            chunk.replace(&["Level", "xPos"], int("", 0)),
+            Err(TagEditError::NotFound("Level.xPos".into()))
+        );
+        assert_eq!(
+            chunk.replace(&["yPos", "y"], int("", 0)),
+            Err(TagEditError::NotACompound("yPos".into()))
+        );
+        assert_eq!(
+            int("y", 0).remove("y"),
+            Err(TagEditError::NotACompound(String::new()))
+        );
+        assert_eq!(chunk.insert("end", Tag::End), Err(TagEditError::EndTag));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::nbt::mutf8;
+
 fn size_to_u16_bytes(size: usize) -> [u8; 2] {
     (size as u16).to_be_bytes()
 }
@@ -13,7 +15,7 @@ fn create_initial_buffer<T>(size: usize) -> Vec<u8> {
 }
 
 pub fn write_string(input: &str) -> Vec<u8> {
-    let input_bytes = input.as_bytes();
+    let input_bytes = &mutf8::encode(input)[..];
     let mut buffer = Vec::with_capacity(input_bytes.len() + 2);
     buffer.extend_from_slice(&size_to_u16_bytes(input_bytes.len()));
     buffer.extend_from_slice(input_bytes);
@@ -0,0 +1,231 @@
+use crate::nbt::tag::Tag;
+
+/// Number of blocks in a chunk section (16x16x16)
+const SECTION_VOLUME: usize = 4096;
+/// Number of biome cells in a chunk section, biomes are stored per 4x4x4 blocks
+const SECTION_BIOME_VOLUME: usize = 64;
+
+/// Counts how many blocks of the section use each entry of its `block_states` palette.
+///
+/// Block states are stored as a palette of block compounds and a packed `data` long array
+/// of palette indices, each index using at least 4 bits and never spanning two longs (1.16+).
+/// When the palette has a single entry, `data` is omitted and the whole section is that block.
+pub fn count_palette_entries(block_states: &Tag) -> Vec<(&str, usize)> {
+    let palette: Vec<&str> = block_states
+        .find_tag("palette")
+        .and_then(|tag| tag.get_list())
+        .unwrap_or_default()
+        .iter()
+        .map(|entry| {
+            entry
+                .find_tag("Name")
+                .and_then(|tag| tag.get_string())
+                .map(|name| name.as_str())
+                .unwrap_or_default()
+        })
+        .collect();
+
+    count_packed_entries(palette, block_states, SECTION_VOLUME, 4)
+}
+
+/// Counts how many biome cells of the section use each entry of its `biomes` palette (1.18+).
+///
+/// Same layout as block states, except that the palette holds plain biome ids
+/// and indices use as few bits as the palette needs.
+pub fn count_biome_entries(biomes: &Tag) -> Vec<(&str, usize)> {
+    let palette: Vec<&str> = biomes
+        .find_tag("palette")
+        .and_then(|tag| tag.get_list())
+        .unwrap_or_default()
+        .iter()
+        .map(|entry| entry.get_string().map(|id| id.as_str()).unwrap_or_default())
+        .collect();
+
+    count_packed_entries(palette, biomes, SECTION_BIOME_VOLUME, 1)
+}
+
+fn count_packed_entries<'a>(
+    palette: Vec<&'a str>,
+    container: &Tag,
+    volume: usize,
+    min_bits: usize,
+) -> Vec<(&'a str, usize)> {
+    let mut counts = vec![0_usize; palette.len()];
+    match container
+        .find_tag("data")
+        .and_then(|tag| tag.get_long_array())
+    {
+        _ if palette.len() == 1 => counts[0] = volume,
+        Some(data) if !palette.is_empty() => {
+            let bits = bits_per_entry(palette.len()).max(min_bits);
+            for index in unpack_entries(data, volume, bits) {
+                // Out of range indices only appear in corrupted sections, they are not counted
+                if let Some(count) = counts.get_mut(index as usize) {
+                    *count += 1;
+                }
+            }
+        }
+        _ => {}
+    }
+
+    palette.into_iter().zip(counts).collect()
+}
+
+/// Palette indices of the whole section, in `(y * 16 + z) * 16 + x` order.
+/// A single entry palette has no `data`, every block is then index 0
+pub(crate) fn block_state_indices(block_states: &Tag, palette_len: usize) -> Vec<u64> {
+    match block_states
+        .find_tag("data")
+        .and_then(|tag| tag.get_long_array())
+    {
+        Some(data) if palette_len > 1 => {
+            unpack_entries(data, SECTION_VOLUME, bits_per_entry(palette_len).max(4))
+        }
+        _ => vec![0; SECTION_VOLUME],
+    }
+}
+
+/// Reads `count` values of `bits` bits packed in longs, values never span two longs.
+/// Stops early if `data` is too short
+pub(crate) fn unpack_entries(data: &[i64], count: usize, bits: usize) -> Vec<u64> {
+    let entries_per_long = 64 / bits;
+    let mask = (1_u64 << bits) - 1;
+    (0..count)
+        .map_while(|i| {
+            data.get(i / entries_per_long)
+                .map(|&long| ((long as u64) >> ((i % entries_per_long) * bits)) & mask)
+        })
+        .collect()
+}
+
+/// Packs values of `bits` bits in longs the way [`unpack_entries`] reads them
+pub(crate) fn pack_entries(values: &[u64], bits: usize) -> Vec<i64> {
+    let entries_per_long = 64 / bits;
+    let mut data = vec![0_i64; values.len().div_ceil(entries_per_long)];
+    for (i, value) in values.iter().enumerate() {
+        data[i / entries_per_long] |= (value << ((i % entries_per_long) * bits)) as i64;
+    }
+    data
+}
+
+/// Smallest number of bits able to hold every index of the palette
+pub(crate) fn bits_per_entry(palette_len: usize) -> usize {
+    (usize::BITS - (palette_len - 1).leading_zeros()) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_states(names: &[&str], data: Option<Vec<i64>>) -> Tag {
+        let palette = names
+            .iter()
+            .map(|name| Tag::Compound {
+                name: None,
+                value: vec![Tag::String {
+                    name: Some("Name".to_string()),
+                    value: name.to_string(),
+                }],
+            })
+            .collect();
+        let mut value = vec![Tag::List {
+            name: Some("palette".to_string()),
+            value: palette,
+            tag_type: 10,
+        }];
+        if let Some(data) = data {
+            value.push(Tag::LongArray {
+                name: Some("data".to_string()),
+                value: data,
+            });
+        }
+        Tag::Compound {
+            name: Some("block_states".to_string()),
+            value,
+        }
+    }
+
+    #[test]
+    fn test_bits_per_entry() {
+        assert_eq!(bits_per_entry(2), 1);
+        assert_eq!(bits_per_entry(3), 2);
+        assert_eq!(bits_per_entry(16), 4);
+        assert_eq!(bits_per_entry(17), 5);
+        assert_eq!(bits_per_entry(64), 6);
+        assert_eq!(bits_per_entry(65), 7);
+    }
+
+    #[test]
+    fn test_pack_unpack_roundtrip() {
+        let values: Vec<u64> = (0..256).map(|i| (i * 7) % 385).collect();
+        let data = pack_entries(&values, 9);
+        // 7 values of 9 bits per long
+        assert_eq!(data.len(), 37);
+        assert_eq!(unpack_entries(&data, 256, 9), values);
+    }
+
+    #[test]
+    fn test_single_entry_palette() {
+        let tag = block_states(&["minecraft:air"], None);
+        assert_eq!(count_palette_entries(&tag), vec![("minecraft:air", 4096)]);
+    }
+
+    #[test]
+    fn test_packed_data() {
+        // 4 bits per entry, 16 entries per long: the first long holds one stone block (index 1) at position 0
+        let mut data = vec![0_i64; 256];
+        data[0] = 1;
+        let tag = block_states(&["minecraft:air", "minecraft:stone"], Some(data));
+        assert_eq!(
+            count_palette_entries(&tag),
+            vec![("minecraft:air", 4095), ("minecraft:stone", 1)]
+        );
+    }
+
+    #[test]
+    fn test_high_bits_of_long() {
+        // 5 bits per entry leaves 12 entries per long, the 4 remaining bits of each long are unused
+        let names: Vec<String> = (0..17).map(|i| format!("minecraft:block_{i}")).collect();
+        let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+        let mut data = vec![0_i64; 4096_usize.div_ceil(12)];
+        data[0] = 16 << (5 * 11);
+        let tag = block_states(&names, Some(data));
+        let counts = count_palette_entries(&tag);
+        assert_eq!(counts[0], ("minecraft:block_0", 4095));
+        assert_eq!(counts[16], ("minecraft:block_16", 1));
+    }
+
+    #[test]
+    fn test_biomes() {
+        // 2 bits per entry, 32 entries per long, 64 cells fit in 2 longs
+        let palette = ["minecraft:plains", "minecraft:river", "custom:removed"]
+            .iter()
+            .map(|id| Tag::String {
+                name: None,
+                value: id.to_string(),
+            })
+            .collect();
+        let biomes = Tag::Compound {
+            name: Some("biomes".to_string()),
+            value: vec![
+                Tag::List {
+                    name: Some("palette".to_string()),
+                    value: palette,
+                    tag_type: 8,
+                },
+                Tag::LongArray {
+                    name: Some("data".to_string()),
+                    value: vec![0b10_01, 0b10 << 62],
+                },
+            ],
+        };
+        assert_eq!(
+            count_biome_entries(&biomes),
+            vec![
+                ("minecraft:plains", 61),
+                ("minecraft:river", 1),
+                ("custom:removed", 2)
+            ]
+        );
+    }
+}
@@ -1,27 +1,50 @@
 use crate::nbt::binary_reader::BinaryReader;
 use crate::nbt::parse::parse_tag;
 use crate::nbt::tag::Tag;
-use crate::region_loader::chunk_loader::compression_scheme::CompressionScheme;
+use crate::region_loader::chunk_loader::block_states::{
+    count_biome_entries, count_palette_entries,
+};
+use crate::region_loader::chunk_loader::chunk_status::ChunkStatus;
+use crate::region_loader::chunk_loader::compression_scheme::{CompressionScheme, SchemeMap};
+use crate::region_loader::chunk_loader::entities;
+use crate::region_loader::chunk_loader::heightmaps;
+use crate::region_loader::chunk_loader::poi;
+use crate::region_loader::chunk_loader::sections;
 use crate::region_loader::get_u32::get_u32;
 use crate::region_loader::location::Location;
-use flate2::read::{GzDecoder, GzEncoder, ZlibDecoder, ZlibEncoder};
 use flate2::Compression;
+use flate2::read::{GzDecoder, GzEncoder, ZlibDecoder, ZlibEncoder};
 use lz4_flex::frame::FrameDecoder;
+use std::collections::BTreeMap;
 use std::io::Read;
+use std::path::Path;
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Chunk {
     pub nbt: Tag,
     pub location: Location,
+    // Read once at load time, looking it up walks the root compound of the NBT
+    position: Result<(i32, i32), &'static str>,
     // Original compressed payload and its scheme, used when recompression fails
     original_compression_scheme: CompressionScheme,
     original_payload: Vec<u8>,
+    // The payload comes from a `.mcc` file, the region only holds the scheme byte with the external flag
+    is_external: bool,
 }
 
 impl Chunk {
     const STATUS_FULL: &'static str = "minecraft:full";
+    // Before 1.18 the status was not namespaced
+    const LEGACY_STATUS_FULL: &'static str = "full";
 
-    pub fn from_location(buf: &[u8], location: Location) -> Result<Self, &'static str> {
+    /// Parses the chunk stored at `location`, decoding nonstandard scheme bytes with `schemes`.
+    /// Oversized chunks only leave their scheme byte in the region, their payload is read from `external_file` (`.mcc`).
+    pub fn from_location(
+        buf: &[u8],
+        location: Location,
+        external_file: Option<&Path>,
+        schemes: &SchemeMap,
+    ) -> Result<Self, &'static str> {
         // Chunk header parsing z ochroną zakresów
         let offset = location.get_offset() as usize;
 
@@ -29,24 +52,33 @@ impl Chunk {
         if offset + 4 > buf.len() {
             return Err("Chunk header out of bounds");
         }
+        // The length is in bytes, not sectors, and counts the scheme byte along with the payload
         let chunk_size = get_u32(buf, offset) as usize;
         if chunk_size == 0 {
             return Err("Invalid chunk size (zero)");
         }
+        // The declared size must fit in the sectors the location table reserves for this chunk,
+        // otherwise slicing would read into the data of the following chunk (interrupted saves)
+        let allocated_size = location.get_sector_count() as usize * 4096;
+        if chunk_size + 4 > allocated_size {
+            return Err("Chunk size exceeds its allocated sectors");
+        }
 
         // Bajt schematu kompresji
         let compression_scheme_index = offset + 4;
         if compression_scheme_index >= buf.len() {
             return Err("Compression scheme out of bounds");
         }
-        let compression_scheme = CompressionScheme::from_u8(buf[compression_scheme_index])?;
+        let scheme_byte = buf[compression_scheme_index];
+        let compression_scheme = schemes.scheme(scheme_byte)?;
+        let is_external = CompressionScheme::is_external(scheme_byte);
 
-        // Dane chunka: payload ma długość (chunk_size - 1)
-        let header_size = 5; // 4 bajty rozmiaru + 1 bajt schematu
+        // The payload follows the 4 length bytes and the scheme byte, it is `chunk_size - 1` bytes long
+        // (`chunk_size > 0` is verified above), so a length of 1 is a chunk without any payload
+        let header_size = 5;
         let start = offset
             .checked_add(header_size)
             .ok_or("Chunk start offset overflow")?;
-        // payload_len = chunk_size - 1 (chunk_size > 0 already verified)
         let payload_len = chunk_size - 1;
         let end = start
             .checked_add(payload_len)
@@ -54,8 +86,14 @@ impl Chunk {
         if end > buf.len() {
             return Err("Chunk payload out of bounds");
         }
-        let raw_first_chunk = &buf[start..end];
-        let original_payload = raw_first_chunk.to_vec();
+        let original_payload = if is_external {
+            let external_file = external_file.ok_or("External chunk without its .mcc file")?;
+            std::fs::read(external_file)
+                .map_err(|_| "Cannot read the .mcc file of an external chunk")?
+        } else {
+            buf[start..end].to_vec()
+        };
+        let raw_first_chunk = &original_payload[..];
 
         // Depending on the compression scheme, read the data
         let decoded_bytes = match compression_scheme {
@@ -69,6 +107,7 @@ impl Chunk {
                 let mut bytes = Vec::new();
                 decoder.read_to_end(&mut bytes).map(|_| bytes)
             }
+            CompressionScheme::Uncompressed => Ok(raw_first_chunk.to_vec()),
             CompressionScheme::Lz4 => {
                 // Najpierw próbujemy dekodera "frame"
                 let mut decoder = FrameDecoder::new(raw_first_chunk);
@@ -96,15 +135,43 @@ impl Chunk {
             })
             .map_err(|_| "Error while parsing NBT")?;
 
-        Ok(Self {
+        let mut chunk = Self::new(nbt, location, compression_scheme, original_payload);
+        chunk.is_external = is_external;
+        Ok(chunk)
+    }
+
+    fn new(
+        nbt: Tag,
+        location: Location,
+        original_compression_scheme: CompressionScheme,
+        original_payload: Vec<u8>,
+    ) -> Self {
+        let mut chunk = Self {
             nbt,
             location,
-            original_compression_scheme: compression_scheme,
+            position: Err("No position for this chunk"),
+            original_compression_scheme,
             original_payload,
-        })
+            is_external: false,
+        };
+        chunk.position = chunk.read_position();
+        chunk
+    }
+
+    /// Wraps NBT built in memory in a chunk, as if it was read from an uncompressed region
+    #[cfg(test)]
+    pub(crate) fn from_nbt(nbt: Tag) -> Self {
+        let payload = nbt.to_bytes();
+        let sectors = (payload.len() + 5).div_ceil(4096) as u32;
+        let location = Location::new(8192, sectors * 4096, 0).expect("Test chunks fit in a region");
+        Self::new(nbt, location, CompressionScheme::Uncompressed, payload)
     }
 
     pub fn to_bytes(&self, compression: Compression) -> Result<Vec<u8>, &'static str> {
+        // Trimming never edits chunks, so the `.mcc` file stays valid and only the stub goes back in the region
+        if self.is_external {
+            return Ok(self.to_original_bytes());
+        }
         let decoded_bytes = self.nbt.to_bytes();
         // Try Zlib first; if it fails, fall back to Gzip. If both fail,
         // do not write mismatched header/payload — propagate error to leave chunk unchanged.
@@ -124,44 +191,312 @@ impl Chunk {
     }
 
     pub fn get_position(&self) -> Result<(i32, i32), &'static str> {
-        let x_pos_tag = self.nbt.find_tag("xPos").and_then(|v| v.get_int());
-        let z_pos_tag = self.nbt.find_tag("zPos").and_then(|v| v.get_int());
+        self.position
+    }
+
+    /// Places a chunk that does not store its coordinates, such as a POI chunk, at the given position
+    pub(crate) fn set_position(&mut self, position: (i32, i32)) {
+        self.position = Ok(position);
+    }
+
+    fn read_position(&self) -> Result<(i32, i32), &'static str> {
+        let x_pos_tag = self.find_chunk_tag("xPos").and_then(|v| v.get_int());
+        let z_pos_tag = self.find_chunk_tag("zPos").and_then(|v| v.get_int());
 
         match (x_pos_tag, z_pos_tag) {
             (Some(x), Some(z)) => Ok((*x, *z)),
-            _ => Err("No position for this chunk"),
+            // Entity chunks (in `entities/`) store their coordinates as a `Position` [x, z] int array
+            _ => match self
+                .nbt
+                .find_tag("Position")
+                .and_then(|v| v.get_int_array())
+            {
+                Some(&[x, z]) => Ok((x, z)),
+                _ => Err("No position for this chunk"),
+            },
         }
     }
 
+    /// Finds a chunk property, either at the root (1.18+) or in the `Level` compound older versions wrapped
+    /// everything in
+    fn find_chunk_tag(&self, name: &str) -> Option<&Tag> {
+        self.nbt.find_tag(name).or_else(|| {
+            self.nbt
+                .find_tag("Level")
+                .and_then(|level| level.find_tag(name))
+        })
+    }
+
     /// Checks if a chunk is not fully generated and has never been inhabited
     pub fn should_delete(&self) -> bool {
-        !self.is_fully_generated() && !self.has_been_inhabited()
+        self.keep_reason().is_none()
     }
 
-    fn is_fully_generated(&self) -> bool {
-        self.nbt
-            .find_tag("Status")
+    /// Tells why the chunk is kept, or `None` if it can be deleted
+    pub fn keep_reason(&self) -> Option<&'static str> {
+        if self.is_fully_generated() {
+            Some("fully generated")
+        } else if self.has_been_inhabited() {
+            Some("inhabited")
+        } else {
+            None
+        }
+    }
+
+    pub fn is_fully_generated(&self) -> bool {
+        match self
+            .find_chunk_tag("Status")
             .and_then(|tag| tag.get_string())
-            .map(|status| status == Chunk::STATUS_FULL)
-            .unwrap_or(false) // if the tag is not present, we can assume that the chunk is not fully generated
+        {
+            Some(status) => status == Chunk::STATUS_FULL || status == Chunk::LEGACY_STATUS_FULL,
+            // Chunks saved before 1.13 only have byte flags, set once their features were placed or their light
+            // computed. Without either flag, we can assume that the chunk is not fully generated
+            None => self.is_flag_set("TerrainPopulated") || self.is_flag_set("LightPopulated"),
+        }
+    }
+
+    fn is_flag_set(&self, name: &str) -> bool {
+        self.find_chunk_tag(name)
+            .and_then(|tag| tag.get_byte())
+            .is_some_and(|&flag| flag != 0)
+    }
+
+    /// Generation stage the chunk reached, see [`ChunkStatus`]. `None` if the status is missing or unknown.
+    /// Chunks saved before 1.13 have no status, only `TerrainPopulated` and `LightPopulated` flags,
+    /// which are read as the `features` and `light` stages
+    pub fn status(&self) -> Option<ChunkStatus> {
+        if let Some(status) = self
+            .find_chunk_tag("Status")
+            .and_then(|tag| tag.get_string())
+        {
+            return ChunkStatus::from_name(status);
+        }
+        if self.is_flag_set("LightPopulated") {
+            Some(ChunkStatus::Light)
+        } else if self.is_flag_set("TerrainPopulated") {
+            Some(ChunkStatus::Features)
+        } else {
+            None
+        }
+    }
+
+    /// Tells if the chunk holds the start of a structure or is covered by one (village, stronghold, monument...).
+    /// Deleting it would leave the structure partly generated
+    pub fn has_structures(&self) -> bool {
+        // 1.18 renamed `Structures` and `Starts` to lower case
+        let Some(structures) = self
+            .find_chunk_tag("structures")
+            .or_else(|| self.find_chunk_tag("Structures"))
+        else {
+            return false;
+        };
+
+        let is_referenced = structures
+            .find_tag("References")
+            .and_then(|tag| tag.get_compound())
+            .unwrap_or_default()
+            .iter()
+            .any(|reference| {
+                reference
+                    .get_long_array()
+                    .is_some_and(|chunks| !chunks.is_empty())
+            });
+        // Chunks without a structure start store an `INVALID` id
+        let has_start = structures
+            .find_tag("starts")
+            .or_else(|| structures.find_tag("Starts"))
+            .and_then(|tag| tag.get_compound())
+            .unwrap_or_default()
+            .iter()
+            .any(|start| {
+                start
+                    .find_tag("id")
+                    .and_then(|tag| tag.get_string())
+                    .is_some_and(|id| id != "INVALID")
+            });
+
+        is_referenced || has_start
     }
 
-    fn has_been_inhabited(&self) -> bool {
+    /// Tells if the chunk holds at least one block entity (chest, furnace, sign...), a hint that a player built
+    /// something there. Generated structures also place some, such as the chests and spawners of dungeons
+    pub fn has_block_entities(&self) -> bool {
+        // Before 1.18 they are in `Level.TileEntities`
+        self.find_chunk_tag("block_entities")
+            .or_else(|| self.find_chunk_tag("TileEntities"))
+            .and_then(|tag| tag.get_list())
+            .is_some_and(|block_entities| !block_entities.is_empty())
+    }
+
+    /// Rebuilds the `WORLD_SURFACE` and `MOTION_BLOCKING` heightmaps of a fully generated 1.18+ chunk from its blocks,
+    /// for chunks whose blocks were edited. Returns `true` if they changed.
+    /// External chunks are left alone, their `.mcc` file is never rewritten
+    pub fn recompute_heightmaps(&mut self) -> Result<bool, &'static str> {
+        if !self.is_fully_generated() || self.is_external {
+            return Ok(false);
+        }
+        heightmaps::recompute_heightmaps(&mut self.nbt)
+    }
+
+    /// Removes the point of interest records of a POI chunk that lie outside of it, see
+    /// [`poi::prune_foreign_records`]. Returns how many were removed
+    pub fn prune_poi_records(&mut self) -> usize {
+        match self.position {
+            Ok(position) if !self.is_external => {
+                poi::prune_foreign_records(&mut self.nbt, position)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Removes the entities of these types from an entity chunk, see [`entities::remove_entity_types`].
+    /// Returns how many were removed
+    pub fn remove_entity_types(&mut self, ids: &[String]) -> usize {
+        if self.is_external {
+            return 0;
+        }
+        entities::remove_entity_types(&mut self.nbt, ids)
+    }
+
+    /// Counts the sections lying entirely below `y`, see [`Chunk::remove_sections_below`]
+    pub fn sections_below(&self, y: i32) -> usize {
+        if self.is_external {
+            return 0;
+        }
+        sections::count_sections_below(&self.nbt, y)
+    }
+
+    /// Removes the sections of a 1.18+ chunk lying entirely below `y`, see [`sections::remove_sections_below`], and
+    /// rebuilds the heightmaps of the chunk when it is fully generated. Returns how many sections were removed
+    pub fn remove_sections_below(&mut self, y: i32) -> usize {
+        if self.is_external {
+            return 0;
+        }
+        let removed = sections::remove_sections_below(&mut self.nbt, y);
+        // Without block states left there is nothing to build heightmaps from. The game rebuilds the missing
+        // heightmaps of a chunk when loading it, stale ones would stay
+        if removed > 0 && self.recompute_heightmaps().is_err() {
+            // Chunks are compounds, there is nothing to remove otherwise
+            let _ = self.nbt.remove("Heightmaps");
+        }
+        removed
+    }
+
+    /// Counts the sections holding any block other than air, from their palettes only.
+    /// A cheap measure of the vertical extent of what the chunk contains
+    pub fn section_count(&self) -> usize {
+        // Before 1.18 sections are in `Level.Sections` and store their palette directly
+        self.find_chunk_tag("sections")
+            .or_else(|| self.find_chunk_tag("Sections"))
+            .and_then(|tag| tag.get_list())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|section| {
+                section
+                    .find_tag("block_states")
+                    .and_then(|block_states| block_states.find_tag("palette"))
+                    .or_else(|| section.find_tag("Palette"))
+                    .and_then(|tag| tag.get_list())
+            })
+            .filter(|palette| {
+                palette.iter().any(|entry| {
+                    !matches!(
+                        entry
+                            .find_tag("Name")
+                            .and_then(|tag| tag.get_string())
+                            .map(|name| name.as_str()),
+                        None | Some("minecraft:air" | "minecraft:cave_air" | "minecraft:void_air")
+                    )
+                })
+            })
+            .count()
+    }
+
+    /// Number of blocks of each id (e.g. `minecraft:stone`) in all the sections of a 1.18+ chunk, whatever their
+    /// block state properties. Ids listed in a palette but used by no block are left out
+    pub fn blocks(&self) -> BTreeMap<&str, usize> {
+        sum_counts(
+            self.section_tags("block_states")
+                .flat_map(count_palette_entries),
+        )
+    }
+
+    /// Number of 4x4x4 biome cells of each biome id (e.g. `minecraft:plains`) in all the sections of a 1.18+ chunk
+    pub fn biomes(&self) -> BTreeMap<&str, usize> {
+        sum_counts(self.section_tags("biomes").flat_map(count_biome_entries))
+    }
+
+    /// Counts the blocks with the given id (e.g. `minecraft:nether_portal`) in all the sections of the chunk
+    pub fn count_block(&self, block_id: &str) -> usize {
+        self.section_tags("block_states")
+            .flat_map(count_palette_entries)
+            .filter(|(id, _)| *id == block_id)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Counts the 4x4x4 biome cells with the given id (e.g. `minecraft:plains`) in all the sections of the chunk
+    pub fn count_biome(&self, biome_id: &str) -> usize {
+        self.section_tags("biomes")
+            .flat_map(count_biome_entries)
+            .filter(|(id, _)| *id == biome_id)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// The `name` compound of every section of a 1.18+ chunk that has one
+    fn section_tags<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Tag> {
+        self.nbt
+            .find_tag("sections")
+            .and_then(|tag| tag.get_list())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(move |section| section.find_tag(name))
+    }
+
+    pub fn has_been_inhabited(&self) -> bool {
         // The InhabitedTime value seems to be incremented for all 8 chunks around a player (including the one the player is standing in)
-        let inhabited_time = self
-            .nbt
-            .find_tag("InhabitedTime")
+        self.inhabited_time() > 0
+    }
+
+    /// Game ticks players spent near the chunk (`InhabitedTime`)
+    pub fn inhabited_time(&self) -> i64 {
+        self.find_chunk_tag("InhabitedTime")
             .and_then(|tag| tag.get_long())
             .copied()
-            .unwrap_or(0); // If the tag is not present, we can assume that the chunk has never been inhabited
+            .unwrap_or(0) // If the tag is not present, we can assume that the chunk has never been inhabited
+    }
 
-        inhabited_time > 0
+    /// Version of the chunk format (`DataVersion`), each Minecraft release and snapshot has its own.
+    /// Chunks saved before 1.9 have none
+    pub fn data_version(&self) -> Option<i32> {
+        self.nbt
+            .find_tag("DataVersion")
+            .and_then(|tag| tag.get_int())
+            .copied()
+    }
+
+    /// Game tick of the last save of the chunk (`LastUpdate`), comparable with the `Time` of `level.dat`
+    pub fn last_update(&self) -> Option<i64> {
+        self.find_chunk_tag("LastUpdate")
+            .and_then(|tag| tag.get_long())
+            .copied()
     }
 
     pub fn to_original_bytes(&self) -> Vec<u8> {
+        if self.is_external {
+            let mut stub = Vec::from(1_u32.to_be_bytes());
+            stub.push(self.original_compression_scheme.to_u8() | CompressionScheme::EXTERNAL_FLAG);
+            return stub;
+        }
         self.to_bytes_compression_scheme(self.original_compression_scheme, &self.original_payload)
     }
 
+    /// Tells if the payload of the chunk is stored in its own `.mcc` file
+    pub fn is_external(&self) -> bool {
+        self.is_external
+    }
+
     fn to_bytes_compression_scheme(
         &self,
         compression_scheme: CompressionScheme,
@@ -174,3 +509,428 @@ impl Chunk {
         result
     }
 }
+
+fn sum_counts<'a>(counts: impl Iterator<Item = (&'a str, usize)>) -> BTreeMap<&'a str, usize> {
+    let mut totals = BTreeMap::new();
+    for (id, count) in counts.filter(|&(_, count)| count > 0) {
+        *totals.entry(id).or_default() += count;
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_loader::region::Region;
+    use crate::region_loader::test_region::{byte, compound, element, int, list, string};
+
+    #[test]
+    fn test_count_block_on_real_sample() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let region = Region::from_file_name(&path, &SchemeMap::default())
+            .expect("Failed to parse region file");
+        let chunk = &region.get_chunks()[0];
+
+        let section_count = chunk
+            .nbt
+            .find_tag("sections")
+            .and_then(|tag| tag.get_list())
+            .map(|sections| sections.len())
+            .unwrap_or_default();
+        assert!(section_count > 0);
+        let total_blocks: usize = chunk
+            .nbt
+            .find_tag("sections")
+            .and_then(|tag| tag.get_list())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|section| section.find_tag("block_states"))
+            .flat_map(count_palette_entries)
+            .map(|(_, count)| count)
+            .sum();
+        assert_eq!(total_blocks, section_count * 4096);
+        assert!(chunk.count_block("minecraft:air") > 0);
+        assert!(chunk.count_block("minecraft:air") <= section_count * 4096);
+        assert_eq!(chunk.count_block("minecraft:not_a_block"), 0);
+
+        let blocks = chunk.blocks();
+        assert_eq!(blocks.values().sum::<usize>(), section_count * 4096);
+        assert_eq!(
+            blocks.get("minecraft:air").copied(),
+            Some(chunk.count_block("minecraft:air"))
+        );
+        assert!(blocks.values().all(|&count| count > 0));
+    }
+
+    /// Times `count_block` over every chunk of a region, the palettes and packed indices of all the sections.
+    /// Run with `cargo test --release bench_count_block -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_count_block() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let region = Region::from_file_name(&path, &SchemeMap::default())
+            .expect("Failed to parse region file");
+
+        const ITERS: u32 = 5;
+        let mut total = std::time::Duration::ZERO;
+        let mut count = 0;
+        for _ in 0..ITERS {
+            let start = std::time::Instant::now();
+            count = region
+                .get_chunks()
+                .iter()
+                .map(|chunk| chunk.count_block("minecraft:stone"))
+                .sum::<usize>();
+            total += start.elapsed();
+        }
+
+        println!(
+            "\n{} chunks, {count} stone blocks: {:?}/iter",
+            region.get_chunk_count(),
+            total / ITERS
+        );
+    }
+
+    #[test]
+    fn test_count_biome_on_real_sample() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let region = Region::from_file_name(&path, &SchemeMap::default())
+            .expect("Failed to parse region file");
+        let chunk = &region.get_chunks()[0];
+
+        let sections = chunk
+            .nbt
+            .find_tag("sections")
+            .and_then(|tag| tag.get_list())
+            .unwrap_or_default();
+        let total_cells: usize = sections
+            .iter()
+            .filter_map(|section| section.find_tag("biomes"))
+            .flat_map(count_biome_entries)
+            .map(|(_, count)| count)
+            .sum();
+        assert_eq!(total_cells, sections.len() * 64);
+        assert_eq!(chunk.count_biome("custom:not_a_biome"), 0);
+
+        let biomes = chunk.biomes();
+        assert_eq!(biomes.values().sum::<usize>(), total_cells);
+        for (biome, count) in biomes {
+            assert_eq!(chunk.count_biome(biome), count);
+        }
+    }
+
+    #[test]
+    fn test_serialized_frames_decode_with_their_scheme() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let region = Region::from_file_name(&path, &SchemeMap::default())
+            .expect("Failed to parse region file");
+        let chunk = &region.get_chunks()[0];
+
+        for frame in [
+            chunk.to_bytes(Compression::fast()).unwrap(),
+            chunk.to_original_bytes(),
+        ] {
+            let size = frame.len().div_ceil(4096) * 4096;
+            let mut buf = vec![0_u8; 8192];
+            buf.extend(&frame);
+            buf.resize(8192 + size, 0);
+            let location = Location::new(8192, size as u32, 0).unwrap();
+
+            let decoded = Chunk::from_location(&buf, location, None, &SchemeMap::default())
+                .expect("Frame does not match its scheme byte");
+            assert_eq!(decoded.nbt, chunk.nbt);
+        }
+    }
+
+    #[test]
+    fn test_chunk_size_larger_than_allocated_sectors() {
+        let mut buf = vec![0_u8; 4 * 4096];
+        // Chunk declared at sector 2 with 1 sector, but its size field claims 5000 bytes
+        buf[8192..8196].copy_from_slice(&5000_u32.to_be_bytes());
+        buf[8196] = 2;
+        let location = Location::new(8192, 4096, 0).unwrap();
+
+        assert_eq!(
+            Chunk::from_location(&buf, location, None, &SchemeMap::default()),
+            Err("Chunk size exceeds its allocated sectors")
+        );
+    }
+
+    #[test]
+    fn test_minimal_chunk_size() {
+        // Uncompressed payloads of TAG_End bytes, which parse as an empty tag
+        let chunk_with_size = |buf_len: usize, chunk_size: u32| {
+            let mut buf = vec![0_u8; buf_len];
+            buf[8192..8196].copy_from_slice(&chunk_size.to_be_bytes());
+            buf[8196] = 3;
+            Chunk::from_location(
+                &buf,
+                Location::new(8192, 4096, 0).unwrap(),
+                None,
+                &SchemeMap::default(),
+            )
+        };
+
+        let payload_len =
+            |chunk: Result<Chunk, &'static str>| chunk.map(|chunk| chunk.original_payload.len());
+
+        // A length of 2 is the scheme byte and a 1 byte payload, here the last byte of the file
+        assert_eq!(payload_len(chunk_with_size(8198, 2)), Ok(1));
+        assert_eq!(
+            chunk_with_size(8198, 2).unwrap().to_original_bytes(),
+            [0, 0, 0, 2, 3, 0]
+        );
+        assert_eq!(
+            payload_len(chunk_with_size(8197, 2)),
+            Err("Chunk payload out of bounds")
+        );
+        assert_eq!(
+            payload_len(chunk_with_size(8197, 1)),
+            Err("Error while parsing NBT")
+        );
+        // The length field, scheme byte and payload fill the whole sector
+        assert_eq!(payload_len(chunk_with_size(3 * 4096, 4092)), Ok(4091));
+        assert_eq!(
+            payload_len(chunk_with_size(3 * 4096, 4093)),
+            Err("Chunk size exceeds its allocated sectors")
+        );
+    }
+
+    #[test]
+    fn test_pre_1_18_nested_level_layout() {
+        let chunk = Chunk::new(
+            compound(
+                "",
+                vec![
+                    int("DataVersion", 2586),
+                    compound(
+                        "Level",
+                        vec![int("xPos", -3), int("zPos", 7), string("Status", "full")],
+                    ),
+                ],
+            ),
+            Location::new(8192, 4096, 0).unwrap(),
+            CompressionScheme::Zlib,
+            Vec::new(),
+        );
+
+        assert_eq!(chunk.get_position(), Ok((-3, 7)));
+        assert_eq!(chunk.keep_reason(), Some("fully generated"));
+        assert_eq!(chunk.status(), Some(ChunkStatus::Full));
+    }
+
+    /// Layout of a chunk saved by 1.21 (`DataVersion` 3953), read back from a compressed frame
+    #[test]
+    fn test_1_21_layout() {
+        // 4 bit indices, the first block of the section is diamond ore and the others stone
+        let mut data = vec![0_i64; 256];
+        data[0] = 1;
+        let chunk_with = |status: &str, inhabited_time| {
+            let nbt = compound(
+                "",
+                vec![
+                    int("DataVersion", 3953),
+                    int("xPos", 12),
+                    int("yPos", -4),
+                    int("zPos", -30),
+                    string("Status", status),
+                    Tag::Long {
+                        name: Some("InhabitedTime".to_string()),
+                        value: inhabited_time,
+                    },
+                    byte("isLightOn", 1),
+                    list(
+                        "sections",
+                        vec![element(vec![
+                            byte("Y", -4),
+                            compound(
+                                "block_states",
+                                vec![
+                                    list(
+                                        "palette",
+                                        vec![
+                                            element(vec![string("Name", "minecraft:stone")]),
+                                            element(vec![string("Name", "minecraft:diamond_ore")]),
+                                        ],
+                                    ),
+                                    Tag::LongArray {
+                                        name: Some("data".to_string()),
+                                        value: data.clone(),
+                                    },
+                                ],
+                            ),
+                        ])],
+                    ),
+                    // Block entities keep their id and store their items as data components since 1.20.5
+                    list(
+                        "block_entities",
+                        vec![element(vec![
+                            string("id", "minecraft:chest"),
+                            int("x", 192),
+                            int("y", -64),
+                            int("z", -480),
+                            compound("components", Vec::new()),
+                        ])],
+                    ),
+                    compound(
+                        "structures",
+                        vec![
+                            compound("References", Vec::new()),
+                            compound("starts", Vec::new()),
+                        ],
+                    ),
+                ],
+            );
+            let frame = Chunk::from_nbt(nbt).to_bytes(Compression::fast()).unwrap();
+            let size = frame.len().div_ceil(4096) * 4096;
+            let mut buf = vec![0_u8; 8192];
+            buf.extend(&frame);
+            buf.resize(8192 + size, 0);
+            Chunk::from_location(
+                &buf,
+                Location::new(8192, size as u32, 0).unwrap(),
+                None,
+                &SchemeMap::default(),
+            )
+            .unwrap()
+        };
+
+        let full = chunk_with("minecraft:full", 0);
+        assert_eq!(full.get_position(), Ok((12, -30)));
+        assert_eq!(full.data_version(), Some(3953));
+        assert!(full.is_fully_generated());
+        assert_eq!(full.keep_reason(), Some("fully generated"));
+        assert_eq!(full.count_block("minecraft:diamond_ore"), 1);
+        assert_eq!(full.count_block("minecraft:stone"), 4095);
+        assert_eq!(full.section_count(), 1);
+        assert!(!full.has_structures());
+
+        let proto = chunk_with("minecraft:initialize_light", 0);
+        assert!(!proto.is_fully_generated());
+        assert_eq!(proto.status(), Some(ChunkStatus::InitializeLight));
+        assert!(proto.should_delete());
+        assert_eq!(
+            chunk_with("minecraft:initialize_light", 40).keep_reason(),
+            Some("inhabited")
+        );
+    }
+
+    #[test]
+    fn test_pre_1_13_population_flags() {
+        let chunk_with = |terrain_populated, light_populated| {
+            Chunk::new(
+                compound(
+                    "",
+                    vec![compound(
+                        "Level",
+                        vec![
+                            byte("TerrainPopulated", terrain_populated),
+                            byte("LightPopulated", light_populated),
+                        ],
+                    )],
+                ),
+                Location::new(8192, 4096, 0).unwrap(),
+                CompressionScheme::Zlib,
+                Vec::new(),
+            )
+        };
+
+        assert!(chunk_with(1, 1).is_fully_generated());
+        assert!(chunk_with(1, 0).is_fully_generated());
+        assert!(chunk_with(0, 1).is_fully_generated());
+        assert!(!chunk_with(0, 0).is_fully_generated());
+        assert_eq!(chunk_with(0, 0).keep_reason(), None);
+        assert_eq!(chunk_with(1, 0).status(), Some(ChunkStatus::Features));
+    }
+
+    #[test]
+    fn test_has_structures() {
+        let chunk_with = |structures| {
+            Chunk::new(
+                compound("", vec![compound("structures", structures)]),
+                Location::new(8192, 4096, 0).unwrap(),
+                CompressionScheme::Zlib,
+                Vec::new(),
+            )
+        };
+        let start = |id: &str| compound("minecraft:stronghold", vec![string("id", id)]);
+        let references = |chunks| {
+            compound(
+                "References",
+                vec![Tag::LongArray {
+                    name: Some("minecraft:stronghold".to_string()),
+                    value: chunks,
+                }],
+            )
+        };
+
+        assert!(!chunk_with(vec![]).has_structures());
+        assert!(
+            !chunk_with(vec![
+                references(vec![]),
+                compound("starts", vec![start("INVALID")])
+            ])
+            .has_structures()
+        );
+        assert!(chunk_with(vec![references(vec![42])]).has_structures());
+        assert!(
+            chunk_with(vec![compound(
+                "starts",
+                vec![start("minecraft:stronghold")]
+            )])
+            .has_structures()
+        );
+    }
+
+    #[test]
+    fn test_section_count() {
+        let section = |blocks: &[&str]| {
+            element(vec![compound(
+                "block_states",
+                vec![list(
+                    "palette",
+                    blocks
+                        .iter()
+                        .map(|block| element(vec![string("Name", block)]))
+                        .collect(),
+                )],
+            )])
+        };
+        let chunk = Chunk::new(
+            compound(
+                "",
+                vec![list(
+                    "sections",
+                    vec![
+                        section(&["minecraft:stone"]),
+                        section(&["minecraft:air", "minecraft:torch"]),
+                        section(&["minecraft:air"]),
+                        section(&["minecraft:cave_air", "minecraft:void_air"]),
+                    ],
+                )],
+            ),
+            Location::new(8192, 4096, 0).unwrap(),
+            CompressionScheme::Zlib,
+            Vec::new(),
+        );
+
+        assert_eq!(chunk.section_count(), 2);
+        assert_eq!(chunk.status(), None);
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let region = Region::from_file_name(&path, &SchemeMap::default())
+            .expect("Failed to parse region file");
+        assert!(
+            region
+                .get_chunks()
+                .iter()
+                .all(|chunk| chunk.section_count() <= 24)
+        );
+        assert!(
+            region
+                .get_chunks()
+                .iter()
+                .any(|chunk| chunk.section_count() > 0)
+        );
+    }
+}
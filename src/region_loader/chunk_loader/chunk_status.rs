@@ -0,0 +1,99 @@
+use std::fmt::Display;
+
+/// Generation stage of a chunk, in the order the game goes through them.
+/// Stages removed or renamed by later versions keep their place in the ladder so that chunks of worlds
+/// saved by any version since 1.14 compare correctly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChunkStatus {
+    Empty,
+    StructureStarts,
+    StructureReferences,
+    Biomes,
+    Noise,
+    Surface,
+    Carvers,
+    /// Merged into `carvers` by 1.18
+    LiquidCarvers,
+    Features,
+    /// Split from `light` by 1.20
+    InitializeLight,
+    Light,
+    Spawn,
+    /// Removed by 1.18
+    Heightmaps,
+    Full,
+}
+
+impl ChunkStatus {
+    /// Every status from the first generation stage to the last
+    pub const LADDER: [ChunkStatus; 14] = [
+        ChunkStatus::Empty,
+        ChunkStatus::StructureStarts,
+        ChunkStatus::StructureReferences,
+        ChunkStatus::Biomes,
+        ChunkStatus::Noise,
+        ChunkStatus::Surface,
+        ChunkStatus::Carvers,
+        ChunkStatus::LiquidCarvers,
+        ChunkStatus::Features,
+        ChunkStatus::InitializeLight,
+        ChunkStatus::Light,
+        ChunkStatus::Spawn,
+        ChunkStatus::Heightmaps,
+        ChunkStatus::Full,
+    ];
+
+    /// Name of the status in its `Status` tag, without the `minecraft:` namespace
+    pub fn name(self) -> &'static str {
+        match self {
+            ChunkStatus::Empty => "empty",
+            ChunkStatus::StructureStarts => "structure_starts",
+            ChunkStatus::StructureReferences => "structure_references",
+            ChunkStatus::Biomes => "biomes",
+            ChunkStatus::Noise => "noise",
+            ChunkStatus::Surface => "surface",
+            ChunkStatus::Carvers => "carvers",
+            ChunkStatus::LiquidCarvers => "liquid_carvers",
+            ChunkStatus::Features => "features",
+            ChunkStatus::InitializeLight => "initialize_light",
+            ChunkStatus::Light => "light",
+            ChunkStatus::Spawn => "spawn",
+            ChunkStatus::Heightmaps => "heightmaps",
+            ChunkStatus::Full => "full",
+        }
+    }
+
+    /// Reads a `Status` value, namespaced (1.18+) or not
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.strip_prefix("minecraft:").unwrap_or(name);
+        ChunkStatus::LADDER
+            .into_iter()
+            .find(|status| status.name() == name)
+    }
+}
+
+impl Display for ChunkStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert!(ChunkStatus::LADDER.windows(2).all(|pair| pair[0] < pair[1]));
+        for status in ChunkStatus::LADDER {
+            assert_eq!(ChunkStatus::from_name(status.name()), Some(status));
+        }
+        assert_eq!(
+            ChunkStatus::from_name("minecraft:features"),
+            Some(ChunkStatus::Features)
+        );
+        assert_eq!(ChunkStatus::from_name("full"), Some(ChunkStatus::Full));
+        assert_eq!(ChunkStatus::from_name("minecraft:decorated"), None);
+        assert!(ChunkStatus::Light > ChunkStatus::Features);
+    }
+}
@@ -2,24 +2,131 @@
 pub enum CompressionScheme {
     Gzip,
     Zlib,
+    Uncompressed,
     Lz4,
 }
 
 impl CompressionScheme {
+    /// Set in the scheme byte when the chunk is too large for the region and lives in its own `.mcc` file
+    pub const EXTERNAL_FLAG: u8 = 0x80;
+    /// Parse failure reason of chunks whose scheme byte is not supported
+    pub const UNSUPPORTED_ERROR: &'static str = "Unsupported compression scheme";
+
+    /// Reads a standard scheme byte, ignoring the external flag. See [`SchemeMap::scheme`] for nonstandard ones
     pub fn from_u8(byte: u8) -> Result<Self, &'static str> {
+        Self::from_standard_u8(byte & !Self::EXTERNAL_FLAG).ok_or(Self::UNSUPPORTED_ERROR)
+    }
+
+    fn from_standard_u8(byte: u8) -> Option<Self> {
         match byte {
-            1 => Ok(CompressionScheme::Gzip),
-            2 => Ok(CompressionScheme::Zlib),
-            3 => Ok(CompressionScheme::Lz4),
-            _ => Err("Unsupported compression scheme"),
+            1 => Some(CompressionScheme::Gzip),
+            2 => Some(CompressionScheme::Zlib),
+            3 => Some(CompressionScheme::Uncompressed),
+            4 => Some(CompressionScheme::Lz4),
+            _ => None,
         }
     }
 
+    /// Parses the name of a scheme, as given to `--assume-scheme`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "gzip" => Some(CompressionScheme::Gzip),
+            "zlib" => Some(CompressionScheme::Zlib),
+            "uncompressed" | "none" => Some(CompressionScheme::Uncompressed),
+            "lz4" => Some(CompressionScheme::Lz4),
+            _ => None,
+        }
+    }
+
+    pub fn is_external(byte: u8) -> bool {
+        byte & Self::EXTERNAL_FLAG != 0
+    }
+
     pub fn to_u8(self) -> u8 {
         match self {
             CompressionScheme::Gzip => 1,
             CompressionScheme::Zlib => 2,
-            CompressionScheme::Lz4 => 3,
+            CompressionScheme::Uncompressed => 3,
+            CompressionScheme::Lz4 => 4,
         }
     }
 }
+
+/// Schemes assumed for nonstandard scheme bytes with `--assume-scheme`, handed to every region read
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemeMap {
+    /// Standard byte of the scheme each nonstandard scheme byte is decoded as, 0 for none
+    assumed: [u8; 128],
+}
+
+impl Default for SchemeMap {
+    fn default() -> Self {
+        Self { assumed: [0; 128] }
+    }
+}
+
+impl SchemeMap {
+    /// Decodes the chunks saved with a nonstandard scheme `byte`, such as those of a server fork, as `scheme`.
+    /// Rewritten chunks are saved with the standard byte of their new scheme
+    pub fn assume(&mut self, byte: u8, scheme: CompressionScheme) {
+        self.assumed[(byte & !CompressionScheme::EXTERNAL_FLAG) as usize] = scheme.to_u8();
+    }
+
+    /// Reads a scheme byte, ignoring the external flag. Nonstandard bytes are only read when a scheme was assumed
+    /// for them
+    pub fn scheme(&self, byte: u8) -> Result<CompressionScheme, &'static str> {
+        CompressionScheme::from_u8(byte).or_else(|error| {
+            let byte = byte & !CompressionScheme::EXTERNAL_FLAG;
+            CompressionScheme::from_standard_u8(self.assumed[byte as usize]).ok_or(error)
+        })
+    }
+
+    /// Nonstandard scheme bytes and the scheme assumed for each, in byte order
+    pub fn assumed(&self) -> Vec<(u8, CompressionScheme)> {
+        (0..self.assumed.len() as u8)
+            .filter_map(|byte| {
+                let scheme = CompressionScheme::from_standard_u8(self.assumed[byte as usize])?;
+                Some((byte, scheme))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_external_flag() {
+        assert_eq!(CompressionScheme::from_u8(0x84), Ok(CompressionScheme::Lz4));
+        assert_eq!(
+            CompressionScheme::from_u8(0x82),
+            Ok(CompressionScheme::Zlib)
+        );
+        assert!(CompressionScheme::is_external(0x84));
+        assert!(!CompressionScheme::is_external(4));
+        assert!(CompressionScheme::from_u8(0x85).is_err());
+    }
+
+    #[test]
+    fn test_assume_scheme() {
+        let mut schemes = SchemeMap::default();
+        assert!(schemes.scheme(10).is_err());
+        schemes.assume(10, CompressionScheme::Zlib);
+        assert_eq!(schemes.scheme(10), Ok(CompressionScheme::Zlib));
+        assert_eq!(
+            schemes.scheme(10 | CompressionScheme::EXTERNAL_FLAG),
+            Ok(CompressionScheme::Zlib)
+        );
+        assert_eq!(schemes.scheme(2), Ok(CompressionScheme::Zlib));
+        assert!(CompressionScheme::from_u8(10).is_err());
+        assert_eq!(schemes.assumed(), vec![(10, CompressionScheme::Zlib)]);
+        assert!(SchemeMap::default().assumed().is_empty());
+
+        assert_eq!(
+            CompressionScheme::from_name("LZ4"),
+            Some(CompressionScheme::Lz4)
+        );
+        assert_eq!(CompressionScheme::from_name("zstd"), None);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::nbt::tag::Tag;
+
+/// Removes the entities of an entity chunk (in `entities/`) whose `id` is one of `ids`, along with the passengers
+/// of this type riding a kept entity. Returns how many were removed, passengers of removed entities excluded.
+///
+/// Entity chunks list their entities in `Entities`, each a compound with its namespaced `id` (`minecraft:item`,
+/// `minecraft:armor_stand`...). A removed entity takes its passengers with it.
+pub(crate) fn remove_entity_types(chunk: &mut Tag, ids: &[String]) -> usize {
+    match chunk.find_tag_mut("Entities") {
+        Some(Tag::List {
+            value: entities, ..
+        }) => remove_from_list(entities, ids),
+        _ => 0,
+    }
+}
+
+fn remove_from_list(entities: &mut Vec<Tag>, ids: &[String]) -> usize {
+    let entity_count = entities.len();
+    entities.retain(|entity| {
+        !entity
+            .find_tag("id")
+            .and_then(|tag| tag.get_string())
+            .is_some_and(|id| ids.contains(id))
+    });
+    let mut removed = entity_count - entities.len();
+    for entity in entities {
+        if let Some(Tag::List {
+            value: passengers, ..
+        }) = entity.find_tag_mut("Passengers")
+        {
+            removed += remove_from_list(passengers, ids);
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(id: &str, passengers: Vec<Tag>) -> Tag {
+        let mut value = vec![Tag::String {
+            name: Some("id".to_string()),
+            value: id.to_string(),
+        }];
+        if !passengers.is_empty() {
+            value.push(Tag::List {
+                name: Some("Passengers".to_string()),
+                value: passengers,
+                tag_type: 10,
+            });
+        }
+        Tag::Compound { name: None, value }
+    }
+
+    fn entity_chunk(entities: Vec<Tag>) -> Tag {
+        Tag::Compound {
+            name: Some(String::new()),
+            value: vec![
+                Tag::IntArray {
+                    name: Some("Position".to_string()),
+                    value: vec![3, -2],
+                },
+                Tag::List {
+                    name: Some("Entities".to_string()),
+                    value: entities,
+                    tag_type: 10,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_remove_entity_types() {
+        let ids = [
+            "minecraft:item".to_string(),
+            "minecraft:armor_stand".to_string(),
+        ];
+        let mut chunk = entity_chunk(vec![
+            entity("minecraft:item", vec![]),
+            entity("minecraft:cow", vec![]),
+            // Its passenger goes with it
+            entity(
+                "minecraft:armor_stand",
+                vec![entity("minecraft:zombie", vec![])],
+            ),
+            entity(
+                "minecraft:boat",
+                vec![
+                    entity("minecraft:item", vec![]),
+                    entity("minecraft:pig", vec![]),
+                ],
+            ),
+        ]);
+        assert_eq!(remove_entity_types(&mut chunk, &ids), 3);
+
+        let expected = entity_chunk(vec![
+            entity("minecraft:cow", vec![]),
+            entity("minecraft:boat", vec![entity("minecraft:pig", vec![])]),
+        ]);
+        assert_eq!(chunk, expected);
+        assert_eq!(remove_entity_types(&mut chunk, &ids), 0);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::nbt::tag::Tag;
+use crate::region_loader::chunk_loader::block_states::{
+    bits_per_entry, block_state_indices, pack_entries,
+};
+
+/// Highest block that is not air
+const WORLD_SURFACE: &str = "WORLD_SURFACE";
+/// Highest block that blocks motion or holds a fluid
+const MOTION_BLOCKING: &str = "MOTION_BLOCKING";
+
+/// Vanilla blocks that neither block motion nor hold a fluid, without their namespace.
+/// Blocks of other namespaces are assumed to block motion
+const NON_BLOCKING: &[&str] = &[
+    "air",
+    "cave_air",
+    "void_air",
+    "light",
+    "structure_void",
+    "cobweb",
+    "snow",
+    "short_grass",
+    "grass",
+    "tall_grass",
+    "fern",
+    "large_fern",
+    "dead_bush",
+    "sweet_berry_bush",
+    "dandelion",
+    "poppy",
+    "blue_orchid",
+    "allium",
+    "azure_bluet",
+    "oxeye_daisy",
+    "cornflower",
+    "lily_of_the_valley",
+    "wither_rose",
+    "torchflower",
+    "sunflower",
+    "lilac",
+    "rose_bush",
+    "peony",
+    "pitcher_plant",
+    "pink_petals",
+    "spore_blossom",
+    "brown_mushroom",
+    "red_mushroom",
+    "crimson_fungus",
+    "warped_fungus",
+    "crimson_roots",
+    "warped_roots",
+    "nether_sprouts",
+    "glow_lichen",
+    "vine",
+    "hanging_roots",
+    "sugar_cane",
+    "wheat",
+    "carrots",
+    "potatoes",
+    "beetroots",
+    "nether_wart",
+    "melon_stem",
+    "pumpkin_stem",
+    "attached_melon_stem",
+    "attached_pumpkin_stem",
+    "torch",
+    "redstone_wire",
+    "repeater",
+    "comparator",
+    "lever",
+    "tripwire",
+    "tripwire_hook",
+    "ladder",
+    "rail",
+    "powered_rail",
+    "detector_rail",
+    "activator_rail",
+    "flower_pot",
+    "candle",
+    "fire",
+    "soul_fire",
+    "nether_portal",
+    "end_portal",
+    "end_gateway",
+];
+const NON_BLOCKING_SUFFIXES: &[&str] = &[
+    "_sapling",
+    "_tulip",
+    "_torch",
+    "_button",
+    "_carpet",
+    "_candle",
+    "_vines",
+    "_vines_plant",
+    "_head",
+    "_skull",
+];
+/// Blocks that always hold water, other blocks only do when `waterlogged`
+const FLUIDS: &[&str] = &[
+    "water",
+    "lava",
+    "bubble_column",
+    "seagrass",
+    "tall_seagrass",
+    "kelp",
+    "kelp_plant",
+];
+
+/// Rebuilds the `WORLD_SURFACE` and `MOTION_BLOCKING` heightmaps of a 1.18+ chunk from its block states.
+/// Returns `true` if any of them changed.
+///
+/// Heightmaps store, for each column in `x + z * 16` order, the height above the bottom of the world
+/// of the first block matching them (0 when there is none), packed like block states.
+pub(crate) fn recompute_heightmaps(chunk: &mut Tag) -> Result<bool, &'static str> {
+    let (bits, world_surface, motion_blocking) = compute_heightmaps(chunk)?;
+
+    if chunk.find_tag("Heightmaps").is_none() {
+        let heightmaps = Tag::Compound {
+            name: None,
+            value: Vec::new(),
+        };
+        chunk
+            .insert("Heightmaps", heightmaps)
+            .map_err(|_| "Chunk is not a compound")?;
+    }
+    let heightmaps = chunk
+        .find_tag_mut("Heightmaps")
+        .ok_or("Cannot create the Heightmaps compound")?;
+
+    let mut changed = false;
+    for (name, heights) in [
+        (WORLD_SURFACE, world_surface),
+        (MOTION_BLOCKING, motion_blocking),
+    ] {
+        changed |= set_heightmap(heightmaps, name, pack_entries(&heights, bits))?;
+    }
+    Ok(changed)
+}
+
+fn set_heightmap(heightmaps: &mut Tag, name: &str, data: Vec<i64>) -> Result<bool, &'static str> {
+    if let Some(Tag::LongArray { value, .. }) = heightmaps.find_tag_mut(name) {
+        if *value == data {
+            return Ok(false);
+        }
+        *value = data;
+        return Ok(true);
+    }
+
+    // Replaces a tag of the wrong type, if any
+    let heightmap = Tag::LongArray {
+        name: None,
+        value: data,
+    };
+    heightmaps
+        .insert(name, heightmap)
+        .map_err(|_| "Heightmaps is not a compound")?;
+    Ok(true)
+}
+
+struct SectionBlocks {
+    y: i32,
+    /// Whether each palette entry is air and whether it blocks motion
+    kinds: Vec<(bool, bool)>,
+    indices: Vec<u64>,
+}
+
+/// Returns the bits per value and the `WORLD_SURFACE` and `MOTION_BLOCKING` values of every column
+fn compute_heightmaps(chunk: &Tag) -> Result<(usize, Vec<u64>, Vec<u64>), &'static str> {
+    let min_section = *chunk
+        .find_tag("yPos")
+        .and_then(|tag| tag.get_int())
+        .ok_or("No yPos, heightmaps can only be rebuilt for 1.18+ chunks")?;
+    let sections = chunk
+        .find_tag("sections")
+        .and_then(|tag| tag.get_list())
+        .ok_or("No sections")?;
+
+    // Sections holding blocks, top first
+    let mut sections: Vec<SectionBlocks> = sections
+        .iter()
+        .filter_map(|section| {
+            let y = *section.find_tag("Y").and_then(|tag| tag.get_byte())? as i32;
+            let block_states = section.find_tag("block_states")?;
+            let palette = block_states
+                .find_tag("palette")
+                .and_then(|tag| tag.get_list())?;
+            Some(SectionBlocks {
+                y,
+                kinds: palette.iter().map(block_kind).collect(),
+                indices: block_state_indices(block_states, palette.len()),
+            })
+        })
+        .collect();
+    sections.sort_by_key(|section| -section.y);
+
+    let max_section = sections
+        .first()
+        .map(|section| section.y)
+        .ok_or("No block states")?;
+    if max_section < min_section {
+        return Err("Sections are below yPos");
+    }
+    let height = (max_section - min_section + 1) as usize * 16;
+    let min_y = min_section * 16;
+
+    let mut world_surface = vec![0_u64; 256];
+    let mut motion_blocking = vec![0_u64; 256];
+    for column in 0..256 {
+        let mut found_surface = false;
+        'sections: for section in &sections {
+            for y in (0..16).rev() {
+                let index = section.indices.get(y * 256 + column).copied().unwrap_or(0) as usize;
+                // Out of range indices only appear in corrupted sections, they are read as air
+                let (is_air, blocks_motion) =
+                    section.kinds.get(index).copied().unwrap_or((true, false));
+                let height_above_bottom = (section.y * 16 + y as i32 - min_y + 1) as u64;
+                if !found_surface && !is_air {
+                    world_surface[column] = height_above_bottom;
+                    found_surface = true;
+                }
+                if blocks_motion {
+                    motion_blocking[column] = height_above_bottom;
+                    break 'sections;
+                }
+            }
+        }
+    }
+
+    Ok((bits_per_entry(height + 1), world_surface, motion_blocking))
+}
+
+/// Tells if a palette entry is air, and if it blocks motion or holds a fluid
+fn block_kind(entry: &Tag) -> (bool, bool) {
+    let name = entry
+        .find_tag("Name")
+        .and_then(|tag| tag.get_string())
+        .map(|name| name.as_str())
+        .unwrap_or_default();
+    let is_air = matches!(
+        name,
+        "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air"
+    );
+
+    let Some(id) = name.strip_prefix("minecraft:") else {
+        return (is_air, true);
+    };
+    let is_waterlogged = entry
+        .find_tag("Properties")
+        .and_then(|properties| properties.find_tag("waterlogged"))
+        .and_then(|tag| tag.get_string())
+        .is_some_and(|value| value == "true");
+    let is_non_blocking = NON_BLOCKING.contains(&id)
+        || id.starts_with("potted_")
+        || NON_BLOCKING_SUFFIXES
+            .iter()
+            .any(|suffix| id.ends_with(suffix));
+
+    (
+        is_air,
+        is_waterlogged || FLUIDS.contains(&id) || !is_non_blocking,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_loader::chunk_loader::block_states::unpack_entries;
+    use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+    use crate::region_loader::region::Region;
+    use crate::region_loader::test_region::{byte, compound, element, int, list, string};
+
+    fn block(name: &str) -> Tag {
+        element(vec![string("Name", name)])
+    }
+
+    /// Section whose blocks are all `name`, or air with a single `name` block at (0, `y`, 0)
+    fn section(section_y: i8, name: &str, single_block_y: Option<usize>) -> Tag {
+        let mut block_states = vec![];
+        match single_block_y {
+            None => block_states.push(list("palette", vec![block(name)])),
+            Some(y) => {
+                block_states.push(list("palette", vec![block("minecraft:air"), block(name)]));
+                let mut indices = vec![0_u64; 4096];
+                indices[y * 256] = 1;
+                block_states.push(Tag::LongArray {
+                    name: Some("data".to_string()),
+                    value: pack_entries(&indices, 4),
+                });
+            }
+        }
+        element(vec![
+            byte("Y", section_y),
+            compound("block_states", block_states),
+        ])
+    }
+
+    fn heightmap<'a>(chunk: &'a Tag, name: &str) -> &'a [i64] {
+        chunk
+            .find_tag("Heightmaps")
+            .and_then(|heightmaps| heightmaps.find_tag(name))
+            .and_then(|tag| tag.get_long_array())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_recompute_heightmaps() {
+        // 384 blocks high world from y = -64: a stone floor in section -4, a torch above it at y = -60
+        // and a stone block at y = 2 in column 0
+        let mut chunk = compound(
+            "",
+            vec![
+                int("yPos", -4),
+                list(
+                    "sections",
+                    (-4..20)
+                        .map(|y| match y {
+                            -4 => section(-4, "minecraft:stone", None),
+                            -3 => section(-3, "minecraft:torch", Some(4)),
+                            0 => section(0, "minecraft:stone", Some(2)),
+                            y => section(y, "minecraft:air", None),
+                        })
+                        .collect(),
+                ),
+            ],
+        );
+
+        assert_eq!(recompute_heightmaps(&mut chunk), Ok(true));
+        let world_surface = unpack_entries(heightmap(&chunk, WORLD_SURFACE), 256, 9);
+        let motion_blocking = unpack_entries(heightmap(&chunk, MOTION_BLOCKING), 256, 9);
+        assert_eq!(world_surface[0], 67);
+        assert_eq!(motion_blocking[0], 67);
+        assert_eq!(world_surface[1], 16);
+        assert_eq!(motion_blocking[1], 16);
+
+        // The torch is now the highest block of column 0, but it does not block motion
+        let Some(Tag::List {
+            value: sections, ..
+        }) = chunk.find_tag_mut("sections")
+        else {
+            unreachable!()
+        };
+        sections[4] = section(0, "minecraft:air", None);
+        assert_eq!(recompute_heightmaps(&mut chunk), Ok(true));
+        assert_eq!(
+            unpack_entries(heightmap(&chunk, WORLD_SURFACE), 256, 9)[0],
+            21
+        );
+        assert_eq!(
+            unpack_entries(heightmap(&chunk, MOTION_BLOCKING), 256, 9)[0],
+            16
+        );
+        assert_eq!(recompute_heightmaps(&mut chunk), Ok(false));
+    }
+
+    #[test]
+    fn test_matches_vanilla_heightmaps() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let region = Region::from_file_name(&path, &SchemeMap::default())
+            .expect("Failed to parse region file");
+        for chunk in region.get_chunks() {
+            let mut nbt = chunk.nbt.clone();
+            assert_eq!(recompute_heightmaps(&mut nbt), Ok(false));
+        }
+    }
+
+    #[test]
+    fn test_block_kind() {
+        assert_eq!(block_kind(&block("minecraft:cave_air")), (true, false));
+        assert_eq!(block_kind(&block("minecraft:oak_sapling")), (false, false));
+        assert_eq!(block_kind(&block("minecraft:water")), (false, true));
+        assert_eq!(block_kind(&block("minecraft:oak_planks")), (false, true));
+        assert_eq!(block_kind(&block("mymod:tiny_flower")), (false, true));
+    }
+}
@@ -1,2 +1,8 @@
+mod block_states;
 pub mod chunk;
-mod compression_scheme;
+pub mod chunk_status;
+pub mod compression_scheme;
+mod entities;
+mod heightmaps;
+mod poi;
+mod sections;
@@ -0,0 +1,101 @@
+use crate::nbt::tag::Tag;
+
+/// Removes the records of a POI chunk (in `poi/`) whose block is not in the chunk at `chunk_position`,
+/// along with records without a valid `pos`. Returns how many were removed.
+///
+/// POI chunks group their records by section, `Sections.<y>.Records`, and each record stores the absolute
+/// block coordinates of its point of interest (bed, workstation, nether portal...) as a `pos` [x, y, z] int array.
+/// The game only looks records up in the chunk holding their block, so the others are never cleaned up
+/// and keep villagers and portals linked to blocks that may be gone.
+pub(crate) fn prune_foreign_records(poi: &mut Tag, chunk_position: (i32, i32)) -> usize {
+    let Some(Tag::Compound {
+        value: sections, ..
+    }) = poi.find_tag_mut("Sections")
+    else {
+        return 0;
+    };
+
+    let mut pruned = 0;
+    for section in sections {
+        let Some(Tag::List { value: records, .. }) = section.find_tag_mut("Records") else {
+            continue;
+        };
+        let record_count = records.len();
+        records.retain(
+            |record| match record.find_tag("pos").and_then(|tag| tag.get_int_array()) {
+                Some(&[x, _, z]) => (x >> 4, z >> 4) == chunk_position,
+                _ => false,
+            },
+        );
+        pruned += record_count - records.len();
+    }
+    pruned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(pos: Vec<i32>) -> Tag {
+        Tag::Compound {
+            name: None,
+            value: vec![
+                Tag::IntArray {
+                    name: Some("pos".to_string()),
+                    value: pos,
+                },
+                Tag::String {
+                    name: Some("type".to_string()),
+                    value: "minecraft:home".to_string(),
+                },
+            ],
+        }
+    }
+
+    fn poi_chunk(records: Vec<Tag>) -> Tag {
+        Tag::Compound {
+            name: Some(String::new()),
+            value: vec![Tag::Compound {
+                name: Some("Sections".to_string()),
+                value: vec![Tag::Compound {
+                    name: Some("4".to_string()),
+                    value: vec![
+                        Tag::Byte {
+                            name: Some("Valid".to_string()),
+                            value: 1,
+                        },
+                        Tag::List {
+                            name: Some("Records".to_string()),
+                            value: records,
+                            tag_type: 10,
+                        },
+                    ],
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_prune_foreign_records() {
+        let mut poi = poi_chunk(vec![
+            record(vec![-17, 70, 33]),
+            record(vec![-32, 70, 47]),
+            // In the neighbouring chunk
+            record(vec![-16, 70, 47]),
+            record(vec![1, 2]),
+        ]);
+        assert_eq!(prune_foreign_records(&mut poi, (-2, 2)), 2);
+
+        let records = poi
+            .find_tag("Sections")
+            .and_then(|sections| sections.find_tag("4"))
+            .and_then(|section| section.find_tag("Records"))
+            .and_then(|records| records.get_list())
+            .unwrap();
+        assert_eq!(
+            records,
+            &[record(vec![-17, 70, 33]), record(vec![-32, 70, 47])]
+        );
+        assert_eq!(prune_foreign_records(&mut poi, (-2, 2)), 0);
+    }
+}
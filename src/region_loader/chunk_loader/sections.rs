@@ -0,0 +1,119 @@
+use crate::nbt::tag::Tag;
+
+/// Lists of a 1.18+ chunk whose entries are placed by a `y` block coordinate
+const BLOCK_LISTS: &[&str] = &["block_entities", "block_ticks", "fluid_ticks"];
+
+/// Lowest block kept when removing the sections below `y`: sections are 16 blocks high, so only those lying
+/// entirely below `y` go
+fn lowest_kept_block(y: i32) -> i32 {
+    y.div_euclid(16) * 16
+}
+
+fn section_y(section: &Tag) -> Option<i32> {
+    section
+        .find_tag("Y")
+        .and_then(|tag| tag.get_byte())
+        .map(|&y| i32::from(y))
+}
+
+/// Counts the sections of a 1.18+ chunk lying entirely below `y`, see [`remove_sections_below`]
+pub(crate) fn count_sections_below(chunk: &Tag, y: i32) -> usize {
+    let lowest_kept_section = lowest_kept_block(y) / 16;
+    chunk
+        .find_tag("sections")
+        .and_then(|tag| tag.get_list())
+        .unwrap_or_default()
+        .iter()
+        .filter(|section| {
+            section_y(section).is_some_and(|section_y| section_y < lowest_kept_section)
+        })
+        .count()
+}
+
+/// Removes the sections of a 1.18+ chunk lying entirely below `y`, along with the block entities and scheduled
+/// ticks they held. Returns how many sections were removed.
+///
+/// Sections are the 16 blocks high slices of `sections`, each with its `Y` index. The game loads the missing ones
+/// as air, so `yPos` and the height of the world do not change. Pre-1.18 chunks (`Level.Sections`) are left alone
+pub(crate) fn remove_sections_below(chunk: &mut Tag, y: i32) -> usize {
+    let lowest_kept_block = lowest_kept_block(y);
+    let lowest_kept_section = lowest_kept_block / 16;
+    let removed = match chunk.find_tag_mut("sections") {
+        Some(Tag::List {
+            value: sections, ..
+        }) => {
+            let section_count = sections.len();
+            // Sections without a `Y` cannot be placed, they are kept
+            sections.retain(|section| {
+                section_y(section).is_none_or(|section_y| section_y >= lowest_kept_section)
+            });
+            section_count - sections.len()
+        }
+        _ => 0,
+    };
+    if removed == 0 {
+        return 0;
+    }
+
+    for name in BLOCK_LISTS {
+        if let Some(Tag::List { value: entries, .. }) = chunk.find_tag_mut(name) {
+            entries.retain(|entry| {
+                entry
+                    .find_tag("y")
+                    .and_then(|tag| tag.get_int())
+                    .is_none_or(|&entry_y| entry_y >= lowest_kept_block)
+            });
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_loader::test_region::{byte, compound, element, int, list, string};
+
+    fn block_entity(y: i32) -> Tag {
+        element(vec![string("id", "minecraft:chest"), int("y", y)])
+    }
+
+    #[test]
+    fn test_remove_sections_below() {
+        let sections = (-4..4).map(|y| element(vec![byte("Y", y)])).collect();
+        let mut chunk = compound(
+            "",
+            vec![
+                list("sections", sections),
+                list(
+                    "block_entities",
+                    vec![
+                        block_entity(-64),
+                        block_entity(-17),
+                        block_entity(-16),
+                        block_entity(3),
+                    ],
+                ),
+                list("fluid_ticks", vec![block_entity(-40)]),
+            ],
+        );
+        let remaining = |chunk: &Tag, name: &str| {
+            chunk
+                .find_tag(name)
+                .and_then(|tag| tag.get_list())
+                .unwrap()
+                .len()
+        };
+
+        // -10 lies in section -1, which holds blocks above it and is kept
+        assert_eq!(count_sections_below(&chunk, -10), 3);
+        assert_eq!(remove_sections_below(&mut chunk, -10), 3);
+        assert_eq!(remaining(&chunk, "sections"), 5);
+        assert_eq!(remaining(&chunk, "block_entities"), 2);
+        assert_eq!(remaining(&chunk, "fluid_ticks"), 0);
+
+        assert_eq!(remove_sections_below(&mut chunk, -16), 0);
+        assert_eq!(remove_sections_below(&mut chunk, 0), 1);
+        assert_eq!(count_sections_below(&chunk, 0), 0);
+        assert_eq!(remaining(&chunk, "block_entities"), 1);
+    }
+}
@@ -0,0 +1,94 @@
+use flate2::Compression;
+use std::str::FromStr;
+
+/// Compression level used when re-serializing chunks.
+/// The only ways to build one are `new` and parsing, so every write path is guaranteed to use a level in `0..=9`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionLevel(u32);
+
+impl CompressionLevel {
+    pub const MAX: u32 = 9;
+
+    const RANGE_ERROR: &'static str = "Compression level must be an integer between 0 and 9";
+
+    pub fn new(level: u32) -> Result<Self, &'static str> {
+        if level > Self::MAX {
+            return Err(Self::RANGE_ERROR);
+        }
+        Ok(Self(level))
+    }
+
+    #[cfg(test)]
+    pub fn fast() -> Self {
+        Self(1)
+    }
+
+    #[cfg(test)]
+    pub fn best() -> Self {
+        Self(Self::MAX)
+    }
+
+    pub fn to_compression(self) -> Compression {
+        Compression::new(self.0)
+    }
+}
+
+/// Parses a level given on the command line (`-c`)
+impl FromStr for CompressionLevel {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s.parse().map_err(|_| Self::RANGE_ERROR)?)
+    }
+}
+
+impl Default for CompressionLevel {
+    fn default() -> Self {
+        Self(6)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compression_level_range() {
+        for level in 0..=9 {
+            assert_eq!(
+                CompressionLevel::new(level)
+                    .unwrap()
+                    .to_compression()
+                    .level(),
+                level
+            );
+        }
+        assert!(CompressionLevel::new(10).is_err());
+        assert!(CompressionLevel::new(u32::MAX).is_err());
+
+        assert_eq!("0".parse(), CompressionLevel::new(0));
+        assert_eq!("9".parse(), Ok(CompressionLevel::best()));
+        for invalid in ["10", "-1", "fast", ""] {
+            assert_eq!(
+                invalid.parse::<CompressionLevel>(),
+                Err(CompressionLevel::RANGE_ERROR)
+            );
+        }
+    }
+
+    #[test]
+    fn test_compression_level_to_compression() {
+        assert_eq!(
+            CompressionLevel::fast().to_compression(),
+            Compression::fast()
+        );
+        assert_eq!(
+            CompressionLevel::best().to_compression(),
+            Compression::best()
+        );
+        assert_eq!(
+            CompressionLevel::default().to_compression(),
+            Compression::default()
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use crate::region_loader::get_u32::get_u32;
+use crate::region_loader::location::Location;
+use crate::region_loader::region::{GZIP_MAGIC, HEADER_SIZE, ParseRegionError};
+use flate2::read::GzDecoder;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek};
+use std::path::Path;
+
+/// Chunk and sector counts computed from the location table only, without reading or decompressing chunk data.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct HeaderStats {
+    pub chunk_count: usize,
+    /// Sectors reserved by the chunks of the location table
+    pub used_sectors: usize,
+    /// Size of the whole file, header included, in sectors
+    pub file_sectors: usize,
+}
+
+impl HeaderStats {
+    pub fn from_file_name(file_name: &Path) -> Result<Self, ParseRegionError> {
+        let (header, file_len) = read_header(file_name)?;
+        Ok(HeaderStats::from_header(&header, file_len))
+    }
+
+    fn from_header(header: &[u8; HEADER_SIZE], file_len: u64) -> Self {
+        let mut stats = HeaderStats {
+            file_sectors: file_len.div_ceil(4096) as usize,
+            ..HeaderStats::default()
+        };
+
+        for location in readable_locations(header, stats.file_sectors) {
+            stats.chunk_count += 1;
+            stats.used_sectors += location.get_sector_count() as usize;
+        }
+
+        stats
+    }
+}
+
+/// Indices in the location table of the chunks a region file holds, read from its header only
+pub fn occupied_slots(file_name: &Path) -> Result<HashSet<usize>, ParseRegionError> {
+    let (header, file_len) = read_header(file_name)?;
+    let file_sectors = file_len.div_ceil(4096) as usize;
+    Ok((0..1024)
+        .filter(|&slot| readable_location(&header, slot, file_sectors).is_some())
+        .collect())
+}
+
+fn readable_locations(
+    header: &[u8; HEADER_SIZE],
+    file_sectors: usize,
+) -> impl Iterator<Item = Location> + '_ {
+    (0..1024).filter_map(move |slot| readable_location(header, slot, file_sectors))
+}
+
+fn readable_location(
+    header: &[u8; HEADER_SIZE],
+    slot: usize,
+    file_sectors: usize,
+) -> Option<Location> {
+    let location = Location::from_bytes(get_u32(header, 4 * slot), 0);
+    // Locations pointing past the end of the file cannot hold a readable chunk
+    let end = location.get_sector_offset() as usize + location.get_sector_count() as usize;
+    (location.is_valid() && end <= file_sectors).then_some(location)
+}
+
+/// Reads the header of a region file and the length of the file, uncompressed if the whole file is gzipped
+fn read_header(file_name: &Path) -> Result<([u8; HEADER_SIZE], u64), ParseRegionError> {
+    let mut file = File::open(file_name).map_err(|_| ParseRegionError::ReadError)?;
+    let mut magic = [0_u8; 2];
+    let is_gzipped = file.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC;
+    file.rewind().map_err(|_| ParseRegionError::ReadError)?;
+    if is_gzipped {
+        return read_gzipped_header(file);
+    }
+
+    let file_len = file
+        .metadata()
+        .map_err(|_| ParseRegionError::ReadError)?
+        .len();
+    if file_len < HEADER_SIZE as u64 {
+        return Err(ParseRegionError::HeaderError);
+    }
+
+    let mut header = [0_u8; HEADER_SIZE];
+    file.read_exact(&mut header)
+        .map_err(|_| ParseRegionError::ReadError)?;
+    Ok((header, file_len))
+}
+
+/// Gzipped region files have to be streamed through entirely to know their uncompressed size
+fn read_gzipped_header(file: File) -> Result<([u8; HEADER_SIZE], u64), ParseRegionError> {
+    let mut decoder = GzDecoder::new(file);
+    let mut header = [0_u8; HEADER_SIZE];
+    match decoder.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+            return Err(ParseRegionError::HeaderError);
+        }
+        Err(_) => return Err(ParseRegionError::ReadError),
+    }
+    let data_len = std::io::copy(&mut decoder, &mut std::io::sink())
+        .map_err(|_| ParseRegionError::ReadError)?;
+    Ok((header, HEADER_SIZE as u64 + data_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_only() {
+        let stats = HeaderStats::from_header(&[0; HEADER_SIZE], HEADER_SIZE as u64);
+        assert_eq!(
+            stats,
+            HeaderStats {
+                chunk_count: 0,
+                used_sectors: 0,
+                file_sectors: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_counts_valid_locations() {
+        let mut header = [0_u8; HEADER_SIZE];
+        header[0..4].copy_from_slice(&[0, 0, 2, 1]);
+        header[4..8].copy_from_slice(&[0, 0, 3, 3]);
+        // Offset without size is not a valid location
+        header[8..12].copy_from_slice(&[0, 0, 6, 0]);
+        // Past the end of the file
+        header[12..16].copy_from_slice(&[0, 0, 6, 1]);
+
+        let stats = HeaderStats::from_header(&header, 6 * 4096);
+        assert_eq!(stats.chunk_count, 2);
+        assert_eq!(stats.used_sectors, 4);
+        assert_eq!(stats.file_sectors, 6);
+    }
+
+    #[test]
+    fn test_matches_full_parse_on_real_sample() {
+        use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+        use crate::region_loader::region::Region;
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let stats = HeaderStats::from_file_name(&path).expect("sample header must be readable");
+        let region = Region::from_file_name(&path, &SchemeMap::default()).unwrap();
+        assert_eq!(stats.chunk_count, region.get_chunk_count());
+    }
+
+    #[test]
+    fn test_occupied_slots() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        assert_eq!(occupied_slots(&path).unwrap().len(), 1024);
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/header_only.mca");
+        assert!(occupied_slots(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_header_without_chunk_data() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/header_only.mca");
+        let stats = HeaderStats::from_file_name(&path).expect("header must be readable");
+        assert_eq!(stats.chunk_count, 0);
+        assert_eq!(stats.file_sectors, 2);
+    }
+
+    #[test]
+    fn test_gzipped_region_file() {
+        use crate::region_loader::compression_level::CompressionLevel;
+        use crate::region_loader::region::gzip_file;
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let gzipped = std::env::temp_dir().join(format!("mwt_stats_{}.mca.gz", std::process::id()));
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(
+            &gzipped,
+            gzip_file(CompressionLevel::fast(), |encoder| {
+                encoder.write_all(&bytes)
+            })
+            .unwrap(),
+        )
+        .unwrap();
+
+        let stats = HeaderStats::from_file_name(&gzipped).expect("gzipped header must be readable");
+        assert_eq!(stats, HeaderStats::from_file_name(&path).unwrap());
+
+        std::fs::remove_file(&gzipped).ok();
+    }
+}
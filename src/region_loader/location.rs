@@ -57,6 +57,16 @@ impl Location {
         self.timestamp
     }
 
+    /// Offset of the chunk in the region file, counted in 4096 bytes sectors
+    pub fn get_sector_offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Number of 4096 bytes sectors reserved for the chunk
+    pub fn get_sector_count(&self) -> u32 {
+        self.size as u32
+    }
+
     #[cfg(test)]
     fn get_size(&self) -> u32 {
         self.size as u32 * 4096
@@ -90,6 +100,8 @@ mod tests {
         assert_eq!(location.get_offset(), 8192_u32);
         assert_eq!(location.get_size(), 4096_u32);
         assert_eq!(location.get_timestamp(), 0);
+        assert_eq!(location.get_sector_offset(), 2);
+        assert_eq!(location.get_sector_count(), 1);
     }
 
     #[test]
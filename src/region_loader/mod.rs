@@ -1,4 +1,8 @@
-mod chunk_loader;
+pub mod chunk_loader;
+pub mod compression_level;
 mod get_u32;
+pub mod header_stats;
 mod location;
 pub mod region;
+#[cfg(test)]
+pub(crate) mod test_region;
@@ -1,22 +1,222 @@
 use crate::region_loader::chunk_loader::chunk::Chunk;
+use crate::region_loader::chunk_loader::compression_scheme::{CompressionScheme, SchemeMap};
+use crate::region_loader::compression_level::CompressionLevel;
 use crate::region_loader::get_u32::get_u32;
 use crate::region_loader::location::Location;
-use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
-#[derive(PartialEq, Debug)]
+/// Size of the location and timestamp tables at the start of every region file
+pub const HEADER_SIZE: usize = 8192;
+
+/// Chunks [`Region::serialize_to`] compresses at once before handing them to the writing thread
+const STREAMED_CHUNKS: usize = 32;
+
+/// Magic bytes of a gzip stream, some backup tools store whole region files gzipped (`r.0.0.mca.gz`)
+pub(crate) const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Debug)]
 pub struct Region {
     chunks: Vec<Chunk>,
     is_modified: bool,
+    /// Sector counts of the parsed file, they describe the file on disk and do not change with removals
+    total_sectors: usize,
+    referenced_sectors: usize,
+    /// The whole file was gzip-compressed on disk and must be compressed again when saved
+    is_gzipped: bool,
+    /// Last modification of the file, in seconds since the Unix epoch.
+    /// A rewrite stores it for chunks whose timestamp some tool left at zero
+    file_timestamp: Option<u32>,
+    /// Chunks of the location table that could not be parsed, a rewrite loses them
+    parse_failures: Vec<ChunkParseFailure>,
+    /// Problems of the location table of the parsed file, found while parsing it
+    location_issues: Vec<RegionIssue>,
+}
+
+/// A chunk listed in the location table that could not be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkParseFailure {
+    /// Index of the chunk in the location table
+    pub slot: usize,
+    /// Absolute coordinates of the chunk, when the name of the region file tells where the region is
+    pub position: Option<(i32, i32)>,
+    pub reason: &'static str,
+}
+
+impl Display for ChunkParseFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.position {
+            Some((x, z)) => write!(f, "chunk ({x}, {z})"),
+            None => write!(
+                f,
+                "chunk ({}, {}) of the region",
+                self.slot % 32,
+                self.slot / 32
+            ),
+        }
+    }
+}
+
+/// A chunk [`Region::serialize`] could not give a location table entry, it is left out of the serialized region
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnplacedChunk {
+    /// Index of the chunk in [`Region::get_chunks`]
+    pub index: usize,
+    pub position: Option<(i32, i32)>,
+    pub reason: &'static str,
+}
+
+impl Display for UnplacedChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.position {
+            Some((x, z)) => write!(f, "chunk ({x}, {z}): {}", self.reason),
+            None => write!(f, "chunk #{}: {}", self.index, self.reason),
+        }
+    }
+}
+
+/// An integrity problem of a region file, as returned by [`Region::validate`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegionIssue {
+    /// The location of the chunk in `slot` points into the header or past the end of the file
+    LocationOutOfBounds { slot: usize, sectors: Range<usize> },
+    /// The chunk in `slot` shares sectors with the chunk in `other_slot`, saving one of them corrupts the other
+    OverlappingSectors { slot: usize, other_slot: usize },
+    /// The chunk in `slot` stores a position that belongs to another slot or another region
+    MisplacedChunk { slot: usize, position: (i32, i32) },
+    /// The chunk was saved with a compression scheme that cannot be decoded, see `--assume-scheme`
+    UnsupportedCompression(ChunkParseFailure),
+    /// The chunk failed to parse for any other reason
+    UnparsableChunk(ChunkParseFailure),
+}
+
+impl RegionIssue {
+    /// Index in the location table of the chunk the issue is about
+    pub fn slot(&self) -> usize {
+        match self {
+            RegionIssue::LocationOutOfBounds { slot, .. }
+            | RegionIssue::OverlappingSectors { slot, .. }
+            | RegionIssue::MisplacedChunk { slot, .. } => *slot,
+            RegionIssue::UnsupportedCompression(failure)
+            | RegionIssue::UnparsableChunk(failure) => failure.slot,
+        }
+    }
+}
+
+impl Display for RegionIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let slot = |slot: usize| format!("slot ({}, {})", slot % 32, slot / 32);
+        match self {
+            RegionIssue::LocationOutOfBounds {
+                slot: index,
+                sectors,
+            } => write!(
+                f,
+                "{} points at sectors {}..{}, outside of the chunk data",
+                slot(*index),
+                sectors.start,
+                sectors.end
+            ),
+            RegionIssue::OverlappingSectors {
+                slot: index,
+                other_slot,
+            } => {
+                write!(
+                    f,
+                    "{} shares sectors with {}",
+                    slot(*index),
+                    slot(*other_slot)
+                )
+            }
+            RegionIssue::MisplacedChunk {
+                slot: index,
+                position: (x, z),
+            } => {
+                write!(
+                    f,
+                    "{} holds chunk ({x}, {z}), which belongs elsewhere",
+                    slot(*index)
+                )
+            }
+            RegionIssue::UnsupportedCompression(failure)
+            | RegionIssue::UnparsableChunk(failure) => {
+                write!(f, "{failure} failed to parse: {}", failure.reason)
+            }
+        }
+    }
+}
+
+/// How the 4 KiB sectors of a region file are used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectorUsage {
+    /// Sectors of the header and of the chunks currently loaded in the region
+    pub used_sectors: usize,
+    /// Sectors of the parsed file, including a trailing partial sector
+    pub total_sectors: usize,
+    /// Distinct sectors of the parsed file covered by the header or the location table,
+    /// including entries whose chunk failed to parse
+    pub referenced_sectors: usize,
+}
+
+impl SectorUsage {
+    /// Sectors of the file that no location references, dead weight left behind by previous saves
+    pub fn orphaned_sectors(&self) -> usize {
+        self.total_sectors - self.referenced_sectors
+    }
+}
+
+/// A region ready to be written: its chunks are compressed and its tables built, but nothing is concatenated yet.
+/// Every compressed chunk is held in memory until written, [`Region::serialize_to`] writes them as they come
+pub struct SerializedRegion {
+    location_table: [u8; 4096],
+    timestamp_table: [u8; 4096],
+    /// Compressed chunks in file order, each padded to whole sectors
+    chunks: Vec<Vec<u8>>,
+    pub compression_fallbacks: usize,
+    /// Chunks missing from the serialized region, writing it loses them
+    pub unplaced_chunks: Vec<UnplacedChunk>,
+}
+
+impl SerializedRegion {
+    /// Length of the region file `write_to` writes
+    pub fn file_len(&self) -> u64 {
+        (HEADER_SIZE + self.chunks.iter().map(Vec::len).sum::<usize>()) as u64
+    }
+
+    /// Writes the header then every chunk to `writer`, without concatenating them into a second copy of the file
+    pub fn write_to(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writer.write_all(&self.location_table)?;
+        writer.write_all(&self.timestamp_table)?;
+        for chunk in &self.chunks {
+            writer.write_all(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+/// What [`Region::serialize_to`] wrote
+pub struct StreamedRegion {
+    /// Length of the written region file
+    pub len: u64,
+    pub compression_fallbacks: usize,
+    /// Chunks missing from the written file, see [`SerializedRegion::unplaced_chunks`]
+    pub unplaced_chunks: Vec<UnplacedChunk>,
 }
 
 pub struct ToBytesResult {
     pub bytes: Vec<u8>,
     pub compression_fallbacks: usize,
-    pub header_write_failures: usize,
+    /// Chunks missing from `bytes`, see [`SerializedRegion::unplaced_chunks`]
+    pub unplaced_chunks: Vec<UnplacedChunk>,
 }
 
 #[derive(Error, Debug)]
@@ -28,19 +228,217 @@ pub enum ParseRegionError {
 }
 
 impl Region {
-    pub fn from_file_name(file_name: &Path) -> Result<Self, ParseRegionError> {
-        let bytes = try_read_bytes(file_name).map_err(|_| ParseRegionError::ReadError)?;
-        Region::from_bytes(&bytes)
+    /// Parses a region file, decoding the chunks saved with a nonstandard scheme byte with `schemes`
+    pub fn from_file_name(file_name: &Path, schemes: &SchemeMap) -> Result<Self, ParseRegionError> {
+        Region::read_file(file_name, false, schemes)
     }
 
+    /// Parses a POI region file (in `poi/`). POI chunks do not store their coordinates,
+    /// they get the position of their slot in the location table of the `r.<x>.<z>.mca` region
+    pub fn from_poi_file(file_name: &Path, schemes: &SchemeMap) -> Result<Self, ParseRegionError> {
+        Region::read_file(file_name, true, schemes)
+    }
+
+    fn read_file(
+        file_name: &Path,
+        locate_by_slot: bool,
+        schemes: &SchemeMap,
+    ) -> Result<Self, ParseRegionError> {
+        let (bytes, is_gzipped) = read_region_bytes(file_name)?;
+        let external_files = ExternalChunkFiles::for_region_file(file_name);
+        let mut region = Region::parse_bytes(&bytes, external_files.as_ref(), schemes)?;
+        if let (true, Some(external_files)) = (locate_by_slot, &external_files) {
+            region.locate_chunks(&bytes, external_files);
+        }
+        region.is_gzipped = is_gzipped;
+        region.file_timestamp = std::fs::metadata(file_name)
+            .and_then(|m| m.modified())
+            .ok()
+            .map(unix_timestamp);
+        Ok(region)
+    }
+
+    /// Parses a region file from any source, such as an entry of a world archive.
+    /// External chunks are not loaded since there is no directory to find their `.mcc` file in
+    pub fn from_reader(
+        mut reader: impl Read,
+        schemes: &SchemeMap,
+    ) -> Result<Self, ParseRegionError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .map_err(|_| ParseRegionError::ReadError)?;
+        let (bytes, is_gzipped) = decompress_region_bytes(bytes)?;
+        let mut region = Region::parse_bytes(&bytes, None, schemes)?;
+        region.is_gzipped = is_gzipped;
+        Ok(region)
+    }
+
+    /// Parses a region, then adds every chunk decoded elsewhere in the file at a position its location table does
+    /// not hold. This is a last resort for files whose header lost track of chunk sectors that survived.
+    /// Returns the region with the number of chunks added, it is only modified when some were
+    pub fn salvage_file(
+        file_name: &Path,
+        schemes: &SchemeMap,
+    ) -> Result<(Self, usize), ParseRegionError> {
+        let (bytes, is_gzipped) = read_region_bytes(file_name)?;
+        let external_files = ExternalChunkFiles::for_region_file(file_name);
+        let (mut region, added) = Region::salvage_bytes(&bytes, external_files.as_ref(), schemes)?;
+        region.is_gzipped = is_gzipped;
+        Ok((region, added))
+    }
+
+    /// Decodes at most `count` chunks spread over the location table, for reports that don't need every chunk
+    pub fn sample_chunks(
+        file_name: &Path,
+        count: usize,
+        schemes: &SchemeMap,
+    ) -> Result<Vec<Chunk>, ParseRegionError> {
+        let (bytes, _) = read_region_bytes(file_name)?;
+        if bytes.len() < HEADER_SIZE {
+            return Err(ParseRegionError::HeaderError);
+        }
+
+        // Locations with their index in the table
+        let locations: Vec<(usize, Location)> = (0..1024)
+            .map(|index| {
+                let location = Location::from_bytes(
+                    get_u32(&bytes, 4 * index),
+                    get_u32(&bytes, 4096 + 4 * index),
+                );
+                (index, location)
+            })
+            .filter(|(_, location)| location.is_valid())
+            .collect();
+        let step = locations.len().div_ceil(count.max(1)).max(1);
+        let external_files = ExternalChunkFiles::for_region_file(file_name);
+
+        Ok(locations
+            .into_iter()
+            .step_by(step)
+            .filter_map(|(index, location)| {
+                let external_file = external_files.as_ref().map(|files| files.chunk_file(index));
+                Chunk::from_location(&bytes, location, external_file.as_deref(), schemes).ok()
+            })
+            .collect())
+    }
+
+    /// Gives the chunks without a position the one of their slot in the location table
+    fn locate_chunks(&mut self, bytes: &[u8], external_files: &ExternalChunkFiles) {
+        let slots: HashMap<u32, usize> = (0..1024)
+            .map(|index| {
+                (
+                    Location::from_bytes(get_u32(bytes, 4 * index), 0).get_sector_offset(),
+                    index,
+                )
+            })
+            .collect();
+        for chunk in &mut self.chunks {
+            if chunk.get_position().is_err() {
+                if let Some(&index) = slots.get(&chunk.location.get_sector_offset()) {
+                    chunk.set_position(external_files.chunk_position(index));
+                }
+            }
+        }
+    }
+
+    fn salvage_bytes(
+        bytes: &[u8],
+        external_files: Option<&ExternalChunkFiles>,
+        schemes: &SchemeMap,
+    ) -> Result<(Self, usize), ParseRegionError> {
+        let mut region = Region::parse_bytes(bytes, external_files, schemes)?;
+        // Chunks without coordinates are kept at their slot, otherwise the rebuild could not place them
+        if let Some(external_files) = external_files {
+            region.locate_chunks(bytes, external_files);
+        }
+        let unparsable = region.parse_failures.iter().map(|failure| failure.position);
+        let held: HashSet<(i32, i32)> = region
+            .chunks
+            .iter()
+            .map(|chunk| chunk.get_position().ok())
+            .chain(unparsable)
+            .flatten()
+            .collect();
+
+        let mut scanned: Vec<_> = Region::scan_chunks(bytes, schemes)
+            .into_iter()
+            .filter(|(position, _)| !held.contains(position))
+            .collect();
+        scanned.sort_by_key(|(position, _)| *position);
+        let added = scanned.len();
+        if added > 0 {
+            let chunks = scanned.into_iter().map(|(_, chunk)| chunk);
+            region.chunks.extend(chunks);
+            region.is_modified = true;
+        }
+        Ok((region, added))
+    }
+
+    /// Decodes every chunk found at the start of a sector past the header, whether the location table references it
+    /// or not. Chunks in `.mcc` files and chunks without coordinates cannot be found this way
+    fn scan_chunks(bytes: &[u8], schemes: &SchemeMap) -> HashMap<(i32, i32), Chunk> {
+        let total_sectors = bytes.len().div_ceil(4096);
+        let mut chunks: HashMap<(i32, i32), Chunk> = HashMap::new();
+
+        // Chunks always start on a sector boundary with their length and compression scheme,
+        // so every sector after the header is tried as the start of a chunk
+        let mut sector = HEADER_SIZE / 4096;
+        while sector < total_sectors {
+            let offset = sector * 4096;
+            let declared_size = if offset + 4 <= bytes.len() {
+                get_u32(bytes, offset) as usize
+            } else {
+                0
+            };
+            let sector_count = (declared_size + 4).div_ceil(4096);
+            let candidate = Location::new(offset as u32, (sector_count * 4096) as u32, 0)
+                .ok()
+                .filter(|_| declared_size > 0)
+                .and_then(|location| Chunk::from_location(bytes, location, None, schemes).ok())
+                .and_then(|chunk| chunk.get_position().ok().map(|position| (position, chunk)));
+
+            match candidate {
+                Some((position, chunk)) => {
+                    // Stale copies of a chunk may survive in unreferenced sectors, keep the most recent save
+                    let is_newer = chunks
+                        .get(&position)
+                        .is_none_or(|kept| chunk.last_update() > kept.last_update());
+                    if is_newer {
+                        chunks.insert(position, chunk);
+                    }
+                    sector += sector_count;
+                }
+                None => sector += 1,
+            }
+        }
+        chunks
+    }
+
+    #[cfg(test)]
     fn from_bytes(bytes: &[u8]) -> Result<Self, ParseRegionError> {
+        Region::parse_bytes(bytes, None, &SchemeMap::default())
+    }
+
+    fn parse_bytes(
+        bytes: &[u8],
+        external_files: Option<&ExternalChunkFiles>,
+        schemes: &SchemeMap,
+    ) -> Result<Self, ParseRegionError> {
         let mut chunks = Vec::with_capacity(1024);
-        if bytes.len() < 8192 {
+        let mut parse_failures = Vec::new();
+        if bytes.len() < HEADER_SIZE {
             return Err(ParseRegionError::HeaderError);
         }
 
         let location_table = &bytes[0..4096];
-        let timestamp_table = &bytes[4096..8192];
+        let timestamp_table = &bytes[4096..HEADER_SIZE];
+
+        let total_sectors = bytes.len().div_ceil(4096);
+        let mut referenced = vec![false; total_sectors];
+        referenced[0..2].fill(true);
+        let mut location_issues = Vec::new();
+        let mut chunk_sectors = Vec::new();
 
         for i in (0..4096).step_by(4) {
             let l = get_u32(location_table, i);
@@ -48,69 +446,213 @@ impl Region {
             let location = Location::from_bytes(l, timestamp);
 
             if location.is_valid() {
-                if let Ok(chunk) = Chunk::from_location(bytes, location) {
-                    chunks.push(chunk);
+                let sectors = location.get_sector_offset() as usize
+                    ..location.get_sector_offset() as usize + location.get_sector_count() as usize;
+                if sectors.start < HEADER_SIZE / 4096 || sectors.end > total_sectors {
+                    location_issues.push(RegionIssue::LocationOutOfBounds {
+                        slot: i / 4,
+                        sectors: sectors.clone(),
+                    });
+                }
+                let start = sectors.start.min(total_sectors);
+                let end = sectors.end.min(total_sectors);
+                referenced[start..end].fill(true);
+                chunk_sectors.push((i / 4, sectors));
+
+                let external_file = external_files.map(|files| files.chunk_file(i / 4));
+                match Chunk::from_location(bytes, location, external_file.as_deref(), schemes) {
+                    Ok(chunk) => {
+                        let misplaced = chunk
+                            .get_position()
+                            .ok()
+                            .filter(|&position| !is_in_slot(position, i / 4, external_files));
+                        if let Some(position) = misplaced {
+                            location_issues.push(RegionIssue::MisplacedChunk {
+                                slot: i / 4,
+                                position,
+                            });
+                        }
+                        chunks.push(chunk);
+                    }
+                    // The chunk is not loaded, so a rewrite loses it. Callers report it
+                    Err(reason) => parse_failures.push(ChunkParseFailure {
+                        slot: i / 4,
+                        position: external_files.map(|files| files.chunk_position(i / 4)),
+                        reason,
+                    }),
                 }
-                // Else, we choose to not load the chunk and loose it because it is invalid
-                // FIXME: We might not want to loose the chunk if the compression scheme is an unsupported type (eg. LZ4 since 24w04a or custom algorithm since 24w05a)
             }
         }
 
+        let referenced_sectors = referenced.iter().filter(|&&used| used).count();
+        location_issues.extend(overlapping_sectors(chunk_sectors));
+        location_issues.sort_by_key(RegionIssue::slot);
+
         Ok(Self {
             chunks,
             is_modified: false,
+            total_sectors,
+            referenced_sectors,
+            is_gzipped: false,
+            file_timestamp: None,
+            parse_failures,
+            location_issues,
         })
     }
 
-    pub fn to_bytes(&self, compression: Compression) -> ToBytesResult {
-        let mut data: Vec<u8> = Vec::with_capacity(self.chunks.len() * 4096);
-        let mut location_table = [0_u8; 4096];
-        let mut timestamp_table = [0_u8; 4096];
-        let mut compression_fallbacks = 0usize;
-        let mut header_write_failures = 0usize;
+    pub fn to_bytes(&self, compression: CompressionLevel) -> ToBytesResult {
+        let serialized = self.serialize(compression);
+        let mut bytes = Vec::with_capacity(serialized.file_len() as usize);
+        serialized
+            .write_to(&mut bytes)
+            .expect("Writing to memory cannot fail");
+        ToBytesResult {
+            bytes,
+            compression_fallbacks: serialized.compression_fallbacks,
+            unplaced_chunks: serialized.unplaced_chunks,
+        }
+    }
 
-        for chunk in &self.chunks {
-            let mut serialized = match chunk.to_bytes(compression) {
-                Ok(bytes) => bytes,
-                Err(_) => {
-                    compression_fallbacks += 1;
-                    chunk.to_original_bytes()
-                }
-            };
-            align_vec_size(&mut serialized);
+    /// Compresses the chunks and lays out the tables of the region file, see [`SerializedRegion::write_to`] to
+    /// write it without concatenating the compressed chunks into a second copy of the whole file.
+    /// Chunks without a position, too large for their table entry or sharing their slot with an earlier chunk are
+    /// left out and listed in [`SerializedRegion::unplaced_chunks`], nothing of them is written
+    pub fn serialize(&self, compression: CompressionLevel) -> SerializedRegion {
+        let mut layout = RegionLayout::new(self.fallback_timestamp());
+        let mut data: Vec<Vec<u8>> = Vec::with_capacity(self.chunks.len());
 
-            let new_position = (data.len() + 8192) as u32;
-            let new_size = serialized.len() as u32;
-            let original_timestamp = chunk.location.get_timestamp();
-            let new_location = Location::new(new_position, new_size, original_timestamp);
+        // Compression dominates the cost of a rewrite, so chunks are compressed in parallel
+        // and only the table assembly below stays sequential
+        let serialized_chunks: Vec<_> = self
+            .chunks
+            .par_iter()
+            .map(|chunk| compress_chunk(chunk, compression))
+            .collect();
 
-            let chunk_position = chunk.get_position();
-            if let (Ok(new_location), Ok((x, z))) = (new_location, chunk_position) {
-                let position_in_table = get_position_in_table(x, z);
+        for (index, (chunk, (serialized, is_fallback))) in
+            self.chunks.iter().zip(serialized_chunks).enumerate()
+        {
+            data.extend(layout.place(index, chunk, serialized, is_fallback));
+        }
 
-                let location_bytes = new_location.to_location_bytes();
-                location_table[position_in_table..(4 + position_in_table)]
-                    .copy_from_slice(&location_bytes);
+        SerializedRegion {
+            location_table: layout.location_table,
+            timestamp_table: layout.timestamp_table,
+            chunks: data,
+            compression_fallbacks: layout.compression_fallbacks,
+            unplaced_chunks: layout.unplaced_chunks,
+        }
+    }
 
-                let timestamp_bytes = new_location.to_timestamp_bytes();
-                timestamp_table[position_in_table..(4 + position_in_table)]
-                    .copy_from_slice(&timestamp_bytes);
+    /// Same as [`Region::serialize`] followed by [`SerializedRegion::write_to`], except that the chunks are written
+    /// as they come out of compression, a few at a time, instead of once the whole region is compressed. Disk writes
+    /// then overlap with compression. The tables are only known at the end, `writer` seeks back to write them over
+    /// the zeroed header it starts with.
+    /// With a single worker (`--single-thread`), the batches are written between compressions on the current thread
+    pub fn serialize_to(
+        &self,
+        compression: CompressionLevel,
+        writer: &mut (impl Write + Seek + Send),
+    ) -> std::io::Result<StreamedRegion> {
+        let batches = self.chunks.chunks(STREAMED_CHUNKS).map(|batch| -> Vec<_> {
+            batch
+                .par_iter()
+                .map(|chunk| compress_chunk(chunk, compression))
+                .collect()
+        });
+        if rayon::current_num_threads() == 1 {
+            return self.write_streamed(batches.flatten(), writer);
+        }
 
-                data.extend(serialized);
-            } else {
-                // Do not append payload if we cannot produce a valid header entry
-                header_write_failures += 1;
+        std::thread::scope(|scope| {
+            let (sender, receiver) = std::sync::mpsc::sync_channel(STREAMED_CHUNKS);
+            // The writing thread never waits on the thread pool, so compressing on it cannot deadlock even when
+            // every worker is streaming a region. Failing to create it, where threads are limited, fails the write
+            let written = std::thread::Builder::new()
+                .spawn_scoped(scope, move || self.write_streamed(receiver, writer))?;
+
+            for compressed in batches {
+                // A failed send means the writing thread stopped on an error, which join returns
+                if compressed
+                    .into_iter()
+                    .try_for_each(|entry| sender.send(entry))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            drop(sender);
+            written
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+
+    /// Writes the chunks of the region, compressed by `serialize_to` in file order, then seeks back to write the
+    /// tables
+    fn write_streamed(
+        &self,
+        compressed: impl IntoIterator<Item = (Vec<u8>, bool)>,
+        writer: &mut (impl Write + Seek),
+    ) -> std::io::Result<StreamedRegion> {
+        let mut layout = RegionLayout::new(self.fallback_timestamp());
+        writer.write_all(&[0; HEADER_SIZE])?;
+        for (index, (chunk, (serialized, is_fallback))) in
+            self.chunks.iter().zip(compressed).enumerate()
+        {
+            if let Some(serialized) = layout.place(index, chunk, serialized, is_fallback) {
+                writer.write_all(&serialized)?;
             }
         }
+        writer.seek(SeekFrom::Start(0))?;
+        writer.write_all(&layout.location_table)?;
+        writer.write_all(&layout.timestamp_table)?;
+        writer.seek(SeekFrom::End(0))?;
+        Ok(StreamedRegion {
+            len: (HEADER_SIZE + layout.data_len) as u64,
+            compression_fallbacks: layout.compression_fallbacks,
+            unplaced_chunks: layout.unplaced_chunks,
+        })
+    }
 
-        let mut bytes = Vec::with_capacity(8192 + data.len());
-        bytes.extend_from_slice(&location_table);
-        bytes.extend_from_slice(&timestamp_table);
-        bytes.extend(data);
-        ToBytesResult {
-            bytes,
-            compression_fallbacks,
-            header_write_failures,
+    /// Timestamp of rewritten chunks saved without one
+    fn fallback_timestamp(&self) -> u32 {
+        self.file_timestamp
+            .unwrap_or_else(|| unix_timestamp(SystemTime::now()))
+    }
+
+    /// Counts the chunks [`Region::serialize`] would leave out of a region holding these chunks because they have no
+    /// position or share their slot with an earlier chunk, without compressing anything
+    pub fn count_unplaceable<'a>(chunks: impl IntoIterator<Item = &'a Chunk>) -> usize {
+        let mut placed_slots = [false; 1024];
+        chunks
+            .into_iter()
+            .filter(|chunk| match chunk.get_position() {
+                Ok((x, z)) => {
+                    std::mem::replace(&mut placed_slots[get_position_in_table(x, z) / 4], true)
+                }
+                Err(_) => true,
+            })
+            .count()
+    }
+
+    /// Region holding chunks built in memory, as if it was read from a file with no orphaned sectors
+    #[cfg(test)]
+    pub(crate) fn from_chunks(chunks: Vec<Chunk>) -> Self {
+        let chunk_sectors: usize = chunks
+            .iter()
+            .map(|chunk| chunk.location.get_sector_count() as usize)
+            .sum();
+        let total_sectors = HEADER_SIZE / 4096 + chunk_sectors;
+        Self {
+            chunks,
+            is_modified: false,
+            total_sectors,
+            referenced_sectors: total_sectors,
+            is_gzipped: false,
+            file_timestamp: None,
+            parse_failures: Vec::new(),
+            location_issues: Vec::new(),
         }
     }
 
@@ -129,6 +671,77 @@ impl Region {
         }
     }
 
+    /// Removes the chunk at the given chunk coordinates.
+    /// Returns `false` if no chunk of this region is at that position.
+    pub fn remove_chunk(&mut self, x: i32, z: i32) -> bool {
+        match self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.get_position() == Ok((x, z)))
+        {
+            Some(index) => {
+                self.remove_chunk_by_index(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Rebuilds the heightmaps of every chunk, see [`Chunk::recompute_heightmaps`].
+    /// Returns how many chunks got new heightmaps and how many could not be handled
+    pub fn recompute_heightmaps(&mut self) -> (usize, usize) {
+        let (mut fixed, mut failed) = (0, 0);
+        for chunk in &mut self.chunks {
+            match chunk.recompute_heightmaps() {
+                Ok(true) => fixed += 1,
+                Ok(false) => {}
+                Err(_) => failed += 1,
+            }
+        }
+        if fixed > 0 {
+            self.is_modified = true;
+        }
+        (fixed, failed)
+    }
+
+    /// Removes the POI records lying outside of their chunk, see [`Chunk::prune_poi_records`].
+    /// Returns how many were removed
+    pub fn prune_poi_records(&mut self) -> usize {
+        let pruned: usize = self.chunks.iter_mut().map(Chunk::prune_poi_records).sum();
+        if pruned > 0 {
+            self.is_modified = true;
+        }
+        pruned
+    }
+
+    /// Removes the entities of these types from the chunks of an entity region, see
+    /// [`Chunk::remove_entity_types`]. Returns how many were removed
+    pub fn remove_entity_types(&mut self, ids: &[String]) -> usize {
+        let removed: usize = self
+            .chunks
+            .iter_mut()
+            .map(|chunk| chunk.remove_entity_types(ids))
+            .sum();
+        if removed > 0 {
+            self.is_modified = true;
+        }
+        removed
+    }
+
+    /// Removes the sections lying entirely below `y` from every chunk, see [`Chunk::remove_sections_below`].
+    /// Returns how many were removed
+    pub fn remove_sections_below(&mut self, y: i32) -> usize {
+        let removed: usize = self
+            .chunks
+            .iter_mut()
+            .map(|chunk| chunk.remove_sections_below(y))
+            .sum();
+        if removed > 0 {
+            self.is_modified = true;
+        }
+        removed
+    }
+
     pub fn is_empty(&self) -> bool {
         self.chunks.is_empty()
     }
@@ -136,6 +749,253 @@ impl Region {
     pub fn is_modified(&self) -> bool {
         self.is_modified
     }
+
+    /// Tells if the file held nothing past an empty header, the smallest valid empty region, gzipped or not
+    pub fn is_header_only(&self) -> bool {
+        self.total_sectors == HEADER_SIZE / 4096
+            && self.chunks.is_empty()
+            && self.parse_failures.is_empty()
+            && self.location_issues.is_empty()
+            && !self.is_modified
+    }
+
+    /// Tells if the whole file was gzip-compressed on disk, like `.mca.gz` backups
+    pub fn is_gzipped(&self) -> bool {
+        self.is_gzipped
+    }
+
+    /// Chunks of the location table that could not be parsed, they are not part of the region any more
+    pub fn get_parse_failures(&self) -> &[ChunkParseFailure] {
+        &self.parse_failures
+    }
+
+    /// Every integrity problem found in the parsed file: locations out of the file, chunks sharing sectors,
+    /// chunks stored in the wrong slot and chunks that failed to parse, ordered by slot
+    pub fn validate(&self) -> Vec<RegionIssue> {
+        let parse_failures = self.parse_failures.iter().map(|failure| {
+            if failure.reason == CompressionScheme::UNSUPPORTED_ERROR {
+                RegionIssue::UnsupportedCompression(failure.clone())
+            } else {
+                RegionIssue::UnparsableChunk(failure.clone())
+            }
+        });
+        let mut issues: Vec<RegionIssue> = self
+            .location_issues
+            .iter()
+            .cloned()
+            .chain(parse_failures)
+            .collect();
+        issues.sort_by_key(RegionIssue::slot);
+        issues
+    }
+
+    /// Number of sectors in the original file that no chunk references.
+    /// Those are dead weight left behind by previous saves and are reclaimed when the region is rewritten.
+    pub fn get_orphaned_sectors(&self) -> usize {
+        self.sector_usage().orphaned_sectors()
+    }
+
+    pub fn sector_usage(&self) -> SectorUsage {
+        let header_sectors = HEADER_SIZE / 4096;
+        let chunk_sectors: usize = self
+            .chunks
+            .iter()
+            .map(|chunk| chunk.location.get_sector_count() as usize)
+            .sum();
+
+        SectorUsage {
+            used_sectors: header_sectors + chunk_sectors,
+            total_sectors: self.total_sectors,
+            referenced_sectors: self.referenced_sectors,
+        }
+    }
+}
+
+/// Regions are equal when they hold the same chunks: same positions, timestamps and NBT.
+/// Where the chunks sit in the file, how they are compressed and whether the region was modified are ignored,
+/// so a region read back after being written equals the original
+impl PartialEq for Region {
+    fn eq(&self, other: &Self) -> bool {
+        self.chunks.len() == other.chunks.len()
+            && self.chunks.iter().zip(&other.chunks).all(|(chunk, other)| {
+                chunk.get_position() == other.get_position()
+                    && chunk.location.get_timestamp() == other.location.get_timestamp()
+                    && chunk.nbt == other.nbt
+            })
+    }
+}
+
+/// Reads the region coordinates from a `r.<x>.<z>.mca` file name
+pub fn region_coordinates(region_file: &Path) -> Option<(i32, i32)> {
+    let file_name = region_file.file_name()?.to_str()?;
+    let mut parts = file_name.strip_prefix("r.")?.split('.');
+    let region_x = parts.next()?.parse().ok()?;
+    let region_z = parts.next()?.parse().ok()?;
+    Some((region_x, region_z))
+}
+
+/// Locates the `.mcc` files holding the oversized chunks of a region, they sit next to the region file
+/// and are named after the absolute coordinates of their chunk (`c.<x>.<z>.mcc`)
+struct ExternalChunkFiles<'a> {
+    directory: &'a Path,
+    region_x: i32,
+    region_z: i32,
+}
+
+impl<'a> ExternalChunkFiles<'a> {
+    fn for_region_file(region_file: &'a Path) -> Option<Self> {
+        let (region_x, region_z) = region_coordinates(region_file)?;
+        Some(Self {
+            directory: region_file.parent()?,
+            region_x,
+            region_z,
+        })
+    }
+
+    /// Path of the `.mcc` file of the chunk at `index` in the location table
+    fn chunk_file(&self, index: usize) -> PathBuf {
+        let (x, z) = self.chunk_position(index);
+        self.directory.join(format!("c.{x}.{z}.mcc"))
+    }
+
+    /// Absolute coordinates of the chunk at `index` in the location table
+    fn chunk_position(&self, index: usize) -> (i32, i32) {
+        (
+            self.region_x * 32 + (index % 32) as i32,
+            self.region_z * 32 + (index / 32) as i32,
+        )
+    }
+}
+
+/// Tells if a chunk at `position` belongs in `slot`. Without the coordinates of the region, only the position
+/// within the region can be checked
+fn is_in_slot(
+    position: (i32, i32),
+    slot: usize,
+    external_files: Option<&ExternalChunkFiles>,
+) -> bool {
+    match external_files {
+        Some(files) => files.chunk_position(slot) == position,
+        None => get_position_in_table(position.0, position.1) == 4 * slot,
+    }
+}
+
+/// Compresses a chunk for a rewrite, falling back to its original bytes if it cannot be encoded.
+/// Returns the bytes and whether they are the fallback
+fn compress_chunk(chunk: &Chunk, compression: CompressionLevel) -> (Vec<u8>, bool) {
+    match chunk.to_bytes(compression.to_compression()) {
+        Ok(bytes) => (bytes, false),
+        Err(_) => (chunk.to_original_bytes(), true),
+    }
+}
+
+/// Tables of a region file being serialized, filled chunk by chunk in file order
+struct RegionLayout {
+    location_table: [u8; 4096],
+    timestamp_table: [u8; 4096],
+    /// Length of the chunks placed so far, past the header
+    data_len: usize,
+    placed_slots: [bool; 1024],
+    fallback_timestamp: u32,
+    compression_fallbacks: usize,
+    unplaced_chunks: Vec<UnplacedChunk>,
+}
+
+impl RegionLayout {
+    fn new(fallback_timestamp: u32) -> Self {
+        Self {
+            location_table: [0; 4096],
+            timestamp_table: [0; 4096],
+            data_len: 0,
+            placed_slots: [false; 1024],
+            fallback_timestamp,
+            compression_fallbacks: 0,
+            unplaced_chunks: Vec::new(),
+        }
+    }
+
+    /// Gives the next chunk of the file its table entries. Returns its bytes padded to whole sectors, `None` when
+    /// the chunk cannot be placed and is left out
+    fn place(
+        &mut self,
+        index: usize,
+        chunk: &Chunk,
+        mut serialized: Vec<u8>,
+        is_fallback: bool,
+    ) -> Option<Vec<u8>> {
+        if is_fallback {
+            self.compression_fallbacks += 1;
+        }
+        align_vec_size(&mut serialized);
+
+        let new_position = (self.data_len + HEADER_SIZE) as u32;
+        let new_size = serialized.len() as u32;
+        // Some tools leave the timestamp at zero, which makes map tools render the chunk again on every run
+        let timestamp = match chunk.location.get_timestamp() {
+            0 => self.fallback_timestamp,
+            original_timestamp => original_timestamp,
+        };
+        let new_location = Location::new(new_position, new_size, timestamp);
+
+        let placement = chunk.get_position().and_then(|(x, z)| {
+            let location = new_location?;
+            let position_in_table = get_position_in_table(x, z);
+            if std::mem::replace(&mut self.placed_slots[position_in_table / 4], true) {
+                // Only one of them could be read back, the payload of the other would be orphaned
+                return Err("Another chunk takes the same slot of the location table");
+            }
+            Ok((location, position_in_table))
+        });
+        match placement {
+            Ok((new_location, position_in_table)) => {
+                let location_bytes = new_location.to_location_bytes();
+                self.location_table[position_in_table..(4 + position_in_table)]
+                    .copy_from_slice(&location_bytes);
+
+                let timestamp_bytes = new_location.to_timestamp_bytes();
+                self.timestamp_table[position_in_table..(4 + position_in_table)]
+                    .copy_from_slice(&timestamp_bytes);
+
+                self.data_len += serialized.len();
+                Some(serialized)
+            }
+            // Without a table entry the payload would only be orphaned sectors
+            Err(reason) => {
+                self.unplaced_chunks.push(UnplacedChunk {
+                    index,
+                    position: chunk.get_position().ok(),
+                    reason,
+                });
+                None
+            }
+        }
+    }
+}
+
+/// Overlaps between the sector ranges of the chunks of a location table, `(slot, sectors)` pairs
+fn overlapping_sectors(mut chunk_sectors: Vec<(usize, Range<usize>)>) -> Vec<RegionIssue> {
+    chunk_sectors.sort_by_key(|(slot, sectors)| (sectors.start, *slot));
+    let mut issues = Vec::new();
+    // Chunk reaching the furthest into the file so far
+    let mut furthest: Option<(usize, usize)> = None;
+    for (slot, sectors) in chunk_sectors {
+        match furthest {
+            Some((other_slot, end)) if sectors.start < end => {
+                issues.push(RegionIssue::OverlappingSectors { slot, other_slot });
+                if sectors.end > end {
+                    furthest = Some((slot, sectors.end));
+                }
+            }
+            _ => furthest = Some((slot, sectors.end)),
+        }
+    }
+    issues
+}
+
+fn unix_timestamp(time: SystemTime) -> u32 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs().min(u32::MAX as u64) as u32)
+        .unwrap_or(0)
 }
 
 fn align_vec_size(vec: &mut Vec<u8>) {
@@ -147,6 +1007,38 @@ fn get_position_in_table(x: i32, z: i32) -> usize {
     (4 * ((x & 31) + (z & 31) * 32)) as usize
 }
 
+/// Compresses a whole region file the way gzipped backups store it, `write` streaming the file to the encoder
+pub fn gzip_file(
+    compression: CompressionLevel,
+    write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), compression.to_compression());
+    write(&mut encoder)?;
+    encoder.finish()
+}
+
+fn gunzip(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decoder = GzDecoder::new(bytes);
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// Reads a region file, decompressing it first if the whole file is gzipped
+fn read_region_bytes(file_name: &Path) -> Result<(Vec<u8>, bool), ParseRegionError> {
+    let bytes = try_read_bytes(file_name).map_err(|_| ParseRegionError::ReadError)?;
+    decompress_region_bytes(bytes)
+}
+
+fn decompress_region_bytes(bytes: Vec<u8>) -> Result<(Vec<u8>, bool), ParseRegionError> {
+    if bytes.starts_with(&GZIP_MAGIC) {
+        // A corrupted archive is a read error rather than an invalid region, it must not get deleted
+        let bytes = gunzip(&bytes).map_err(|_| ParseRegionError::ReadError)?;
+        return Ok((bytes, true));
+    }
+    Ok((bytes, false))
+}
+
 fn try_read_bytes(file_path: &Path) -> std::io::Result<Vec<u8>> {
     let estimated_len = std::fs::metadata(file_path)
         .map(|m| m.len() as usize)
@@ -162,6 +1054,9 @@ fn try_read_bytes(file_path: &Path) -> std::io::Result<Vec<u8>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::nbt::tag::Tag;
+    use crate::region_loader::chunk_loader::compression_scheme::CompressionScheme;
+    use crate::region_loader::test_region::{TestChunk, corrupt_chunk};
 
     #[test]
     fn test_align_vec_size() {
@@ -178,13 +1073,435 @@ mod tests {
         assert_eq!(8192, vec_4097.len());
     }
 
+    #[test]
+    fn test_negative_region_coordinates() {
+        assert_eq!(
+            region_coordinates(Path::new("world/region/r.-1.-2.mca")),
+            Some((-1, -2))
+        );
+        assert_eq!(
+            region_coordinates(Path::new("r.-1000.7.mca.gz")),
+            Some((-1000, 7))
+        );
+        assert_eq!(region_coordinates(Path::new("r.-.0.mca")), None);
+        assert_eq!(region_coordinates(Path::new("c.-1.-2.mcc")), None);
+
+        for (region_x, region_z) in [(0, 0), (-1, -1), (-1, 2), (3, -2), (-30_000, -30_000)] {
+            let path = PathBuf::from(format!("region/r.{region_x}.{region_z}.mca"));
+            let files = ExternalChunkFiles::for_region_file(&path).unwrap();
+            for slot in 0..1024 {
+                // The slot of a chunk only depends on its position within the region, also below 0
+                let (x, z) = files.chunk_position(slot);
+                assert_eq!((x.div_euclid(32), z.div_euclid(32)), (region_x, region_z));
+                assert_eq!(get_position_in_table(x, z), 4 * slot, "chunk ({x}, {z})");
+                assert!(is_in_slot((x, z), slot, Some(&files)));
+                assert!(!is_in_slot((x - 32, z), slot, Some(&files)));
+            }
+        }
+        assert_eq!(get_position_in_table(-1, -1), 4 * 1023);
+        assert_eq!(get_position_in_table(-32, -33), 4 * (31 * 32));
+    }
+
+    #[test]
+    fn test_orphaned_sectors() {
+        let mut bytes = vec![0_u8; HEADER_SIZE];
+        // Chunk at sector 2 using 1 sector, sectors 3 and 4 are not referenced by anything
+        bytes[0..4].copy_from_slice(&[0, 0, 2, 1]);
+        bytes.resize(5 * 4096, 0);
+
+        let region = Region::from_bytes(&bytes).expect("Failed to parse region file");
+        assert_eq!(region.get_orphaned_sectors(), 2);
+        // The zeroed chunk does not parse, so only the header is used but its sector stays referenced
+        assert_eq!(
+            region.sector_usage(),
+            SectorUsage {
+                used_sectors: 2,
+                total_sectors: 5,
+                referenced_sectors: 3,
+            }
+        );
+
+        let header_only = Region::from_bytes(&[0_u8; HEADER_SIZE]).expect("Failed to parse header");
+        assert_eq!(header_only.get_orphaned_sectors(), 0);
+    }
+
+    #[test]
+    fn test_gzipped_region_file() {
+        let original_bytes = include_bytes!("../../test_files/r.-1.-1.mca");
+        let tmp_dir = std::env::temp_dir().join(format!("mwt_gzip_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let gzipped = tmp_dir.join("r.-1.-1.mca.gz");
+        std::fs::write(
+            &gzipped,
+            gzip_file(CompressionLevel::fast(), |encoder| {
+                encoder.write_all(original_bytes)
+            })
+            .unwrap(),
+        )
+        .unwrap();
+        let truncated = tmp_dir.join("truncated.mca.gz");
+        std::fs::write(&truncated, &std::fs::read(&gzipped).unwrap()[..1000]).unwrap();
+
+        let plain = Region::from_bytes(original_bytes).expect("Failed to parse region file");
+        let region = Region::from_file_name(&gzipped, &SchemeMap::default())
+            .expect("Failed to parse gzipped region file");
+        assert!(region.is_gzipped());
+        assert!(!plain.is_gzipped());
+        assert_eq!(region.get_chunks(), plain.get_chunks());
+        assert!(matches!(
+            Region::from_file_name(&truncated, &SchemeMap::default()),
+            Err(ParseRegionError::ReadError)
+        ));
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+
+    #[test]
+    fn test_header_only_files() {
+        // A region created by the game before any chunk was saved
+        let empty = Region::from_bytes(&[0_u8; HEADER_SIZE]).expect("Failed to parse header");
+        assert!(empty.is_empty());
+        assert!(!empty.is_modified());
+        assert_eq!(
+            empty.to_bytes(CompressionLevel::fast()).bytes,
+            vec![0_u8; HEADER_SIZE]
+        );
+
+        // A header whose locations all point past the end of the file
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/header_only.mca");
+        let truncated =
+            Region::from_file_name(&path, &SchemeMap::default()).expect("Failed to parse header");
+        assert!(truncated.is_empty());
+        assert!(
+            Region::sample_chunks(&path, 8, &SchemeMap::default())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_zero_timestamps_use_file_time() {
+        let mut bytes = include_bytes!("../../test_files/r.-1.-1.mca").to_vec();
+        bytes[4096..HEADER_SIZE].fill(0);
+        let path = std::env::temp_dir().join(format!("mwt_timestamps_{}.mca", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let file_time = unix_timestamp(std::fs::metadata(&path).unwrap().modified().unwrap());
+
+        let region = Region::from_file_name(&path, &SchemeMap::default())
+            .expect("Failed to parse region file");
+        let result = region.to_bytes(CompressionLevel::fast());
+        for chunk in Region::from_bytes(&result.bytes).unwrap().get_chunks() {
+            assert_eq!(chunk.location.get_timestamp(), file_time);
+        }
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_external_chunk() {
+        let original = Region::from_bytes(include_bytes!("../../test_files/r.-1.-1.mca")).unwrap();
+        let chunk = &original.get_chunks()[0];
+        let (x, z) = chunk.get_position().unwrap();
+        let frame = chunk.to_original_bytes();
+
+        // The region only keeps a 1 byte frame with the external flag, the payload moves to the .mcc file
+        let mut bytes = vec![0_u8; HEADER_SIZE];
+        let slot = get_position_in_table(x, z);
+        bytes[slot..slot + 4].copy_from_slice(&[0, 0, 2, 1]);
+        let stub = [0, 0, 0, 1, frame[4] | CompressionScheme::EXTERNAL_FLAG];
+        bytes.extend(stub);
+        bytes.resize(3 * 4096, 0);
+
+        let tmp_dir = std::env::temp_dir().join(format!("mwt_mcc_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let region_file = tmp_dir.join("r.-1.-1.mca");
+        std::fs::write(&region_file, &bytes).unwrap();
+        std::fs::write(tmp_dir.join(format!("c.{x}.{z}.mcc")), &frame[5..]).unwrap();
+
+        let region = Region::from_file_name(&region_file, &SchemeMap::default())
+            .expect("Failed to parse region file");
+        assert_eq!(region.get_chunk_count(), 1);
+        assert!(region.get_chunks()[0].is_external());
+        assert_eq!(region.get_chunks()[0].nbt, chunk.nbt);
+        // Without the .mcc file the chunk cannot be loaded
+        assert!(Region::from_bytes(&bytes).unwrap().is_empty());
+
+        let result = region.to_bytes(CompressionLevel::fast());
+        assert_eq!(&result.bytes[HEADER_SIZE..HEADER_SIZE + 5], &stub);
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+
+    #[test]
+    fn test_parse_failures_are_located() {
+        let mut bytes = include_bytes!("../../test_files/r.-1.-1.mca").to_vec();
+        // The chunk in slot 33, one chunk from the north-west corner on both axes, gets an unknown scheme
+        corrupt_chunk(&mut bytes, 33);
+
+        let tmp_dir =
+            std::env::temp_dir().join(format!("mwt_parse_failures_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let region_file = tmp_dir.join("r.-1.-1.mca");
+        std::fs::write(&region_file, &bytes).unwrap();
+        let region = Region::from_file_name(&region_file, &SchemeMap::default()).unwrap();
+        assert_eq!(region.get_chunk_count(), 1023);
+        let failure = ChunkParseFailure {
+            slot: 33,
+            position: Some((-31, -31)),
+            reason: "Unsupported compression scheme",
+        };
+        assert_eq!(region.get_parse_failures(), std::slice::from_ref(&failure));
+        assert_eq!(failure.to_string(), "chunk (-31, -31)");
+
+        // Without a file name, only the slot tells where the chunk is
+        let region = Region::from_bytes(&bytes).unwrap();
+        assert_eq!(region.get_parse_failures()[0].position, None);
+        assert_eq!(
+            region.get_parse_failures()[0].to_string(),
+            "chunk (1, 1) of the region"
+        );
+
+        std::fs::remove_dir_all(&tmp_dir).ok();
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut bytes = include_bytes!("../../test_files/r.-1.-1.mca").to_vec();
+        assert_eq!(Region::from_bytes(&bytes).unwrap().validate(), Vec::new());
+
+        let offset = Location::from_bytes(get_u32(&bytes, 4 * 33), 0).get_offset() as usize;
+        bytes[offset + 4] = 0x7f;
+        // Slot 34 points at the chunk of slot 35
+        let location_35: [u8; 4] = bytes[4 * 35..4 * 35 + 4].try_into().unwrap();
+        bytes[4 * 34..4 * 34 + 4].copy_from_slice(&location_35);
+        let total_sectors = bytes.len().div_ceil(4096);
+        bytes[4 * 40..4 * 40 + 4].copy_from_slice(&((total_sectors as u32) << 8 | 1).to_be_bytes());
+
+        let issues = Region::from_bytes(&bytes).unwrap().validate();
+        let failure = |slot, reason| ChunkParseFailure {
+            slot,
+            position: None,
+            reason,
+        };
+        assert_eq!(
+            issues,
+            vec![
+                RegionIssue::UnsupportedCompression(failure(
+                    33,
+                    CompressionScheme::UNSUPPORTED_ERROR
+                )),
+                RegionIssue::MisplacedChunk {
+                    slot: 34,
+                    position: (-29, -31),
+                },
+                RegionIssue::OverlappingSectors {
+                    slot: 35,
+                    other_slot: 34,
+                },
+                RegionIssue::LocationOutOfBounds {
+                    slot: 40,
+                    sectors: total_sectors..total_sectors + 1,
+                },
+                RegionIssue::UnparsableChunk(failure(40, "Chunk header out of bounds")),
+            ]
+        );
+        assert_eq!(
+            issues[2].to_string(),
+            "slot (3, 1) shares sectors with slot (2, 1)"
+        );
+    }
+
+    #[test]
+    fn test_salvage_region_with_zeroed_header() {
+        let mut bytes = include_bytes!("../../test_files/r.-1.-1.mca").to_vec();
+        let original = Region::from_bytes(&bytes).expect("Failed to parse region file");
+        bytes[0..HEADER_SIZE].fill(0);
+        assert!(Region::from_bytes(&bytes).unwrap().is_empty());
+
+        let (salvaged, added) = Region::salvage_bytes(&bytes, None, &SchemeMap::default()).unwrap();
+        assert!(salvaged.is_modified());
+        assert_eq!(added, original.get_chunk_count());
+        assert_eq!(salvaged.get_chunk_count(), original.get_chunk_count());
+        for chunk in original.get_chunks() {
+            let position = chunk.get_position();
+            let recovered = salvaged
+                .get_chunks()
+                .iter()
+                .find(|salvaged| salvaged.get_position() == position)
+                .expect("every chunk must be recovered");
+            assert_eq!(recovered.nbt, chunk.nbt);
+        }
+    }
+
+    #[test]
+    fn test_rewritten_region_has_no_orphaned_sectors() {
+        let original_bytes = include_bytes!("../../test_files/r.-1.-1.mca");
+        let region = Region::from_bytes(original_bytes).expect("Failed to parse region file");
+        let result = region.to_bytes(CompressionLevel::fast());
+
+        let parsed_again =
+            Region::from_bytes(&result.bytes).expect("Failed to parse serialized region file");
+        assert_eq!(parsed_again.get_orphaned_sectors(), 0);
+    }
+
+    #[test]
+    fn test_rewritten_region_has_no_trailing_padding() {
+        let original_bytes = include_bytes!("../../test_files/r.-1.-1.mca");
+        let mut region = Region::from_bytes(original_bytes).expect("Failed to parse region file");
+        // Chunks smaller than a sector still take a whole one each, and nothing more
+        region.chunks.truncate(3);
+        let result = region.to_bytes(CompressionLevel::fast());
+
+        let parsed_again =
+            Region::from_bytes(&result.bytes).expect("Failed to parse serialized region file");
+        let chunk_sectors: usize = parsed_again
+            .get_chunks()
+            .iter()
+            .map(|chunk| chunk.location.get_sector_count() as usize)
+            .sum();
+        assert_eq!(result.bytes.len(), HEADER_SIZE + chunk_sectors * 4096);
+        assert_eq!(
+            parsed_again.sector_usage().used_sectors * 4096,
+            result.bytes.len()
+        );
+
+        // The last chunk ends exactly at the end of the file
+        let last_end = parsed_again
+            .get_chunks()
+            .iter()
+            .map(|chunk| {
+                (chunk.location.get_sector_offset() + chunk.location.get_sector_count()) as usize
+            })
+            .max()
+            .unwrap();
+        assert_eq!(last_end * 4096, result.bytes.len());
+    }
+
+    #[test]
+    fn test_streamed_region_matches_to_bytes() {
+        let original_bytes = include_bytes!("../../test_files/r.-1.-1.mca");
+        let mut region = Region::from_bytes(original_bytes).expect("Failed to parse region file");
+        region.chunks.truncate(40);
+        let bytes = region.to_bytes(CompressionLevel::fast()).bytes;
+
+        let serialized = region.serialize(CompressionLevel::fast());
+        let mut streamed = Vec::new();
+        serialized.write_to(&mut streamed).unwrap();
+        assert_eq!(streamed, bytes);
+        assert_eq!(serialized.file_len(), bytes.len() as u64);
+
+        // More chunks than are compressed at once, the tables are written last
+        let mut written = std::io::Cursor::new(Vec::new());
+        let streamed = region
+            .serialize_to(CompressionLevel::fast(), &mut written)
+            .unwrap();
+        assert_eq!(written.into_inner(), bytes);
+        assert_eq!(streamed.len, bytes.len() as u64);
+        assert!(streamed.unplaced_chunks.is_empty());
+
+        // A single worker writes on its own thread, no writing thread is spawned
+        let single_thread = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let written = single_thread.install(|| {
+            let mut written = SameThreadWriter {
+                thread: std::thread::current().id(),
+                cursor: std::io::Cursor::new(Vec::new()),
+            };
+            region
+                .serialize_to(CompressionLevel::fast(), &mut written)
+                .unwrap();
+            written.cursor.into_inner()
+        });
+        assert_eq!(written, bytes);
+
+        let mut gunzipped = Vec::new();
+        let gzipped = gzip_file(CompressionLevel::fast(), |encoder| {
+            serialized.write_to(encoder)
+        })
+        .unwrap();
+        GzDecoder::new(&gzipped[..])
+            .read_to_end(&mut gunzipped)
+            .unwrap();
+        assert_eq!(gunzipped, bytes);
+    }
+
+    /// Writer failing when used from another thread than `thread`
+    struct SameThreadWriter {
+        thread: std::thread::ThreadId,
+        cursor: std::io::Cursor<Vec<u8>>,
+    }
+
+    impl Write for SameThreadWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            assert_eq!(std::thread::current().id(), self.thread);
+            self.cursor.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.cursor.flush()
+        }
+    }
+
+    impl Seek for SameThreadWriter {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.cursor.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_serialize_reports_unplaced_chunks() {
+        let no_position = Chunk::from_nbt(Tag::Compound {
+            name: Some(String::new()),
+            value: Vec::new(),
+        });
+        let region = Region::from_chunks(vec![
+            TestChunk::full(0, 0).to_chunk(),
+            no_position,
+            // Same slot as (0, 0) in a region file
+            TestChunk::full(32, 0).to_chunk(),
+        ]);
+        assert_eq!(Region::count_unplaceable(region.get_chunks()), 2);
+
+        let result = region.to_bytes(CompressionLevel::fast());
+        let reasons: Vec<_> = result
+            .unplaced_chunks
+            .iter()
+            .map(|unplaced| (unplaced.index, unplaced.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                (1, "No position for this chunk"),
+                (2, "Another chunk takes the same slot of the location table")
+            ]
+        );
+        assert_eq!(
+            result.unplaced_chunks[0].to_string(),
+            "chunk #1: No position for this chunk"
+        );
+
+        // Nothing of them is written, not even orphaned sectors
+        let parsed_again = Region::from_bytes(&result.bytes).unwrap();
+        assert_eq!(parsed_again.get_chunk_count(), 1);
+        assert_eq!(parsed_again.get_orphaned_sectors(), 0);
+
+        let mut written = std::io::Cursor::new(Vec::new());
+        let streamed = region
+            .serialize_to(CompressionLevel::fast(), &mut written)
+            .unwrap();
+        assert_eq!(streamed.unplaced_chunks, result.unplaced_chunks);
+        assert_eq!(written.into_inner(), result.bytes);
+    }
+
     #[test]
     fn test_small_region() {
         let original_bytes = include_bytes!("../../test_files/r.-1.-1.mca");
 
         let original_parsed_region_file = Region::from_bytes(original_bytes)
             .expect("Failed to parse original region file");
-        let result = original_parsed_region_file.to_bytes(Compression::fast());
+        let result = original_parsed_region_file.to_bytes(CompressionLevel::fast());
 
         // We cannot validate the header as the compression and chunk order in the payload may differ
         // resulting in a modification of the offset bytes, so as long as the re-parsed region file is
@@ -204,6 +1521,107 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_region_equality_ignores_layout() {
+        let original_bytes = include_bytes!("../../test_files/r.-1.-1.mca");
+        let original = Region::from_bytes(original_bytes).expect("Failed to parse region file");
+        let mut region = Region::from_bytes(original_bytes).expect("Failed to parse region file");
+        assert!(region == original);
+
+        let (x, z) = region.chunks[0].get_position().unwrap();
+        region.remove_chunk(x, z);
+        assert!(region != original);
+
+        // Read back, the chunks move to other sectors and the region is no longer modified
+        let parsed_again = Region::from_bytes(&region.to_bytes(CompressionLevel::best()).bytes)
+            .expect("Failed to parse serialized region file");
+        assert!(region.is_modified() && !parsed_again.is_modified());
+        assert!(
+            parsed_again
+                .chunks
+                .iter()
+                .zip(&region.chunks)
+                .any(|(a, b)| a.location != b.location)
+        );
+        assert!(parsed_again == region);
+    }
+
+    #[test]
+    fn test_compression_level_affects_output_size() {
+        let original_bytes = include_bytes!("../../test_files/r.-1.-1.mca");
+        let region = Region::from_bytes(original_bytes).expect("Failed to parse region file");
+
+        let stored = region.to_bytes(CompressionLevel::new(0).unwrap());
+        let best = region.to_bytes(CompressionLevel::best());
+
+        assert!(
+            stored.bytes.len() > best.bytes.len(),
+            "level 0 ({} bytes) must produce a larger region than level 9 ({} bytes)",
+            stored.bytes.len(),
+            best.bytes.len()
+        );
+    }
+
+    #[test]
+    fn test_remove_chunk_by_position() {
+        let original_bytes = include_bytes!("../../test_files/r.-1.-1.mca");
+        let mut region = Region::from_bytes(original_bytes).expect("Failed to parse region file");
+        let chunk_count = region.get_chunk_count();
+        let (x, z) = region.get_chunks()[0]
+            .get_position()
+            .expect("sample chunk must have a position");
+
+        assert!(!region.remove_chunk(i32::MAX, i32::MAX));
+        assert!(!region.is_modified());
+
+        assert!(region.remove_chunk(x, z));
+        assert!(region.is_modified());
+        assert_eq!(region.get_chunk_count(), chunk_count - 1);
+        assert!(
+            region
+                .get_chunks()
+                .iter()
+                .all(|chunk| chunk.get_position() != Ok((x, z)))
+        );
+
+        assert!(!region.remove_chunk(x, z));
+    }
+
+    #[test]
+    fn test_removed_chunks_leave_empty_table_entries() {
+        let original_bytes = include_bytes!("../../test_files/r.-1.-1.mca");
+        let mut region = Region::from_bytes(original_bytes).expect("Failed to parse region file");
+        let removed: Vec<(i32, i32)> = [0, 500]
+            .iter()
+            .map(|&index| region.get_chunks()[index].get_position().unwrap())
+            .collect();
+        for &(x, z) in &removed {
+            let slot = get_position_in_table(x, z);
+            // The sample stores a location and a timestamp for these chunks, a rebuild must not carry them over
+            assert_ne!(get_u32(original_bytes, slot), 0);
+            assert_ne!(get_u32(original_bytes, 4096 + slot), 0);
+            assert!(region.remove_chunk(x, z));
+        }
+
+        let bytes = region.to_bytes(CompressionLevel::fast()).bytes;
+        for &(x, z) in &removed {
+            let slot = get_position_in_table(x, z);
+            assert_eq!(&bytes[slot..slot + 4], &[0; 4], "location of ({x}, {z})");
+            assert_eq!(
+                &bytes[4096 + slot..4096 + slot + 4],
+                &[0; 4],
+                "timestamp of ({x}, {z})"
+            );
+        }
+        let occupied = (0..1024)
+            .filter(|index| get_u32(&bytes, 4 * index) != 0)
+            .count();
+        let timestamped = (0..1024)
+            .filter(|index| get_u32(&bytes, 4096 + 4 * index) != 0)
+            .count();
+        assert_eq!((occupied, timestamped), (1022, 1022));
+    }
+
     /// Byte-for-byte round-trip check on the *decompressed* NBT payload of every chunk
     /// in a real Minecraft region file. Guards against:
     ///   - `flate2` bumps producing a lossy deflate/inflate path
@@ -227,9 +1645,9 @@ mod tests {
         );
 
         for compression in [
-            Compression::fast(),
-            Compression::default(),
-            Compression::best(),
+            CompressionLevel::fast(),
+            CompressionLevel::default(),
+            CompressionLevel::best(),
         ] {
             let result = original_region.to_bytes(compression);
 
@@ -238,7 +1656,8 @@ mod tests {
                 "zlib should not fall back to gzip on a healthy sample"
             );
             assert_eq!(
-                result.header_write_failures, 0,
+                result.unplaced_chunks,
+                Vec::new(),
                 "every chunk should produce a valid header entry"
             );
 
@@ -272,4 +1691,69 @@ mod tests {
             }
         }
     }
+
+    /// Compares the parallel chunk compression of `to_bytes` with a single thread.
+    /// Run with `cargo test --release bench_to_bytes -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_to_bytes() {
+        let original_bytes = include_bytes!("../../test_files/r.-1.-1.mca");
+        let region = Region::from_bytes(original_bytes).expect("Failed to parse region file");
+        let single_thread = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+
+        const ITERS: u32 = 5;
+        let mut sequential_total = std::time::Duration::ZERO;
+        let mut parallel_total = std::time::Duration::ZERO;
+        for _ in 0..ITERS {
+            let start = std::time::Instant::now();
+            let sequential = single_thread.install(|| region.to_bytes(CompressionLevel::default()));
+            sequential_total += start.elapsed();
+
+            let start = std::time::Instant::now();
+            let parallel = region.to_bytes(CompressionLevel::default());
+            parallel_total += start.elapsed();
+
+            assert_eq!(sequential.bytes, parallel.bytes);
+        }
+
+        println!(
+            "\n{} chunks, 1 thread: {:?}/iter, {} threads: {:?}/iter",
+            region.get_chunk_count(),
+            sequential_total / ITERS,
+            rayon::current_num_threads(),
+            parallel_total / ITERS
+        );
+    }
+
+    /// Times the parsing of a whole region, decompression and NBT parsing of every chunk included.
+    /// Run with `cargo test --release bench_from_bytes -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_from_bytes() {
+        let original_bytes = include_bytes!("../../test_files/r.-1.-1.mca");
+
+        const ITERS: u32 = 5;
+        let mut total = std::time::Duration::ZERO;
+        let mut best = std::time::Duration::MAX;
+        let mut chunk_count = 0;
+        for _ in 0..ITERS {
+            let start = std::time::Instant::now();
+            let region = Region::from_bytes(original_bytes).expect("Failed to parse region file");
+            let elapsed = start.elapsed();
+            total += elapsed;
+            best = best.min(elapsed);
+            chunk_count = region.get_chunk_count();
+        }
+
+        println!(
+            "\n{} chunks, {:.2} MiB: {:?}/iter, best {:?}",
+            chunk_count,
+            original_bytes.len() as f64 / 1_048_576.0,
+            total / ITERS,
+            best
+        );
+    }
 }
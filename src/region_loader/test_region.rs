@@ -0,0 +1,172 @@
+//! Builds region files in memory for tests, since real worlds are too large to commit
+
+use crate::nbt::tag::Tag;
+use crate::region_loader::chunk_loader::chunk::Chunk;
+use crate::region_loader::compression_level::CompressionLevel;
+use crate::region_loader::get_u32::get_u32;
+use crate::region_loader::location::Location;
+use crate::region_loader::region::Region;
+
+/// `TAG_Int` named `name`
+pub(crate) fn int(name: &str, value: i32) -> Tag {
+    Tag::Int {
+        name: Some(name.to_string()),
+        value,
+    }
+}
+
+/// `TAG_Byte` named `name`
+pub(crate) fn byte(name: &str, value: i8) -> Tag {
+    Tag::Byte {
+        name: Some(name.to_string()),
+        value,
+    }
+}
+
+/// `TAG_String` named `name`
+pub(crate) fn string(name: &str, value: &str) -> Tag {
+    Tag::String {
+        name: Some(name.to_string()),
+        value: value.to_string(),
+    }
+}
+
+/// `TAG_Compound` named `name`, the root of a chunk being named `""`
+pub(crate) fn compound(name: &str, value: Vec<Tag>) -> Tag {
+    Tag::Compound {
+        name: Some(name.to_string()),
+        value,
+    }
+}
+
+/// Unnamed `TAG_Compound`, as stored in a list
+pub(crate) fn element(value: Vec<Tag>) -> Tag {
+    Tag::Compound { name: None, value }
+}
+
+/// `TAG_List` of compounds named `name`
+pub(crate) fn list(name: &str, value: Vec<Tag>) -> Tag {
+    Tag::List {
+        name: Some(name.to_string()),
+        value,
+        tag_type: 10,
+    }
+}
+
+/// Description of a 1.18+ chunk without any section
+pub(crate) struct TestChunk {
+    pub position: (i32, i32),
+    pub status: &'static str,
+    pub inhabited_time: i64,
+    pub data_version: i32,
+    /// Ids of the block entities of the chunk, all placed at its origin
+    pub block_entities: Vec<&'static str>,
+}
+
+impl TestChunk {
+    /// Fully generated and never inhabited chunk at the given chunk coordinates
+    pub fn full(x: i32, z: i32) -> Self {
+        Self {
+            position: (x, z),
+            status: "minecraft:full",
+            inhabited_time: 0,
+            data_version: 3465,
+            block_entities: Vec::new(),
+        }
+    }
+
+    pub fn status(mut self, status: &'static str) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn inhabited_time(mut self, inhabited_time: i64) -> Self {
+        self.inhabited_time = inhabited_time;
+        self
+    }
+
+    pub fn block_entity(mut self, id: &'static str) -> Self {
+        self.block_entities.push(id);
+        self
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let block_entities = self
+            .block_entities
+            .iter()
+            .map(|id| {
+                element(vec![
+                    string("id", id),
+                    int("x", self.position.0 * 16),
+                    int("y", 64),
+                    int("z", self.position.1 * 16),
+                ])
+            })
+            .collect();
+        Chunk::from_nbt(compound(
+            "",
+            vec![
+                int("DataVersion", self.data_version),
+                int("xPos", self.position.0),
+                int("zPos", self.position.1),
+                string("Status", self.status),
+                Tag::Long {
+                    name: Some("InhabitedTime".to_string()),
+                    value: self.inhabited_time,
+                },
+                list("sections", Vec::new()),
+                list("block_entities", block_entities),
+            ],
+        ))
+    }
+}
+
+/// Bytes of a region file holding these chunks, as written by [`Region::to_bytes`]
+pub(crate) fn test_region_bytes(chunks: &[TestChunk]) -> Vec<u8> {
+    Region::from_chunks(chunks.iter().map(TestChunk::to_chunk).collect())
+        .to_bytes(CompressionLevel::fast())
+        .bytes
+}
+
+/// Gives the chunk in `slot` of the location table of region bytes an unknown compression scheme, so that it fails
+/// to parse
+pub(crate) fn corrupt_chunk(bytes: &mut [u8], slot: usize) {
+    let offset = Location::from_bytes(get_u32(bytes, 4 * slot), 0).get_offset() as usize;
+    bytes[offset + 4] = 0x7f;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_loader::chunk_loader::compression_scheme::SchemeMap;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_generated_region_round_trip() {
+        let chunks = [
+            TestChunk::full(-32, -32),
+            TestChunk::full(-1, -1).status("minecraft:features"),
+            TestChunk::full(-5, -20)
+                .status("minecraft:noise")
+                .inhabited_time(1200),
+        ];
+        let region = Region::from_reader(
+            Cursor::new(test_region_bytes(&chunks)),
+            &SchemeMap::default(),
+        )
+        .unwrap();
+
+        assert_eq!(region.get_chunk_count(), 3);
+        assert_eq!(region.get_orphaned_sectors(), 0);
+        // Parsing goes through the location table, not in the order the chunks were written
+        for (expected, should_delete) in chunks.iter().zip([false, true, false]) {
+            let chunk = region
+                .get_chunks()
+                .iter()
+                .find(|chunk| chunk.get_position() == Ok(expected.position))
+                .unwrap();
+            assert_eq!(chunk.nbt.to_bytes(), expected.to_chunk().nbt.to_bytes());
+            assert_eq!(chunk.should_delete(), should_delete);
+        }
+    }
+}
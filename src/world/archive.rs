@@ -0,0 +1,424 @@
+use crate::world::get_region_files::is_mca_file;
+use crate::world::scan_options::ScanOptions;
+use flate2::read::{DeflateDecoder, GzDecoder};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Read, Result, Seek, SeekFrom};
+use std::path::Path;
+
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4b50;
+const ZIP_CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP_END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR: u32 = 0x0706_4b50;
+const TAR_BLOCK: usize = 512;
+
+/// Tells if the path is a world archive that can be read in place (`.zip`, `.tar`, `.tar.gz` or `.tgz`)
+pub fn is_archive(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    [".zip", ".tar", ".tar.gz", ".tgz"]
+        .iter()
+        .any(|extension| name.ends_with(extension))
+}
+
+/// Calls `visit` with the path and content of every region file (`.../region/*.mca`) accepted by the scan options.
+/// Paths are the archive path joined with the entry name, so dimensions are detected as for extracted worlds.
+///
+/// Zip entries are visited in parallel, a tar stream has to be read sequentially.
+pub fn visit_region_entries<R: Send>(
+    archive: &Path,
+    scan_options: &ScanOptions,
+    visit: impl Fn(&Path, &mut dyn Read) -> R + Sync,
+) -> Result<Vec<R>> {
+    let is_region_entry = |name: &str| {
+        let path = Path::new(name);
+        is_mca_file(path)
+            && path.parent().and_then(|dir| dir.file_name()) == Some("region".as_ref())
+            && scan_options.accepts(path)
+    };
+
+    let name = archive.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") {
+        let entries: Vec<ZipEntry> = read_zip_entries(archive)?
+            .into_iter()
+            .filter(|entry| is_region_entry(&entry.name))
+            .collect();
+        entries
+            .into_par_iter()
+            .map(|entry| {
+                let mut reader = entry.open(archive)?;
+                Ok(visit(&archive.join(&entry.name), &mut reader))
+            })
+            .collect()
+    } else {
+        let file = BufReader::new(File::open(archive)?);
+        let mut stream: Box<dyn Read> = if name.ends_with(".tar") {
+            Box::new(file)
+        } else {
+            Box::new(GzDecoder::new(file))
+        };
+        let mut results = Vec::new();
+        visit_tar_entries(&mut stream, |entry_name, reader| {
+            if is_region_entry(entry_name) {
+                results.push(visit(&archive.join(entry_name), reader));
+            }
+        })?;
+        Ok(results)
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_entry_name(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// Reads the ustar numeric fields, in octal text or in the base-256 form used for large values
+fn read_tar_number(field: &[u8]) -> u64 {
+    if field.first().is_some_and(|b| b & 0x80 != 0) {
+        return field[1..]
+            .iter()
+            .fold(u64::from(field[0] & 0x7f), |value, &b| {
+                (value << 8) | u64::from(b)
+            });
+    }
+    let text = read_entry_name(field);
+    u64::from_str_radix(text.trim(), 8).unwrap_or(0)
+}
+
+/// Walks the regular files of a tar stream, handling ustar prefixes, GNU long names and pax paths
+fn visit_tar_entries(
+    stream: &mut dyn Read,
+    mut visit: impl FnMut(&str, &mut dyn Read),
+) -> Result<()> {
+    let mut long_name: Option<String> = None;
+    let mut header = [0_u8; TAR_BLOCK];
+    loop {
+        match stream.read_exact(&mut header) {
+            Ok(()) => {}
+            // Some writers omit the two zero blocks closing the archive
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        if header.iter().all(|&b| b == 0) {
+            return Ok(());
+        }
+
+        let size = read_tar_number(&header[124..136]);
+        let padding = (TAR_BLOCK as u64 - size % TAR_BLOCK as u64) % TAR_BLOCK as u64;
+        let type_flag = header[156];
+        let mut data = stream.take(size);
+        match type_flag {
+            b'L' | b'x' => {
+                let mut bytes = Vec::new();
+                data.read_to_end(&mut bytes)?;
+                long_name = if type_flag == b'L' {
+                    Some(read_entry_name(&bytes))
+                } else {
+                    pax_path(&bytes).or(long_name)
+                };
+            }
+            b'0' | 0 => {
+                let name = long_name.take().unwrap_or_else(|| {
+                    let prefix = read_entry_name(&header[345..500]);
+                    let name = read_entry_name(&header[0..100]);
+                    if prefix.is_empty() {
+                        name
+                    } else {
+                        format!("{prefix}/{name}")
+                    }
+                });
+                visit(&name, &mut data);
+            }
+            _ => long_name = None,
+        }
+        // Skip whatever the visitor did not read, then the padding up to the next block
+        std::io::copy(&mut data, &mut std::io::sink())?;
+        std::io::copy(&mut stream.take(padding), &mut std::io::sink())?;
+    }
+}
+
+/// Extracts the `path` record of a pax extended header (`<length> path=<value>\n`)
+fn pax_path(bytes: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .filter_map(|record| {
+            record
+                .split_once(' ')
+                .map(|(_, keyword_value)| keyword_value)
+        })
+        .find_map(|keyword_value| keyword_value.strip_prefix("path=").map(str::to_string))
+}
+
+struct ZipEntry {
+    name: String,
+    method: u16,
+    compressed_size: u64,
+    local_header_offset: u64,
+}
+
+impl ZipEntry {
+    fn open(&self, archive: &Path) -> Result<Box<dyn Read>> {
+        let mut file = File::open(archive)?;
+        file.seek(SeekFrom::Start(self.local_header_offset))?;
+        let mut header = [0_u8; 30];
+        file.read_exact(&mut header)?;
+        if u32_at(&header, 0) != ZIP_LOCAL_HEADER {
+            return Err(invalid_data("Invalid zip local header"));
+        }
+        let skipped = u64::from(u16_at(&header, 26)) + u64::from(u16_at(&header, 28));
+        file.seek(SeekFrom::Current(skipped as i64))?;
+
+        let data = BufReader::new(file).take(self.compressed_size);
+        match self.method {
+            0 => Ok(Box::new(data)),
+            8 => Ok(Box::new(DeflateDecoder::new(data))),
+            _ => Err(invalid_data("Unsupported zip compression method")),
+        }
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// Reads the central directory of a zip file, including zip64 archives (over 4 GiB or 65535 entries)
+fn read_zip_entries(archive: &Path) -> Result<Vec<ZipEntry>> {
+    let mut file = File::open(archive)?;
+    let file_len = file.metadata()?.len();
+
+    // The end of central directory record is followed by a comment of at most 65535 bytes
+    let tail_len = file_len.min(22 + 65535 + 20);
+    file.seek(SeekFrom::Start(file_len - tail_len))?;
+    let mut tail = vec![0_u8; tail_len as usize];
+    file.read_exact(&mut tail)?;
+    let eocd = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| u32_at(&tail, i) == ZIP_END_OF_CENTRAL_DIRECTORY)
+        .ok_or_else(|| invalid_data("Not a zip file"))?;
+
+    let mut entry_count = u64::from(u16_at(&tail, eocd + 10));
+    let mut directory_size = u64::from(u32_at(&tail, eocd + 12));
+    let mut directory_offset = u64::from(u32_at(&tail, eocd + 16));
+    if eocd >= 20 && u32_at(&tail, eocd - 20) == ZIP64_LOCATOR {
+        file.seek(SeekFrom::Start(u64_at(&tail, eocd - 20 + 8)))?;
+        let mut record = [0_u8; 56];
+        file.read_exact(&mut record)?;
+        if u32_at(&record, 0) != ZIP64_END_OF_CENTRAL_DIRECTORY {
+            return Err(invalid_data("Invalid zip64 end of central directory"));
+        }
+        entry_count = u64_at(&record, 32);
+        directory_size = u64_at(&record, 40);
+        directory_offset = u64_at(&record, 48);
+    }
+
+    file.seek(SeekFrom::Start(directory_offset))?;
+    let mut directory = Vec::new();
+    (&mut file)
+        .take(directory_size)
+        .read_to_end(&mut directory)?;
+
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    for _ in 0..entry_count {
+        if offset + 46 > directory.len() || u32_at(&directory, offset) != ZIP_CENTRAL_HEADER {
+            return Err(invalid_data("Invalid zip central directory"));
+        }
+        let name_len = usize::from(u16_at(&directory, offset + 28));
+        let extra_len = usize::from(u16_at(&directory, offset + 30));
+        let comment_len = usize::from(u16_at(&directory, offset + 32));
+        let name_end = offset + 46 + name_len;
+        let extra_end = name_end + extra_len;
+        if extra_end > directory.len() {
+            return Err(invalid_data("Invalid zip central directory"));
+        }
+
+        let mut entry = ZipEntry {
+            name: String::from_utf8_lossy(&directory[offset + 46..name_end]).into_owned(),
+            method: u16_at(&directory, offset + 10),
+            compressed_size: u64::from(u32_at(&directory, offset + 20)),
+            local_header_offset: u64::from(u32_at(&directory, offset + 42)),
+        };
+        let uncompressed_is_zip64 = u32_at(&directory, offset + 24) == u32::MAX;
+        apply_zip64_extra(
+            &mut entry,
+            uncompressed_is_zip64,
+            &directory[name_end..extra_end],
+        );
+        entries.push(entry);
+
+        offset = extra_end + comment_len;
+    }
+
+    Ok(entries)
+}
+
+/// Replaces the saturated 32-bit fields of an entry by the values of its zip64 extra field.
+/// The field only holds the saturated values, in the order uncompressed size, compressed size, offset.
+fn apply_zip64_extra(entry: &mut ZipEntry, uncompressed_is_zip64: bool, mut extra: &[u8]) {
+    while extra.len() >= 4 {
+        let id = u16_at(extra, 0);
+        let len = usize::from(u16_at(extra, 2)).min(extra.len() - 4);
+        if id == 0x0001 {
+            let mut values = extra[4..4 + len]
+                .chunks_exact(8)
+                .map(|value| u64_at(value, 0));
+            if uncompressed_is_zip64 {
+                values.next();
+            }
+            if entry.compressed_size == u64::from(u32::MAX) {
+                entry.compressed_size = values.next().unwrap_or(entry.compressed_size);
+            }
+            if entry.local_header_offset == u64::from(u32::MAX) {
+                entry.local_header_offset = values.next().unwrap_or(entry.local_header_offset);
+            }
+        }
+        extra = &extra[4 + len..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::read::DeflateEncoder;
+
+    fn tar_entry(name: &str, content: &[u8]) -> Vec<u8> {
+        let mut header = [0_u8; TAR_BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", content.len()).as_bytes());
+        header[156] = b'0';
+        let mut entry = header.to_vec();
+        entry.extend_from_slice(content);
+        entry.resize(entry.len().div_ceil(TAR_BLOCK) * TAR_BLOCK, 0);
+        entry
+    }
+
+    /// Zip with one stored and one deflated entry
+    fn zip_file(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = Vec::new();
+        let mut directory = Vec::new();
+        for (i, (name, content)) in entries.iter().enumerate() {
+            let (method, data) = if i % 2 == 0 {
+                (0_u16, content.to_vec())
+            } else {
+                let mut data = Vec::new();
+                DeflateEncoder::new(*content, Compression::fast())
+                    .read_to_end(&mut data)
+                    .unwrap();
+                (8, data)
+            };
+            let offset = zip.len() as u32;
+            zip.extend(ZIP_LOCAL_HEADER.to_le_bytes());
+            zip.extend([0; 4]);
+            zip.extend(method.to_le_bytes());
+            zip.extend([0; 8]);
+            zip.extend((data.len() as u32).to_le_bytes());
+            zip.extend((content.len() as u32).to_le_bytes());
+            zip.extend((name.len() as u16).to_le_bytes());
+            zip.extend([0; 2]);
+            zip.extend(name.as_bytes());
+            zip.extend(&data);
+
+            directory.extend(ZIP_CENTRAL_HEADER.to_le_bytes());
+            directory.extend([0; 6]);
+            directory.extend(method.to_le_bytes());
+            directory.extend([0; 8]);
+            directory.extend((data.len() as u32).to_le_bytes());
+            directory.extend((content.len() as u32).to_le_bytes());
+            directory.extend((name.len() as u16).to_le_bytes());
+            directory.extend([0; 12]);
+            directory.extend(offset.to_le_bytes());
+            directory.extend(name.as_bytes());
+        }
+        let directory_offset = zip.len() as u32;
+        zip.extend(&directory);
+        zip.extend(ZIP_END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        zip.extend([0; 4]);
+        zip.extend((entries.len() as u16).to_le_bytes());
+        zip.extend((entries.len() as u16).to_le_bytes());
+        zip.extend((directory.len() as u32).to_le_bytes());
+        zip.extend(directory_offset.to_le_bytes());
+        zip.extend([0; 2]);
+        zip
+    }
+
+    fn read_all(path: &Path, reader: &mut dyn Read) -> (String, Vec<u8>) {
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).unwrap();
+        (path.to_string_lossy().into_owned(), content)
+    }
+
+    #[test]
+    fn test_is_archive() {
+        assert!(is_archive(Path::new("backups/world.zip")));
+        assert!(is_archive(Path::new("backups/world.TAR.GZ")));
+        assert!(is_archive(Path::new("backups/world.tgz")));
+        assert!(!is_archive(Path::new("saves/world")));
+    }
+
+    #[test]
+    fn test_tar_gz_region_entries() {
+        let mut tar = tar_entry("world/level.dat", b"level");
+        tar.extend(tar_entry("world/region/r.0.0.mca", b"region 0 0"));
+        tar.extend(tar_entry("world/entities/r.0.0.mca", b"entities"));
+        tar.extend(tar_entry("world/DIM-1/region/r.1.0.mca", &[7; 600]));
+        tar.extend([0; 2 * TAR_BLOCK]);
+        let mut tar_gz = Vec::new();
+        flate2::read::GzEncoder::new(&tar[..], Compression::fast())
+            .read_to_end(&mut tar_gz)
+            .unwrap();
+        let archive =
+            std::env::temp_dir().join(format!("mwt_archive_{}.tar.gz", std::process::id()));
+        std::fs::write(&archive, tar_gz).unwrap();
+
+        let entries = visit_region_entries(&archive, &ScanOptions::default(), read_all).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].0,
+            archive.join("world/region/r.0.0.mca").to_string_lossy()
+        );
+        assert_eq!(entries[0].1, b"region 0 0");
+        assert_eq!(
+            entries[1].0,
+            archive
+                .join("world/DIM-1/region/r.1.0.mca")
+                .to_string_lossy()
+        );
+        assert_eq!(entries[1].1, vec![7; 600]);
+
+        std::fs::remove_file(&archive).ok();
+    }
+
+    #[test]
+    fn test_zip_region_entries() {
+        let zip = zip_file(&[
+            ("world/region/r.0.0.mca", b"stored region"),
+            ("world/region/r.0.1.mca", &[3; 5000]),
+            ("world/level.dat", b"level"),
+        ]);
+        let archive = std::env::temp_dir().join(format!("mwt_archive_{}.zip", std::process::id()));
+        std::fs::write(&archive, zip).unwrap();
+
+        let entries = visit_region_entries(&archive, &ScanOptions::default(), read_all).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].1, b"stored region");
+        assert_eq!(entries[1].1, vec![3; 5000]);
+
+        std::fs::remove_file(&archive).ok();
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dimension {
+    Overworld,
+    Nether,
+    End,
+}
+
+impl Dimension {
+    /// Vanilla stores the nether in `DIM-1/` and the end in `DIM1/`, the overworld regions are at the world root.
+    /// Server worlds (`world_nether/DIM-1/region`) use the same folder names.
+    pub fn from_region_path(region_file_path: &Path) -> Self {
+        let in_folder = |folder: &str| {
+            region_file_path
+                .components()
+                .any(|component| component.as_os_str() == folder)
+        };
+
+        if in_folder("DIM-1") {
+            Dimension::Nether
+        } else if in_folder("DIM1") {
+            Dimension::End
+        } else {
+            Dimension::Overworld
+        }
+    }
+
+    /// Reads a dimension name as given on the command line: `overworld`, `nether` or `end`,
+    /// optionally with the `minecraft:` namespace and `the_` prefix of the game's dimension ids
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.strip_prefix("minecraft:").unwrap_or(name);
+        match name.strip_prefix("the_").unwrap_or(name) {
+            "overworld" => Some(Dimension::Overworld),
+            "nether" => Some(Dimension::Nether),
+            "end" => Some(Dimension::End),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_region_path() {
+        assert_eq!(
+            Dimension::from_region_path(Path::new("world/region/r.0.0.mca")),
+            Dimension::Overworld
+        );
+        assert_eq!(
+            Dimension::from_region_path(Path::new("world/DIM-1/region/r.0.0.mca")),
+            Dimension::Nether
+        );
+        assert_eq!(
+            Dimension::from_region_path(Path::new("world_the_end/DIM1/region/r.0.0.mca")),
+            Dimension::End
+        );
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Dimension::from_name("nether"), Some(Dimension::Nether));
+        assert_eq!(
+            Dimension::from_name("minecraft:the_nether"),
+            Some(Dimension::Nether)
+        );
+        assert_eq!(Dimension::from_name("the_end"), Some(Dimension::End));
+        assert_eq!(
+            Dimension::from_name("overworld"),
+            Some(Dimension::Overworld)
+        );
+        assert_eq!(Dimension::from_name("DIM-1"), None);
+    }
+}
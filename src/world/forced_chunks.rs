@@ -0,0 +1,72 @@
+use crate::nbt::tag::Tag;
+use crate::world::level_dat::read_gzipped_nbt;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// File of a dimension directory listing the chunks force-loaded with `/forceload`
+const FORCED_CHUNKS_FILE: &str = "data/chunks.dat";
+
+/// `chunks.dat` of the dimension a region file belongs to: `<world>/data` for the overworld, `<world>/DIM-1/data`
+/// for the nether and `<world>/DIM1/data` for the end, next to the `region` directory
+pub fn forced_chunks_file(region_file: &Path) -> Option<PathBuf> {
+    Some(region_file.parent()?.parent()?.join(FORCED_CHUNKS_FILE))
+}
+
+/// Reads the chunks force-loaded in the dimension of a region file, `None` if the dimension has no `chunks.dat`
+/// or it cannot be read
+pub fn read_forced_chunks(region_file: &Path) -> Option<HashSet<(i32, i32)>> {
+    let chunks_dat = forced_chunks_file(region_file)?;
+    if !chunks_dat.is_file() {
+        return None;
+    }
+    Some(forced_chunks(&read_gzipped_nbt(&chunks_dat)?))
+}
+
+/// Positions of the `data.Forced` list, where each long packs the x coordinate of a chunk in its low 32 bits and
+/// the z coordinate in the high ones
+fn forced_chunks(chunks_dat: &Tag) -> HashSet<(i32, i32)> {
+    chunks_dat
+        .find_tag("data")
+        .and_then(|data| data.find_tag("Forced"))
+        .and_then(|tag| tag.get_long_array())
+        .unwrap_or_default()
+        .iter()
+        .map(|&packed| (packed as i32, (packed >> 32) as i32))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::read::GzEncoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_read_forced_chunks() {
+        let world = std::env::temp_dir().join(format!("mwt_forced_chunks_{}", std::process::id()));
+        std::fs::create_dir_all(world.join("DIM-1/data")).unwrap();
+        let pack = |x: i32, z: i32| i64::from(x as u32) | i64::from(z) << 32;
+        let chunks_dat = Tag::Compound {
+            name: Some(String::new()),
+            value: vec![Tag::Compound {
+                name: Some("data".to_string()),
+                value: vec![Tag::LongArray {
+                    name: Some("Forced".to_string()),
+                    value: vec![pack(3, -4), pack(-1, -1), pack(-30_000_000, 7)],
+                }],
+            }],
+        };
+        let mut compressed = Vec::new();
+        GzEncoder::new(&chunks_dat.to_bytes()[..], Compression::fast())
+            .read_to_end(&mut compressed)
+            .unwrap();
+        std::fs::write(world.join("DIM-1/data/chunks.dat"), compressed).unwrap();
+
+        let forced = read_forced_chunks(&world.join("DIM-1/region/r.0.0.mca")).unwrap();
+        assert_eq!(forced, HashSet::from([(3, -4), (-1, -1), (-30_000_000, 7)]));
+        assert_eq!(read_forced_chunks(&world.join("region/r.0.0.mca")), None);
+
+        std::fs::remove_dir_all(&world).ok();
+    }
+}
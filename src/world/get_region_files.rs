@@ -1,38 +1,368 @@
+use crate::world::scan_options::ScanOptions;
 use crate::world::validate::validate_worlds;
+use crate::{info, warn};
 use std::error::Error;
 use std::path::{Path, PathBuf};
 
-pub fn get_region_files(world_paths: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
-    let worlds = validate_worlds(world_paths)?;
-    Ok(worlds
+/// Gathers the region files of the given worlds.
+/// A path pointing directly to a `.mca` (or gzipped `.mca.gz`) file is taken as a single region file instead of a world.
+/// Only files named like region files are returned, see [`is_mca_file`]
+pub fn get_region_files(
+    world_paths: &[PathBuf],
+    options: &ScanOptions,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let (region_files, world_paths): (Vec<PathBuf>, Vec<PathBuf>) = world_paths
         .iter()
-        .flat_map(|world| get_region_files_from_world(world))
-        .collect::<Vec<_>>())
+        .cloned()
+        .partition(|path| path.is_file() && has_mca_extension(path));
+    let region_files = region_files.into_iter().filter(|path| {
+        let is_region_file = is_mca_file(path);
+        if !is_region_file {
+            warn_not_region_file(path);
+        }
+        is_region_file
+    });
+    // Worlds hold no other worlds, so detection can search as deep as the folders go
+    let max_depth = if options.detect_worlds {
+        usize::MAX
+    } else {
+        options.max_depth
+    };
+    let world_paths: Vec<PathBuf> = world_paths
+        .into_iter()
+        .flat_map(|path| discover_worlds(path, max_depth))
+        .collect();
+    let worlds = validate_worlds(&world_paths)?;
+    if options.detect_worlds {
+        for world in worlds {
+            info!("Found the world {:?}", world);
+        }
+    }
+
+    let mut region_files = region_files
+        .chain(
+            worlds
+                .iter()
+                .flat_map(|world| get_region_files_from_world(world, options.detect_worlds)),
+        )
+        .filter(|region_file| options.accepts(region_file) && options.is_recent(region_file))
+        .collect::<Vec<_>>();
+
+    if let Some(trial_regions) = options.trial_regions {
+        // Directories are listed in no particular order, sorting makes the trial pick the same files every run
+        region_files.sort();
+        if region_files.len() > trial_regions {
+            info!(
+                "Trial run on the first {trial_regions} of {} region file(s)",
+                region_files.len()
+            );
+            region_files.truncate(trial_regions);
+        }
+    }
+    Ok(region_files)
+}
+
+/// Finds the worlds at most `max_depth` levels below `path`, without descending into a world once found.
+/// Paths holding no world are returned as is, so that validation reports them.
+fn discover_worlds(path: PathBuf, max_depth: usize) -> Vec<PathBuf> {
+    let mut worlds = Vec::new();
+    let mut directories = vec![(path.clone(), 0)];
+    while let Some((directory, depth)) = directories.pop() {
+        if directory.join("level.dat").is_file() {
+            worlds.push(directory);
+        } else if depth < max_depth {
+            // Reversed so that the stack pops them in path order
+            let children = subdirectories(&directory);
+            directories.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+        }
+    }
+
+    if worlds.is_empty() {
+        worlds.push(path);
+    }
+    worlds
+}
+
+/// Warns when a scan found nothing, which otherwise looks like a successful run with a zero summary
+pub fn warn_if_no_region_files(world_paths: &[PathBuf], region_files_count: usize) {
+    if region_files_count > 0 {
+        return;
+    }
+    let paths = world_paths
+        .iter()
+        .map(|path| format!("{path:?}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    warn!(
+        "No region files found under {paths}. Expected world folders laid out as <world>/region/r.X.Z.mca \
+         (plus DIM-1/region and DIM1/region), or paths to .mca files. Check the path and the glob filters"
+    );
+}
+
+fn has_mca_extension(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.ends_with(".mca") || name.ends_with(".mca.gz"))
+        .unwrap_or(false)
 }
 
-fn get_region_files_from_world(world_dir: &Path) -> Vec<PathBuf> {
+/// Tells if the file is named like a region file, `r.<x>.<z>.mca` or gzipped `r.<x>.<z>.mca.gz`. Everything else
+/// that sits in region directories, such as backups (`r.0.0.mca.bak`), temporary files or copies, is never touched:
+/// a file that fails to parse as a region would otherwise be deleted as an invalid region
+pub(crate) fn is_mca_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let coordinates = name
+        .strip_suffix(".mca")
+        .or_else(|| name.strip_suffix(".mca.gz"))
+        .and_then(|name| name.strip_prefix("r."));
+    match coordinates.and_then(|coordinates| coordinates.split_once('.')) {
+        Some((x, z)) => x.parse::<i32>().is_ok() && z.parse::<i32>().is_ok(),
+        None => false,
+    }
+}
+
+/// Tells if the file name mentions `.mca` without being a region file name, such as a backup or a copy of one
+fn looks_like_mca_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.contains(".mca"))
+        && !is_mca_file(path)
+}
+
+fn warn_not_region_file(path: &Path) {
+    warn!(
+        "Skipping {:?}, it is not named like a region file (r.X.Z.mca)",
+        path
+    );
+}
+
+/// With `custom_dimensions`, the regions of datapack dimensions (`dimensions/<namespace>/<name>/region`) are
+/// gathered along with those of the vanilla dimensions
+fn get_region_files_from_world(world_dir: &Path, custom_dimensions: bool) -> Vec<PathBuf> {
     let mut overworld_regions = get_region_dir(world_dir.to_path_buf());
     let nether_regions = get_region_dir(world_dir.join("DIM-1"));
     let the_end_regions = get_region_dir(world_dir.join("DIM1"));
 
     overworld_regions.extend(nether_regions);
     overworld_regions.extend(the_end_regions);
+    if custom_dimensions {
+        for dimension in subdirectories(&world_dir.join("dimensions"))
+            .iter()
+            .flat_map(|dir| subdirectories(dir))
+        {
+            overworld_regions.extend(get_region_dir(dimension));
+        }
+    }
 
     overworld_regions
 }
 
+/// Folders directly inside `directory`, in path order
+fn subdirectories(directory: &Path) -> Vec<PathBuf> {
+    let mut children: Vec<PathBuf> = std::fs::read_dir(directory)
+        .map(|dir| {
+            dir.flatten()
+                // Symbolic links are not followed, they could loop back to a parent
+                .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    children.sort_unstable();
+    children
+}
+
 fn get_region_dir(dimension_directory: PathBuf) -> Vec<PathBuf> {
     get_mca_files(dimension_directory.join("region"))
 }
 
-fn get_mca_files(region_directory: PathBuf) -> Vec<PathBuf> {
+/// Lists the region files of a region-like directory (`region/`, `entities/`, `poi/`), see [`is_mca_file`].
+/// Other files named like `.mca` files, such as `r.0.0.mca.bak`, are skipped with a warning
+pub fn get_mca_files(region_directory: PathBuf) -> Vec<PathBuf> {
     std::fs::read_dir(region_directory)
         .map(|dir| {
             dir.flatten()
                 .map(|entry| entry.path())
-                // mcc files are not supported yet
-                .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("mca"))
+                // mcc files are loaded with their region
+                .filter(|path| {
+                    if looks_like_mca_file(path) && path.is_file() {
+                        warn_not_region_file(path);
+                    }
+                    is_mca_file(path)
+                })
                 .collect::<Vec<_>>()
         })
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_region_file() {
+        let region_file = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/r.-1.-1.mca");
+        let entries =
+            get_region_files(std::slice::from_ref(&region_file), &ScanOptions::default()).unwrap();
+        assert_eq!(entries, vec![region_file]);
+    }
+
+    #[test]
+    fn test_only_region_file_names() {
+        for name in [
+            "r.0.0.mca",
+            "r.-1.-12.mca",
+            "r.3.-4.mca.gz",
+            "region/r.0.0.mca",
+            "backup/r.0.-1.mca.gz",
+        ] {
+            assert!(is_mca_file(Path::new(name)), "{name}");
+            assert!(!looks_like_mca_file(Path::new(name)), "{name}");
+        }
+        for name in [
+            "r.0.0.mca.bak",
+            "r.0.0.mca.tmp.12.0",
+            "copy.mca",
+            "r.0.mca",
+            "r.a.0.mca",
+            "c.0.0.mcc",
+            "region/r.0.0.mcc",
+            "backup/level.dat.gz",
+        ] {
+            assert!(!is_mca_file(Path::new(name)), "{name}");
+        }
+        assert!(looks_like_mca_file(Path::new("region/r.0.0.mca.bak")));
+        assert!(!looks_like_mca_file(Path::new("backups.mca/level.dat")));
+
+        let world = std::env::temp_dir().join(format!("mwt_region_names_{}", std::process::id()));
+        std::fs::create_dir_all(world.join("region")).unwrap();
+        std::fs::write(world.join("level.dat"), b"").unwrap();
+        for name in ["r.0.0.mca", "r.0.0.mca.bak", "r.0.0 copy.mca", ".DS_Store"] {
+            std::fs::write(world.join("region").join(name), b"").unwrap();
+        }
+        let entries =
+            get_region_files(std::slice::from_ref(&world), &ScanOptions::default()).unwrap();
+        assert_eq!(entries, vec![world.join("region/r.0.0.mca")]);
+        let copy = world.join("region/r.0.0 copy.mca");
+        assert!(
+            get_region_files(&[copy], &ScanOptions::default())
+                .unwrap()
+                .is_empty()
+        );
+
+        std::fs::remove_dir_all(&world).ok();
+    }
+
+    #[test]
+    fn test_trial_regions() {
+        let world = std::env::temp_dir().join(format!("mwt_trial_regions_{}", std::process::id()));
+        std::fs::create_dir_all(world.join("region")).unwrap();
+        std::fs::create_dir_all(world.join("DIM-1/region")).unwrap();
+        std::fs::write(world.join("level.dat"), b"").unwrap();
+        for region in [
+            "region/r.1.0.mca",
+            "region/r.0.0.mca",
+            "DIM-1/region/r.0.0.mca",
+        ] {
+            std::fs::write(world.join(region), b"").unwrap();
+        }
+        let trial = |trial_regions| ScanOptions {
+            trial_regions: Some(trial_regions),
+            ..ScanOptions::default()
+        };
+
+        let entries = get_region_files(std::slice::from_ref(&world), &trial(2)).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                world.join("DIM-1/region/r.0.0.mca"),
+                world.join("region/r.0.0.mca")
+            ]
+        );
+        assert_eq!(
+            get_region_files(std::slice::from_ref(&world), &trial(10))
+                .unwrap()
+                .len(),
+            3
+        );
+
+        std::fs::remove_dir_all(&world).ok();
+    }
+
+    #[test]
+    fn test_file_that_is_not_a_region_is_rejected() {
+        let not_a_region = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files/bigtest.nbt");
+        assert!(get_region_files(&[not_a_region], &ScanOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_discover_worlds_up_to_max_depth() {
+        let root = std::env::temp_dir().join(format!("mwt_discover_{}", std::process::id()));
+        for world in ["world", "world_nether", "backups/2024/world"] {
+            std::fs::create_dir_all(root.join(world).join("region")).unwrap();
+            std::fs::write(root.join(world).join("level.dat"), b"").unwrap();
+        }
+
+        assert_eq!(discover_worlds(root.clone(), 0), vec![root.clone()]);
+        assert_eq!(
+            discover_worlds(root.clone(), 1),
+            vec![root.join("world"), root.join("world_nether")]
+        );
+        assert_eq!(
+            discover_worlds(root.clone(), 3),
+            vec![
+                root.join("backups/2024/world"),
+                root.join("world"),
+                root.join("world_nether")
+            ]
+        );
+        // A world's own subfolders are never searched
+        assert_eq!(
+            discover_worlds(root.join("world"), 3),
+            vec![root.join("world")]
+        );
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_detect_worlds() {
+        let root = std::env::temp_dir().join(format!("mwt_detect_worlds_{}", std::process::id()));
+        for world in ["world", "worlds/creative"] {
+            std::fs::create_dir_all(root.join(world).join("region")).unwrap();
+            std::fs::write(root.join(world).join("level.dat"), b"").unwrap();
+        }
+        std::fs::create_dir_all(root.join("world/dimensions/custom/mines/region")).unwrap();
+        for region in [
+            "world/region/r.0.0.mca",
+            "world/dimensions/custom/mines/region/r.0.0.mca",
+        ] {
+            std::fs::write(root.join(region), b"").unwrap();
+        }
+        std::fs::write(root.join("worlds/creative/region/r.1.0.mca"), b"").unwrap();
+        let options = ScanOptions {
+            detect_worlds: true,
+            ..ScanOptions::default()
+        };
+
+        let mut entries = get_region_files(std::slice::from_ref(&root), &options).unwrap();
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![
+                root.join("world/dimensions/custom/mines/region/r.0.0.mca"),
+                root.join("world/region/r.0.0.mca"),
+                root.join("worlds/creative/region/r.1.0.mca"),
+            ]
+        );
+        // Without detection, datapack dimensions are left alone
+        let world = root.join("world");
+        let entries =
+            get_region_files(std::slice::from_ref(&world), &ScanOptions::default()).unwrap();
+        assert_eq!(entries, vec![world.join("region/r.0.0.mca")]);
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
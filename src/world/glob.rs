@@ -0,0 +1,56 @@
+/// Matches `text` against a shell-style glob `pattern`.
+/// Supports `*` (any sequence of characters, including none) and `?` (exactly one character).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen in the pattern and the text index it is currently matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            // Let the last `*` swallow one more character and retry
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal() {
+        assert!(glob_match("r.0.0.mca", "r.0.0.mca"));
+        assert!(!glob_match("r.0.0.mca", "r.0.1.mca"));
+    }
+
+    #[test]
+    fn test_star() {
+        assert!(glob_match("r.0.*.mca", "r.0.-12.mca"));
+        assert!(glob_match("r.0.*.mca", "r.0..mca"));
+        assert!(!glob_match("r.0.*.mca", "r.1.0.mca"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*.bak", "r.0.0.mca.bak"));
+        assert!(glob_match("r.*.*.mca", "r.-1.-1.mca"));
+    }
+
+    #[test]
+    fn test_question_mark() {
+        assert!(glob_match("r.?.0.mca", "r.5.0.mca"));
+        assert!(!glob_match("r.?.0.mca", "r.10.0.mca"));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::nbt::binary_reader::BinaryReader;
+use crate::nbt::parse::parse_tag;
+use crate::nbt::tag::Tag;
+use crate::world::dimension::Dimension;
+use flate2::read::GzDecoder;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Finds the `level.dat` of the world a region file belongs to, in the closest parent directory holding one
+/// (`<world>/region`, `<world>/DIM-1/region` and server worlds such as `world_nether/DIM-1/region`)
+pub fn find_level_dat(region_file: &Path) -> Option<PathBuf> {
+    region_file
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join("level.dat"))
+        .find(|level_dat| level_dat.is_file())
+}
+
+/// Reads the gzipped NBT of a `level.dat` file
+pub fn read_level_dat(level_dat: &Path) -> Option<Tag> {
+    read_gzipped_nbt(level_dat)
+}
+
+/// Reads a gzipped NBT file, like `level.dat` and the files of `data/`
+pub(crate) fn read_gzipped_nbt(path: &Path) -> Option<Tag> {
+    let compressed = std::fs::read(path).ok()?;
+    let mut bytes = Vec::new();
+    GzDecoder::new(&compressed[..])
+        .read_to_end(&mut bytes)
+        .ok()?;
+    parse_tag(&mut BinaryReader::new(&bytes)).ok()
+}
+
+/// Current game time of the world, in ticks (`Data.Time`)
+pub fn read_game_time(level_dat: &Path) -> Option<i64> {
+    read_level_dat(level_dat)?
+        .find_tag("Data")?
+        .find_tag("Time")?
+        .get_long()
+        .copied()
+}
+
+/// World border of a world, as stored in `level.dat` in overworld block coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldBorder {
+    pub center_x: f64,
+    pub center_z: f64,
+    /// Length of the sides of the square border, in blocks
+    pub size: f64,
+}
+
+impl WorldBorder {
+    /// Tells if the whole chunk lies outside of the border in this dimension. The nether border is the overworld one
+    /// scaled down 8 times, like nether coordinates
+    pub fn excludes_chunk(&self, (x, z): (i32, i32), dimension: Dimension) -> bool {
+        let scale = if dimension == Dimension::Nether {
+            8.0
+        } else {
+            1.0
+        };
+        let (center_x, center_z, half_size) = (
+            self.center_x / scale,
+            self.center_z / scale,
+            self.size / scale / 2.0,
+        );
+        let outside = |chunk: i32, center: f64| {
+            let (first_block, end_block) =
+                (f64::from(chunk) * 16.0, f64::from(chunk) * 16.0 + 16.0);
+            end_block <= center - half_size || first_block >= center + half_size
+        };
+        outside(x, center_x) || outside(z, center_z)
+    }
+}
+
+/// World border of the world (`Data.BorderCenterX`, `Data.BorderCenterZ` and `Data.BorderSize`)
+pub fn read_world_border(level_dat: &Path) -> Option<WorldBorder> {
+    let level_dat = read_level_dat(level_dat)?;
+    let data = level_dat.find_tag("Data")?;
+    let read = |name| {
+        data.find_tag(name)
+            .and_then(|tag| tag.get_double())
+            .copied()
+    };
+    Some(WorldBorder {
+        center_x: read("BorderCenterX")?,
+        center_z: read("BorderCenterZ")?,
+        size: read("BorderSize")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::read::GzEncoder;
+
+    #[test]
+    fn test_read_game_time() {
+        let world = std::env::temp_dir().join(format!("mwt_level_dat_{}", std::process::id()));
+        let region_dir = world.join("DIM-1/region");
+        std::fs::create_dir_all(&region_dir).unwrap();
+        let level_dat = Tag::Compound {
+            name: Some(String::new()),
+            value: vec![Tag::Compound {
+                name: Some("Data".to_string()),
+                value: vec![
+                    Tag::Long {
+                        name: Some("Time".to_string()),
+                        value: 123_456,
+                    },
+                    Tag::Double {
+                        name: Some("BorderCenterX".to_string()),
+                        value: 0.5,
+                    },
+                    Tag::Double {
+                        name: Some("BorderCenterZ".to_string()),
+                        value: -32.0,
+                    },
+                    Tag::Double {
+                        name: Some("BorderSize".to_string()),
+                        value: 2000.0,
+                    },
+                ],
+            }],
+        };
+        let mut compressed = Vec::new();
+        GzEncoder::new(&level_dat.to_bytes()[..], Compression::fast())
+            .read_to_end(&mut compressed)
+            .unwrap();
+        std::fs::write(world.join("level.dat"), compressed).unwrap();
+
+        let found = find_level_dat(&region_dir.join("r.0.0.mca")).expect("level.dat must be found");
+        assert_eq!(found, world.join("level.dat"));
+        assert_eq!(read_game_time(&found), Some(123_456));
+        let border = WorldBorder {
+            center_x: 0.5,
+            center_z: -32.0,
+            size: 2000.0,
+        };
+        assert_eq!(read_world_border(&found), Some(border));
+
+        std::fs::remove_dir_all(&world).ok();
+    }
+
+    #[test]
+    fn test_world_border_excludes_chunk() {
+        // Blocks from -100 to 300 on x and from -200 to 200 on z
+        let border = WorldBorder {
+            center_x: 100.0,
+            center_z: 0.0,
+            size: 400.0,
+        };
+        assert!(!border.excludes_chunk((0, 0), Dimension::Overworld));
+        // Blocks -112 to -97 and 288 to 303 straddle the border
+        assert!(!border.excludes_chunk((-7, 0), Dimension::Overworld));
+        assert!(!border.excludes_chunk((18, 0), Dimension::Overworld));
+        assert!(border.excludes_chunk((-8, 0), Dimension::Overworld));
+        assert!(border.excludes_chunk((19, 0), Dimension::Overworld));
+        assert!(border.excludes_chunk((0, 13), Dimension::End));
+        assert!(!border.excludes_chunk((0, 12), Dimension::End));
+
+        // Blocks from -12.5 to 37.5 and from -25 to 25 in the nether
+        assert!(!border.excludes_chunk((2, -2), Dimension::Nether));
+        assert!(border.excludes_chunk((3, 0), Dimension::Nether));
+        assert!(border.excludes_chunk((0, -3), Dimension::Nether));
+    }
+}
@@ -0,0 +1,86 @@
+/// `DataVersion` of every Minecraft release since 1.9, the first version saving one, in increasing order
+const RELEASES: &[(i32, &str)] = &[
+    (169, "1.9"),
+    (175, "1.9.1"),
+    (176, "1.9.2"),
+    (183, "1.9.3"),
+    (184, "1.9.4"),
+    (510, "1.10"),
+    (511, "1.10.1"),
+    (512, "1.10.2"),
+    (819, "1.11"),
+    (921, "1.11.1"),
+    (922, "1.11.2"),
+    (1139, "1.12"),
+    (1241, "1.12.1"),
+    (1343, "1.12.2"),
+    (1519, "1.13"),
+    (1628, "1.13.1"),
+    (1631, "1.13.2"),
+    (1952, "1.14"),
+    (1957, "1.14.1"),
+    (1963, "1.14.2"),
+    (1968, "1.14.3"),
+    (1976, "1.14.4"),
+    (2225, "1.15"),
+    (2227, "1.15.1"),
+    (2230, "1.15.2"),
+    (2566, "1.16"),
+    (2567, "1.16.1"),
+    (2578, "1.16.2"),
+    (2580, "1.16.3"),
+    (2584, "1.16.4"),
+    (2586, "1.16.5"),
+    (2724, "1.17"),
+    (2730, "1.17.1"),
+    (2860, "1.18"),
+    (2865, "1.18.1"),
+    (2975, "1.18.2"),
+    (3105, "1.19"),
+    (3117, "1.19.1"),
+    (3120, "1.19.2"),
+    (3218, "1.19.3"),
+    (3337, "1.19.4"),
+    (3463, "1.20"),
+    (3465, "1.20.1"),
+    (3578, "1.20.2"),
+    (3698, "1.20.3"),
+    (3700, "1.20.4"),
+    (3837, "1.20.5"),
+    (3839, "1.20.6"),
+    (3953, "1.21"),
+    (3955, "1.21.1"),
+    (4080, "1.21.2"),
+    (4082, "1.21.3"),
+    (4189, "1.21.4"),
+    (4325, "1.21.5"),
+    (4435, "1.21.6"),
+    (4438, "1.21.7"),
+    (4440, "1.21.8"),
+];
+
+/// Names the Minecraft version that saves chunks with this `DataVersion`.
+/// Versions between two releases are snapshots or pre-releases of the next one
+pub fn version_name(data_version: i32) -> String {
+    match RELEASES.binary_search_by_key(&data_version, |&(version, _)| version) {
+        Ok(index) => RELEASES[index].1.to_string(),
+        Err(0) => "before 1.9".to_string(),
+        Err(index) if index == RELEASES.len() => format!("newer than {}", RELEASES[index - 1].1),
+        Err(index) => format!("{} snapshot", RELEASES[index].1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_name() {
+        assert!(RELEASES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(version_name(3465), "1.20.1");
+        assert_eq!(version_name(2586), "1.16.5");
+        assert_eq!(version_name(3466), "1.20.2 snapshot");
+        assert_eq!(version_name(100), "before 1.9");
+        assert_eq!(version_name(9999), "newer than 1.21.8");
+    }
+}
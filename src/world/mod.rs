@@ -1,2 +1,10 @@
+pub mod archive;
+pub mod dimension;
+pub mod forced_chunks;
 pub mod get_region_files;
+mod glob;
+pub mod level_dat;
+pub mod minecraft_version;
+pub mod modified_since;
+pub mod scan_options;
 pub mod validate;
@@ -0,0 +1,131 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Parses the cutoff of `--modified-since`, relative to `now`:
+/// - a duration before `now` made of a number and a unit: `90s`, `30m`, `12h`, `2d` or `1w`
+/// - a UTC date `2024-05-01` or date and time `2024-05-01T03:00:00Z`
+/// - a Unix timestamp in seconds
+pub fn parse_cutoff(value: &str, now: SystemTime) -> Result<SystemTime, String> {
+    let invalid = || {
+        format!(
+            "Invalid cutoff {value:?}, expected a duration (e.g. `2d`, `12h`), a UTC date (`2024-05-01`, \
+             `2024-05-01T03:00:00Z`) or a Unix timestamp"
+        )
+    };
+
+    if let Ok(unix_seconds) = value.parse::<u64>() {
+        return Ok(UNIX_EPOCH + Duration::from_secs(unix_seconds));
+    }
+    if let Some(duration) = parse_duration(value) {
+        return now.checked_sub(duration).ok_or_else(invalid);
+    }
+    parse_utc(value)
+        .map(|unix_seconds| UNIX_EPOCH + Duration::from_secs(unix_seconds))
+        .ok_or_else(invalid)
+}
+
+fn parse_duration(value: &str) -> Option<Duration> {
+    let unit_start = value.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = value.split_at(unit_start);
+    let unit_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return None,
+    };
+    amount
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(unit_seconds)
+        .map(Duration::from_secs)
+}
+
+/// Parses `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SSZ` to seconds since the Unix epoch
+fn parse_utc(value: &str) -> Option<u64> {
+    let (date, time) = match value.split_once('T') {
+        Some((date, time)) => (date, Some(time.strip_suffix('Z')?)),
+        None => (value, None),
+    };
+
+    let mut date_parts = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (
+        date_parts.next()??,
+        date_parts.next()??,
+        date_parts.next()??,
+    );
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+    let seconds_of_day = match time {
+        Some(time) => {
+            let mut time_parts = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+            let (hour, minute, second) = (
+                time_parts.next()??,
+                time_parts.next()??,
+                time_parts.next()??,
+            );
+            if !(0..24).contains(&hour) || !(0..60).contains(&minute) || !(0..60).contains(&second)
+            {
+                return None;
+            }
+            hour * 3600 + minute * 60 + second
+        }
+        None => 0,
+    };
+
+    u64::try_from(days_from_civil(year, month, day) * 86_400 + seconds_of_day).ok()
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Converts a proleptic Gregorian date to days since 1970-01-01, counting eras of 400 years from 0000-03-01
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cutoff() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_714_532_461);
+        let seconds = |value| {
+            parse_cutoff(value, now)
+                .map(|cutoff| cutoff.duration_since(UNIX_EPOCH).unwrap().as_secs())
+        };
+
+        assert_eq!(seconds("2d"), Ok(1_714_532_461 - 2 * 86_400));
+        assert_eq!(seconds("90s"), Ok(1_714_532_461 - 90));
+        assert_eq!(seconds("1714000000"), Ok(1_714_000_000));
+        assert_eq!(seconds("2024-05-01"), Ok(1_714_521_600));
+        assert_eq!(seconds("2024-05-01T03:01:01Z"), Ok(1_714_532_461));
+        assert_eq!(seconds("2000-02-29"), Ok(951_782_400));
+
+        for invalid in [
+            "",
+            "2x",
+            "d",
+            "2023-02-29",
+            "2024-13-01",
+            "2024-05-01T03:01:01",
+            "yesterday",
+        ] {
+            assert!(seconds(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::world::dimension::Dimension;
+use crate::world::glob::glob_match;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Restricts which region files are gathered from the worlds.
+#[derive(Default)]
+pub struct ScanOptions {
+    /// If not empty, only region files whose name matches one of these globs are kept
+    pub include_globs: Vec<String>,
+    /// Region files whose name matches one of these globs are skipped
+    pub exclude_globs: Vec<String>,
+    /// How many directory levels below a given path to look for worlds (folders with a `level.dat`).
+    /// 0 means the given paths must be worlds themselves
+    pub max_depth: usize,
+    /// Look for worlds at any depth below the given paths instead of `max_depth`, and also gather the regions of
+    /// their datapack dimensions (`dimensions/<namespace>/<name>/region`)
+    pub detect_worlds: bool,
+    /// If set, only region files last modified after this time are kept
+    pub modified_since: Option<SystemTime>,
+    /// Region files of these dimensions are skipped
+    pub skip_dimensions: Vec<Dimension>,
+    /// If set, only the first region files in path order are processed, to try settings on part of a world
+    pub trial_regions: Option<usize>,
+}
+
+impl ScanOptions {
+    pub fn accepts(&self, region_file: &Path) -> bool {
+        let file_name = region_file
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+
+        let included = self.include_globs.is_empty()
+            || self
+                .include_globs
+                .iter()
+                .any(|pattern| glob_match(pattern, &file_name));
+        let excluded = self
+            .exclude_globs
+            .iter()
+            .any(|pattern| glob_match(pattern, &file_name));
+
+        included
+            && !excluded
+            && !self
+                .skip_dimensions
+                .contains(&Dimension::from_region_path(region_file))
+    }
+
+    /// Tells if the region file was modified after `modified_since`. Files whose modification time
+    /// cannot be read are kept rather than silently skipped
+    pub fn is_recent(&self, region_file: &Path) -> bool {
+        let Some(cutoff) = self.modified_since else {
+            return true;
+        };
+        std::fs::metadata(region_file)
+            .and_then(|metadata| metadata.modified())
+            .map_or(true, |modified| modified > cutoff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_default_accepts_everything() {
+        let options = ScanOptions::default();
+        assert!(options.accepts(Path::new("world/region/r.0.0.mca")));
+    }
+
+    #[test]
+    fn test_include_and_exclude_globs() {
+        let options = ScanOptions {
+            include_globs: vec!["r.0.*.mca".to_string(), "r.1.*.mca".to_string()],
+            exclude_globs: vec!["*.-1.mca".to_string()],
+            ..ScanOptions::default()
+        };
+        assert!(options.accepts(Path::new("world/region/r.0.5.mca")));
+        assert!(options.accepts(Path::new("world/region/r.1.0.mca")));
+        assert!(!options.accepts(Path::new("world/region/r.2.0.mca")));
+        assert!(!options.accepts(Path::new("world/region/r.0.-1.mca")));
+    }
+
+    #[test]
+    fn test_skip_dimensions() {
+        let options = ScanOptions {
+            skip_dimensions: vec![Dimension::Nether],
+            ..ScanOptions::default()
+        };
+        assert!(options.accepts(Path::new("world/region/r.0.0.mca")));
+        assert!(options.accepts(Path::new("world/DIM1/region/r.0.0.mca")));
+        assert!(!options.accepts(Path::new("world/DIM-1/region/r.0.0.mca")));
+        assert!(!options.accepts(Path::new("world/DIM-1/entities/r.0.0.mca")));
+    }
+
+    #[test]
+    fn test_modified_since() {
+        let path =
+            std::env::temp_dir().join(format!("mwt_modified_since_{}.mca", std::process::id()));
+        std::fs::write(&path, []).unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        let since = |cutoff| ScanOptions {
+            modified_since: Some(cutoff),
+            ..ScanOptions::default()
+        };
+
+        assert!(ScanOptions::default().is_recent(&path));
+        assert!(since(modified - Duration::from_secs(60)).is_recent(&path));
+        assert!(!since(modified).is_recent(&path));
+        assert!(since(modified).is_recent(Path::new("missing/r.0.0.mca")));
+
+        std::fs::remove_file(&path).ok();
+    }
+}